@@ -0,0 +1,159 @@
+use crate::interval::Interval;
+use crate::ray::Ray;
+use crate::vector::Vec3;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+    x: Interval,
+    y: Interval,
+    z: Interval,
+}
+
+impl Aabb {
+    pub fn new(x: Interval, y: Interval, z: Interval) -> Self {
+        Self { x, y, z }
+    }
+
+    pub fn empty() -> Self {
+        Self::new(Interval::empty(), Interval::empty(), Interval::empty())
+    }
+
+    pub fn from_points(a: Vec3, b: Vec3) -> Self {
+        // Treat the two points a and b as extrema for the bounding box, so we don't require a
+        // particular minimum/maximum coordinate order.
+        Self::new(
+            Interval::new(a.x().min(b.x()), a.x().max(b.x())),
+            Interval::new(a.y().min(b.y()), a.y().max(b.y())),
+            Interval::new(a.z().min(b.z()), a.z().max(b.z())),
+        )
+    }
+
+    pub fn surrounding(box0: Aabb, box1: Aabb) -> Self {
+        Self::new(
+            Interval::enclosing(box0.x, box1.x),
+            Interval::enclosing(box0.y, box1.y),
+            Interval::enclosing(box0.z, box1.z),
+        )
+    }
+
+    pub fn pad(&self) -> Self {
+        // Adjust the box so that no side is narrower than some delta, padding if necessary.
+        // Flat primitives such as axis-aligned triangles would otherwise get a degenerate box.
+        let delta = 1e-4;
+        let pad_axis = |i: Interval| match i.size() < delta {
+            true => i.expand(delta),
+            false => i,
+        };
+        Self::new(pad_axis(self.x), pad_axis(self.y), pad_axis(self.z))
+    }
+
+    pub fn axis_interval(&self, n: usize) -> Interval {
+        match n {
+            1 => self.y,
+            2 => self.z,
+            _ => self.x,
+        }
+    }
+
+    pub fn min(&self) -> Vec3 {
+        Vec3::from_xyz(self.x.min(), self.y.min(), self.z.min())
+    }
+
+    pub fn max(&self) -> Vec3 {
+        Vec3::from_xyz(self.x.max(), self.y.max(), self.z.max())
+    }
+
+    pub fn centroid(&self) -> Vec3 {
+        0.5 * (self.min() + self.max())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.x.size() < 0. || self.y.size() < 0. || self.z.size() < 0.
+    }
+
+    pub fn longest_axis(&self) -> usize {
+        // Returns the index of the longest axis of the bounding box.
+        if self.x.size() > self.y.size() {
+            if self.x.size() > self.z.size() {
+                0
+            } else {
+                2
+            }
+        } else if self.y.size() > self.z.size() {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        if self.is_empty() {
+            return 0.;
+        }
+        let (dx, dy, dz) = (self.x.size(), self.y.size(), self.z.size());
+        2. * (dx * dy + dy * dz + dz * dx)
+    }
+
+    pub fn hit(&self, r: &Ray, r_t: Interval) -> bool {
+        self.clip(r, r_t).is_some()
+    }
+
+    pub fn clip(&self, r: &Ray, r_t: Interval) -> Option<Interval> {
+        // Part of `r_t` during which the ray is inside the box.
+        let origin = r.origin();
+        let direction = r.direction();
+        let (mut t_min, mut t_max) = (r_t.min(), r_t.max());
+
+        for axis in 0..3 {
+            let ax = self.axis_interval(axis);
+            let adinv = 1.0 / direction.e[axis];
+
+            let t0 = (ax.min() - origin.e[axis]) * adinv;
+            let t1 = (ax.max() - origin.e[axis]) * adinv;
+
+            // NaN (0 * inf) compares false and leaves the bounds untouched.
+            let (t0, t1) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+            if t0 > t_min {
+                t_min = t0;
+            }
+            if t1 < t_max {
+                t_max = t1;
+            }
+
+            if t_max < t_min {
+                return None;
+            }
+        }
+        Some(Interval::new(t_min, t_max))
+    }
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Aabb::empty()
+    }
+}
+
+#[test]
+fn test_aabb() {
+    let bbox = Aabb::from_points(Vec3::from_xyz(1., 1., 1.), Vec3::from_xyz(-1., -2., -1.));
+    assert_eq!(bbox.min(), Vec3::from_xyz(-1., -2., -1.));
+    assert_eq!(bbox.longest_axis(), 1);
+    assert_eq!(bbox.surface_area(), 2. * (6. + 6. + 4.));
+    assert_eq!(Aabb::empty().surface_area(), 0.);
+
+    let flat = Aabb::from_points(Vec3::zeros(), Vec3::from_xyz(1., 1., 0.)).pad();
+    assert!(flat.axis_interval(2).size() > 0.);
+
+    let merged = Aabb::surrounding(bbox, Aabb::empty());
+    assert_eq!(merged.max(), bbox.max());
+
+    let towards = Ray::new(Vec3::from_xyz(0., 0., -5.), Vec3::from_xyz(0., 0., 1.));
+    let away = Ray::new(Vec3::from_xyz(0., 0., -5.), Vec3::from_xyz(0., 0., -1.));
+    let beside = Ray::new(Vec3::from_xyz(2., 0., -5.), Vec3::from_xyz(0., 0., 1.));
+    let r_t = Interval::new(0., f64::INFINITY);
+    assert!(bbox.hit(&towards, r_t));
+    assert!(!bbox.hit(&away, r_t));
+    assert!(!bbox.hit(&beside, r_t));
+    assert!(!bbox.hit(&towards, Interval::new(0., 3.)));
+}
@@ -0,0 +1,235 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::hittable_list::{self, HittableList};
+use crate::interval::Interval;
+use crate::ray::Ray;
+use crate::vector::Vec3;
+
+// Number of buckets used to evaluate the surface area heuristic along each axis.
+const SAH_BUCKETS: usize = 12;
+// Largest leaf allowed when the SAH finds no split cheaper than a leaf. Bigger sets are halved
+// along their longest axis instead.
+const MAX_LEAF_SIZE: usize = 4;
+// Cost of visiting a child node, relative to a single primitive intersection.
+const TRAVERSAL_COST: f64 = 0.125;
+
+#[derive(Clone)]
+pub enum BvhNode {
+    Leaf {
+        bbox: Aabb,
+        objects: Vec<Arc<dyn Hittable>>,
+    },
+    Branch {
+        bbox: Aabb,
+        left: Box<BvhNode>,
+        right: Box<BvhNode>,
+    },
+}
+
+impl BvhNode {
+    pub fn new(list: &HittableList) -> Self {
+        BvhNode::build(list.objects().to_vec())
+    }
+
+    fn build(objects: Vec<Arc<dyn Hittable>>) -> Self {
+        let bbox = objects.iter().fold(Aabb::empty(), |acc, obj| {
+            Aabb::surrounding(acc, obj.bounding_box())
+        });
+        if objects.len() <= 1 {
+            return BvhNode::Leaf { bbox, objects };
+        }
+
+        match BvhNode::split_sah(&objects, &bbox) {
+            Some((axis, extent, split)) => {
+                let (left, right): (Vec<_>, Vec<_>) = objects.into_iter().partition(|obj| {
+                    BvhNode::bucket(obj.bounding_box().centroid().e[axis], extent) < split
+                });
+                BvhNode::Branch {
+                    bbox,
+                    left: Box::new(BvhNode::build(left)),
+                    right: Box::new(BvhNode::build(right)),
+                }
+            }
+            None if objects.len() <= MAX_LEAF_SIZE => BvhNode::Leaf { bbox, objects },
+            None => {
+                // All centroids coincide (or no split beats a leaf), fall back to halving the
+                // primitives along the longest axis so that large leaves can't occur.
+                let mut objects = objects;
+                let axis = bbox.longest_axis();
+                objects.sort_by(|a, b| {
+                    let ca = a.bounding_box().centroid().e[axis];
+                    let cb = b.bounding_box().centroid().e[axis];
+                    ca.total_cmp(&cb)
+                });
+                let right = objects.split_off(objects.len() / 2);
+                BvhNode::Branch {
+                    bbox,
+                    left: Box::new(BvhNode::build(objects)),
+                    right: Box::new(BvhNode::build(right)),
+                }
+            }
+        }
+    }
+
+    fn bucket(centroid: f64, extent: Interval) -> usize {
+        let offset = (centroid - extent.min()) / extent.size();
+        ((offset * SAH_BUCKETS as f64) as usize).min(SAH_BUCKETS - 1)
+    }
+
+    fn split_sah(objects: &[Arc<dyn Hittable>], bbox: &Aabb) -> Option<(usize, Interval, usize)> {
+        // Returns the split (axis, centroid extent, bucket) with the lowest surface area
+        // heuristic cost, or None if keeping all objects in a single leaf is cheaper.
+        let centroid_bounds = objects.iter().fold(Aabb::empty(), |acc, obj| {
+            let c = obj.bounding_box().centroid();
+            Aabb::surrounding(acc, Aabb::from_points(c, c))
+        });
+        let parent_area = bbox.surface_area();
+        let leaf_cost = objects.len() as f64;
+
+        let mut best = None;
+        let mut best_cost = leaf_cost;
+        for axis in 0..3 {
+            let extent = centroid_bounds.axis_interval(axis);
+            if extent.size() <= 0. {
+                continue;
+            }
+
+            let mut counts = [0usize; SAH_BUCKETS];
+            let mut bounds = [Aabb::empty(); SAH_BUCKETS];
+            for obj in objects {
+                let obj_bbox = obj.bounding_box();
+                let b = BvhNode::bucket(obj_bbox.centroid().e[axis], extent);
+                counts[b] += 1;
+                bounds[b] = Aabb::surrounding(bounds[b], obj_bbox);
+            }
+
+            for split in 1..SAH_BUCKETS {
+                let (left_count, left_bbox) = (0..split).fold((0, Aabb::empty()), |acc, b| {
+                    (acc.0 + counts[b], Aabb::surrounding(acc.1, bounds[b]))
+                });
+                let (right_count, right_bbox) = (split..SAH_BUCKETS)
+                    .fold((0, Aabb::empty()), |acc, b| {
+                        (acc.0 + counts[b], Aabb::surrounding(acc.1, bounds[b]))
+                    });
+                if left_count == 0 || right_count == 0 {
+                    continue;
+                }
+
+                let cost = TRAVERSAL_COST
+                    + (left_count as f64 * left_bbox.surface_area()
+                        + right_count as f64 * right_bbox.surface_area())
+                        / parent_area;
+                if cost < best_cost {
+                    best_cost = cost;
+                    best = Some((axis, extent, split));
+                }
+            }
+        }
+        best
+    }
+}
+
+impl Hittable for BvhNode {
+    fn hit(&self, r: &Ray, r_t: Interval, rec: &mut HitRecord) -> bool {
+        if !self.bounding_box().hit(r, r_t) {
+            return false;
+        }
+
+        match self {
+            BvhNode::Leaf { objects, .. } => {
+                let mut hit_anything = false;
+                let mut closest_so_far = r_t.max();
+                for obj in objects.iter() {
+                    if obj.hit(r, Interval::new(r_t.min(), closest_so_far), rec) {
+                        hit_anything = true;
+                        closest_so_far = rec.t;
+                    }
+                }
+                hit_anything
+            }
+            BvhNode::Branch { left, right, .. } => {
+                let hit_left = left.hit(r, r_t, rec);
+                let max = if hit_left { rec.t } else { r_t.max() };
+                let hit_right = right.hit(r, Interval::new(r_t.min(), max), rec);
+                hit_left || hit_right
+            }
+        }
+    }
+
+    fn transmittance(&self, r: &Ray, r_t: Interval) -> f64 {
+        if !self.bounding_box().hit(r, r_t) {
+            return 1.;
+        }
+
+        match self {
+            BvhNode::Leaf { objects, .. } => hittable_list::transmittance(objects, r, r_t),
+            BvhNode::Branch { left, right, .. } => match left.transmittance(r, r_t) {
+                t if t <= 0. => 0.,
+                t => t * right.transmittance(r, r_t),
+            },
+        }
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3, r_t: Interval) -> f64 {
+        // Sum over the lights, only those whose boxes the direction passes through can add to
+        // it.
+        if !self.bounding_box().hit(&Ray::new(origin, direction), r_t) {
+            return 0.;
+        }
+
+        match self {
+            BvhNode::Leaf { objects, .. } => objects
+                .iter()
+                .map(|obj| obj.pdf_value(origin, direction, r_t))
+                .sum(),
+            BvhNode::Branch { left, right, .. } => {
+                left.pdf_value(origin, direction, r_t) + right.pdf_value(origin, direction, r_t)
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        match self {
+            BvhNode::Leaf { bbox, .. } => *bbox,
+            BvhNode::Branch { bbox, .. } => *bbox,
+        }
+    }
+}
+
+#[test]
+fn test_bvh() {
+    use crate::material::Material;
+    use crate::sphere::Sphere;
+    use crate::vector::Vec3;
+
+    let mut world = HittableList::new();
+    let mat = Material::lambertian(Vec3::zeros());
+    for a in -5..5 {
+        for b in -5..5 {
+            let center = Vec3::from_xyz(a as f64, 0.3 * b as f64, b as f64);
+            world.add(Sphere::new(
+                center,
+                0.1 + 0.03 * (a + 5) as f64,
+                mat.clone(),
+            ));
+        }
+    }
+    let bvh = BvhNode::new(&world);
+    assert_eq!(bvh.bounding_box().min(), world.bounding_box().min());
+    assert_eq!(bvh.bounding_box().max(), world.bounding_box().max());
+
+    for _ in 0..1000 {
+        let r = Ray::new(Vec3::random_range(-10., 10.), Vec3::random_range(-1., 1.));
+        let r_t = Interval::new(1e-3, f64::INFINITY);
+        let (mut rec_list, mut rec_bvh) = (HitRecord::new(), HitRecord::new());
+        let hit_list = world.hit(&r, r_t, &mut rec_list);
+        let hit_bvh = bvh.hit(&r, r_t, &mut rec_bvh);
+        assert_eq!(hit_list, hit_bvh);
+        if hit_list {
+            assert_eq!(rec_list.t, rec_bvh.t);
+            assert_eq!(rec_list.p, rec_bvh.p);
+        }
+    }
+}
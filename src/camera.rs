@@ -1,20 +1,23 @@
-use std::f64::INFINITY;
-use std::io::{self, Write};
+use std::sync::Arc;
 
 use derive_builder::Builder;
 use indicatif::{ProgressIterator, ProgressStyle};
 use rayon::prelude::*;
 
+use crate::environment::EnvironmentMap;
 use crate::hittable::{HitRecord, Hittable};
-use crate::hittable_list::HittableList;
+use crate::image::Image;
 use crate::interval::Interval;
+use crate::light::{power_heuristic, LightList};
 use crate::material::Scatterable;
+use crate::medium::Fog;
+use crate::random::{self, random_f64};
 use crate::ray::Ray;
+use crate::spectrum;
+use crate::tone_map::ToneMap;
 use crate::vector::Vec3;
 
-use crate::color::write_color;
-
-#[derive(Builder, Clone, Copy)]
+#[derive(Builder, Clone)]
 #[builder(setter(skip))]
 pub struct Camera {
     #[builder(setter)]
@@ -29,7 +32,9 @@ pub struct Camera {
     #[builder(setter)]
     samples_per_pixel: usize, // Count of random samples for each pixel
     #[builder(setter)]
-    max_depth: usize, // Maximum depth of ray recursion
+    max_depth: usize, // Maximum number of bounces of a path
+    #[builder(setter, default = "3")]
+    min_depth: usize, // Bounces before Russian roulette may terminate a path
     #[builder(setter)]
     vfov: f64, // Vertical view angle (field of view)
     #[builder(setter)]
@@ -41,12 +46,28 @@ pub struct Camera {
     u: Vec3,                 // Camera u vector
     v: Vec3,                 // Camera v vector
     w: Vec3,                 // Camera w vector
+    #[builder(setter, default)]
+    seed: u64, // Seed of the per-pixel random number generators
     #[builder(setter)]
     defocus_angle: f64, // Variation angle of rays through each pixel
     #[builder(setter)]
     focus_dist: f64, // Distance from camera lookfrom point to plane of perfect focus
     defocus_disk_u: Vec3,    // Defocus disk horizontal radius
     defocus_disk_v: Vec3,    // Defocus disk vertical radius
+    #[builder(setter(strip_option), default)]
+    background: Option<Vec3>, // Radiance of rays escaping the scene, the sky gradient if unset
+    #[builder(setter, default)]
+    exposure: f64, // Exposure compensation in stops (EV)
+    #[builder(setter, default)]
+    tone_map: ToneMap, // Tone mapping operator for display-referred output
+    #[builder(setter, default = "4.0")]
+    white_point: f64, // Smallest radiance mapped to white by extended Reinhard
+    #[builder(setter, default)]
+    spectral: bool, // Trace one sampled wavelength per path instead of RGB
+    #[builder(setter(strip_option), default)]
+    fog: Option<Fog>, // Homogeneous medium filling the bounds of the scene
+    #[builder(setter(strip_option), default)]
+    environment: Option<Arc<EnvironmentMap>>, // Image lighting the scene, replaces `background`
 }
 
 impl CameraBuilder {
@@ -57,7 +78,8 @@ impl CameraBuilder {
 
 impl Camera {
     pub fn initialize(&mut self) -> Camera {
-        self.pixel_sample_scale = 1.0 / self.samples_per_pixel as f64;
+        // Exposure is folded into the sample averaging so HDR output is scaled as well.
+        self.pixel_sample_scale = 2f64.powf(self.exposure) / self.samples_per_pixel as f64;
 
         // let defocus_angle = 10.0;
         // let focus_dist = 3.4;
@@ -65,7 +87,7 @@ impl Camera {
         // let look_at = Vec3::from_xyz(0., 0., -1.);
         // let vup = Vec3::from_xyz(0., 1., 0.);
 
-        self.center = self.look_from.clone();
+        self.center = self.look_from;
 
         // Determine viewport dimensions.
         let theta = self.vfov.to_radians();
@@ -98,65 +120,266 @@ impl Camera {
         self.pixel00_loc = pixel00_loc;
 
         // Calculate the camera defocus disk basis vectors.
-        let defocus_radius = self.focus_dist * (self.defocus_angle / 2.0 as f64).to_radians().tan();
+        let defocus_radius = self.focus_dist * (self.defocus_angle / 2.0).to_radians().tan();
         self.defocus_disk_u = u * defocus_radius;
         self.defocus_disk_v = v * defocus_radius;
-        *self
+        self.clone()
     }
 
-    pub fn render(&self, world: &HittableList) {
+    pub fn render<H: Hittable + Sync>(&self, world: &H, lights: &LightList) -> Image {
         let style = ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}]) {pos}/{len} Scanlines ({eta})").unwrap().progress_chars("#>-");
-        println!("P3\n{} {}\n255", self.image_width, self.image_height);
+        let mut image = Image::new(self.image_width, self.image_height);
         for j in (0..self.image_height).progress_with_style(style) {
             // eprint!("\rScanlines remaining: {}", self.image_height - j);
-            io::stdout().flush().unwrap();
             let pixels: Vec<Vec3> = (0..self.image_width)
                 .into_par_iter()
                 .map(|i| {
-                    let pixel_color = self.get_pixel_color(i as f64, j as f64, world);
+                    random::seed_for(self.seed, (j * self.image_width + i) as u64);
+                    let pixel_color = self.get_pixel_color(i as f64, j as f64, world, lights);
                     self.pixel_sample_scale * pixel_color
                 })
                 .collect();
-            for pixel in pixels {
-                write_color(pixel);
-            }
+            image.set_row(j, &pixels);
         }
         eprintln!("\nDone.");
+        image
+    }
+
+    pub fn apply_tone_map(&self, image: &mut Image) {
+        // Maps a rendered HDR framebuffer into the displayable range.
+        image.map_pixels(|pixel| self.tone_map.apply(pixel, self.white_point));
     }
 
-    pub fn get_pixel_color(&self, i: f64, j: f64, world: &HittableList) -> Vec3 {
+    pub fn get_pixel_color<H: Hittable>(
+        &self,
+        i: f64,
+        j: f64,
+        world: &H,
+        lights: &LightList,
+    ) -> Vec3 {
         let mut pixel_color = Vec3::zeros();
         for _sample in 0..self.samples_per_pixel {
-            let ray = self.get_ray(i, j);
-            pixel_color += self.ray_color(&ray, self.max_depth, world);
+            let mut ray = self.get_ray(i, j);
+            if self.spectral {
+                ray = ray.with_wavelength(Some(spectrum::sample_wavelength(random_f64())));
+            }
+            pixel_color += self.ray_color(&ray, world, lights);
         }
         pixel_color
     }
 
-    fn ray_color(&self, r: &Ray, depth: usize, world: &HittableList) -> Vec3 {
-        if depth == 0 {
+    fn ray_color<H: Hittable>(&self, r: &Ray, world: &H, lights: &LightList) -> Vec3 {
+        // Iterative path tracer carrying the throughput of the path so far.
+        let mut color = Vec3::zeros();
+        let mut throughput = Vec3::ones();
+        let mut ray = *r;
+        let mut scattering_pdf: Option<f64> = None; // Density that chose `ray`, None if delta
+        let mut interior: Option<Vec3> = None; // Absorption of the dielectric `ray` is inside
+        let wavelength = r.wavelength();
+
+        for depth in 0..self.max_depth {
+            let mut rec = HitRecord::new();
+            let mut hit = world.hit(&ray, Interval::new(1e-3, f64::INFINITY), &mut rec);
+            // Outside of dielectrics, the ray may scatter in the fog before reaching the hit or
+            // escaping the scene.
+            if let (Some(fog), None) = (self.fog, interior) {
+                let t_max = if hit { rec.t } else { f64::INFINITY };
+                if let Some(t) = fog.sample(&ray, world.bounding_box(), t_max) {
+                    fog.collide(&mut rec, &ray, t);
+                    hit = true;
+                }
+            }
+            if !hit {
+                let mut background = at_wavelength(self.background_color(&ray), wavelength);
+                if let (Some(environment), Some(scattering_pdf)) =
+                    (&self.environment, scattering_pdf)
+                {
+                    // Like lights, the environment was also reachable by light sampling.
+                    let light_pdf =
+                        self.environment_share(lights) * environment.pdf(ray.direction());
+                    background = background * power_heuristic(scattering_pdf, light_pdf);
+                }
+                color += throughput * background;
+                break;
+            }
+            // Beer-Lambert attenuation along the segment inside an absorbing dielectric.
+            if let Some(absorption) = interior.filter(|absorption| !absorption.near_zero()) {
+                let distance = rec.t * ray.direction().length();
+                let absorption = at_wavelength(absorption, wavelength);
+                throughput = throughput * Vec3::new(absorption.e.map(|a| (-a * distance).exp()));
+            }
+
+            // Bump and normal maps perturb the shading normal before any shading.
+            let mat = rec.mat.clone();
+            mat.perturb_normal(&mut rec);
+            let mut color_from_emission = at_wavelength(mat.emitted(&ray, &rec), wavelength);
+            if let (Some(scattering_pdf), false) = (scattering_pdf, color_from_emission.near_zero())
+            {
+                // The light was also reachable by light sampling at the previous hit, only
+                // count its MIS share. Lights are matched by the distance of this hit. Most hits
+                // emit nothing and skip the lookup.
+                let r_t = Interval::new(rec.t, rec.t).expand(1e-6 * rec.t);
+                let light_pdf = (1. - self.environment_share(lights))
+                    * lights.pdf_value(ray.origin(), ray.direction(), r_t);
+                color_from_emission =
+                    color_from_emission * power_heuristic(scattering_pdf, light_pdf);
+            }
+            color += throughput * color_from_emission;
+
+            let Some(sample) = mat.sample(&ray, &rec) else {
+                break;
+            };
+            // Delta distributions can't be reached by light sampling.
+            scattering_pdf = match sample.is_delta {
+                true => None,
+                false => {
+                    color += throughput * self.sample_lights(&ray, &rec, world, lights);
+                    Some(sample.pdf)
+                }
+            };
+            throughput = throughput * at_wavelength(sample.weight(), wavelength);
+            // Transmission through the surface enters its interior through the front face and
+            // leaves it through the back face. Shading normals may tilt away from the surface,
+            // so the side is decided by the geometric normal.
+            if sample.direction.dot(rec.geometric_normal) < 0. {
+                interior = match rec.front_face {
+                    true => Some(mat.absorption()),
+                    false => None,
+                };
+            }
+
+            // Russian roulette: continue dim paths with a probability proportional to their
+            // throughput and compensate the survivors, which keeps the estimate unbiased.
+            if depth + 1 >= self.min_depth {
+                let survival = throughput
+                    .x()
+                    .max(throughput.y())
+                    .max(throughput.z())
+                    .min(1.);
+                if random_f64() >= survival {
+                    break;
+                }
+                throughput = throughput / survival;
+            }
+            ray = Ray::new(rec.p, sample.direction).with_wavelength(wavelength);
+        }
+        match wavelength {
+            Some(lambda) => spectrum::spectrum_to_rgb(color.x(), lambda),
+            None => color,
+        }
+    }
+
+    fn sample_lights<H: Hittable>(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        world: &H,
+        lights: &LightList,
+    ) -> Vec3 {
+        // Next-event estimation: direct light from one sampled light point, weighted by MIS
+        // against finding the same point by scattering.
+        let environment_share = self.environment_share(lights);
+        if let Some(environment) = &self.environment {
+            if random_f64() < environment_share {
+                return self.sample_environment(environment, environment_share, r_in, rec, world);
+            }
+        }
+        let Some((light, mut sample)) = lights.sample(rec.p) else {
+            return Vec3::zeros();
+        };
+        sample.pdf *= 1. - environment_share;
+        let to_light = sample.p - rec.p;
+        let distance = to_light.length();
+        let direction = to_light / distance;
+
+        let f = rec.mat.eval(r_in, rec, direction);
+        if f.near_zero() {
+            return Vec3::zeros();
+        }
+        let shadow_ray = Ray::new(rec.p, direction).with_wavelength(r_in.wavelength());
+        let r_t = Interval::new(1e-3, distance - 1e-3);
+        let transmittance = self.transmittance(&shadow_ray, r_t, world);
+        if transmittance <= 0. {
+            return Vec3::zeros();
+        }
+        let mut light_rec = HitRecord::new();
+        let r_t = Interval::new(distance, distance).expand(1e-3);
+        if !light.hit(&shadow_ray, r_t, &mut light_rec) {
             return Vec3::zeros();
         }
 
-        let mut rec = HitRecord::new();
-        let color = match world.hit(r, Interval::new(1e-3, INFINITY), &mut rec) {
-            true => {
-                let mut scattered = Ray::new(Vec3::zeros(), Vec3::zeros());
-                let mut attenuation = Vec3::ones();
-                let mat = rec.mat.clone();
-                if mat.scatter(&r, &mut rec, &mut attenuation, &mut scattered) {
-                    attenuation * self.ray_color(&scattered, depth - 1, world)
-                } else {
-                    Vec3::zeros()
-                }
+        let emitted = light_rec.mat.emitted(&shadow_ray, &light_rec);
+        let weight = power_heuristic(sample.pdf, rec.mat.pdf(r_in, rec, direction));
+        let wavelength = r_in.wavelength();
+        at_wavelength(f, wavelength)
+            * at_wavelength(emitted, wavelength)
+            * (transmittance * weight / sample.pdf)
+    }
+
+    fn sample_environment<H: Hittable>(
+        &self,
+        environment: &EnvironmentMap,
+        share: f64,
+        r_in: &Ray,
+        rec: &HitRecord,
+        world: &H,
+    ) -> Vec3 {
+        // Next-event estimation towards a direction of the environment chosen with `share`.
+        let Some((direction, pdf)) = environment.sample() else {
+            return Vec3::zeros();
+        };
+        let pdf = pdf * share;
+        let f = rec.mat.eval(r_in, rec, direction);
+        if f.near_zero() {
+            return Vec3::zeros();
+        }
+        let shadow_ray = Ray::new(rec.p, direction).with_wavelength(r_in.wavelength());
+        let transmittance =
+            self.transmittance(&shadow_ray, Interval::new(1e-3, f64::INFINITY), world);
+        if transmittance <= 0. {
+            return Vec3::zeros();
+        }
+
+        let weight = power_heuristic(pdf, rec.mat.pdf(r_in, rec, direction));
+        let wavelength = r_in.wavelength();
+        at_wavelength(f, wavelength)
+            * at_wavelength(environment.radiance(direction), wavelength)
+            * (transmittance * weight / pdf)
+    }
+
+    fn environment_share(&self, lights: &LightList) -> f64 {
+        // Probability of sampling the environment rather than the lights of the scene.
+        match (&self.environment, lights.is_empty()) {
+            (None, _) => 0.,
+            (Some(_), true) => 1.,
+            (Some(_), false) => 0.5,
+        }
+    }
+
+    fn transmittance<H: Hittable>(&self, shadow_ray: &Ray, r_t: Interval, world: &H) -> f64 {
+        // Fraction of light reaching the origin of `shadow_ray` from its end, through the
+        // objects and the fog.
+        let transmittance = world.transmittance(shadow_ray, r_t);
+        match self.fog {
+            Some(fog) if transmittance > 0. => {
+                transmittance * fog.transmittance(shadow_ray, world.bounding_box(), r_t)
             }
-            false => {
+            _ => transmittance,
+        }
+    }
+
+    fn background_color(&self, r: &Ray) -> Vec3 {
+        if let Some(environment) = &self.environment {
+            return environment.radiance(r.direction());
+        }
+        match self.background {
+            Some(background) => background,
+            None => {
                 let unit_direction = r.direction().unit_vector();
                 let t = 0.5 * (unit_direction.y() + 1.0);
                 Vec3::ones() * (1.0 - t) + Vec3::from_rgb(0.5, 0.7, 1.0) * t
             }
-        };
-        color
+        }
     }
 
     fn get_ray(&self, i: f64, j: f64) -> Ray {
@@ -174,7 +397,7 @@ impl Camera {
     }
 
     fn sample_square(&self) -> Vec3 {
-        Vec3::from_xyz(rand::random::<f64>() - 0.5, rand::random::<f64>() + 0.5, 0.)
+        Vec3::from_xyz(random_f64() - 0.5, random_f64() + 0.5, 0.)
     }
 
     fn defocus_disk_sample(&self) -> Vec3 {
@@ -183,3 +406,81 @@ impl Camera {
         self.center + (p.x() * self.defocus_disk_u) + (p.y() * self.defocus_disk_v)
     }
 }
+
+fn at_wavelength(rgb: Vec3, wavelength: Option<f64>) -> Vec3 {
+    // Spectral paths upsample RGB quantities and carry their value at the path's wavelength in
+    // every channel.
+    match wavelength {
+        Some(lambda) => Vec3::ones() * spectrum::rgb_to_spectrum(rgb, lambda),
+        None => rgb,
+    }
+}
+
+#[test]
+fn test_camera() {
+    use crate::hittable_list::HittableList;
+    use crate::material::{Dielectric, Material};
+    use crate::quad::cuboid;
+    use crate::sphere::Sphere;
+
+    let camera = CameraBuilder::default()
+        .image_size(1, 1)
+        .samples_per_pixel(1)
+        .max_depth(10)
+        .min_depth(10)
+        .vfov(90.)
+        .look_from(Vec3::zeros())
+        .look_at(Vec3::from_xyz(0., 0., -1.))
+        .vup(Vec3::from_xyz(0., 1., 0.))
+        .defocus_angle(0.)
+        .focus_dist(1.)
+        .background(Vec3::ones())
+        .build()
+        .unwrap();
+    let lights = LightList::default();
+
+    // Index-matched glass 2 units thick in front of a white background: at normal incidence
+    // it never reflects, and only the segment inside of it is absorbed.
+    let glass = Dielectric::new(1.).with_transmittance(Vec3::from_rgb(0.5, 0.25, 1.), 2.);
+    let mut world = HittableList::new();
+    world.add(cuboid(
+        Vec3::from_xyz(-1., -1., -3.),
+        Vec3::from_xyz(1., 1., -1.),
+        0.,
+        Material::Dielectric(glass),
+    ));
+    let r = Ray::new(Vec3::from_xyz(0.3, 0.2, 0.), Vec3::from_xyz(0., 0., -1.));
+    let color = camera.ray_color(&r, &world, &lights);
+    assert!((color - Vec3::from_rgb(0.5, 0.25, 1.)).length() < 1e-9);
+
+    // Furnace: a small light at the center of a closed diffuse sphere. Every wall point gets
+    // the same irradiance, so the walls have the radiance L = a Le k / (1 - a (1 - k)), with k
+    // the fraction of the cosine-weighted hemisphere covered by the light. Russian roulette
+    // from the first bounce must not bias it.
+    let (albedo, emit, radius) = (0.8, 25., 0.1);
+    let mut world = HittableList::new();
+    world.add(Sphere::new(
+        Vec3::zeros(),
+        1.,
+        Material::lambertian(Vec3::ones() * albedo),
+    ));
+    world.add(Sphere::new(
+        Vec3::zeros(),
+        radius,
+        Material::diffuse_light(Vec3::ones() * emit),
+    ));
+    let lights = LightList::new(&world);
+    let mut camera = camera.clone();
+    camera.max_depth = 200;
+    camera.min_depth = 0;
+    let k = radius * radius;
+    let expected = albedo * emit * k / (1. - albedo * (1. - k));
+    let r = Ray::new(Vec3::from_xyz(0., 0.5, 0.), Vec3::from_xyz(1., 0., 0.));
+    random::seed_for(7, 0);
+    let n = 20000;
+    let mean = (0..n)
+        .map(|_| camera.ray_color(&r, &world, &lights).x())
+        .sum::<f64>()
+        / n as f64;
+    assert!((mean - expected).abs() < 0.02 * expected);
+}
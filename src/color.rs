@@ -1,23 +1,40 @@
 use crate::interval::Interval;
 use crate::vector::Vec3;
 
-pub fn linear_to_gamma(linear_component: f64) -> f64 {
-    match linear_component > 0. {
-        true => linear_component.sqrt(),
-        false => 0.,
+pub fn linear_to_srgb(linear_component: f64) -> f64 {
+    // Piecewise sRGB transfer function (OETF).
+    match linear_component > 0.0031308 {
+        true => 1.055 * linear_component.powf(1. / 2.4) - 0.055,
+        false => 12.92 * linear_component.max(0.),
     }
 }
 
-pub fn write_color(color: Vec3) {
-    let intensity = Interval::new(0.000, 0.999);
+pub fn srgb_to_linear(srgb_component: f64) -> f64 {
+    // Inverse of `linear_to_srgb` (EOTF), used to decode 8 and 16-bit textures.
+    match srgb_component > 0.04045 {
+        true => ((srgb_component + 0.055) / 1.055).powf(2.4),
+        false => srgb_component.max(0.) / 12.92,
+    }
+}
 
-    let r = linear_to_gamma(color.x());
-    let g = linear_to_gamma(color.y());
-    let b = linear_to_gamma(color.z());
+pub fn encode_color(color: Vec3, max_value: u16) -> [u16; 3] {
+    // sRGB-encode a linear color and quantize it to integers in [0, max_value].
+    let intensity = Interval::new(0.000, 1.000);
+    let scale = max_value as f64 + 0.999;
 
-    let ir = (255.999 * intensity.clamp(r)) as i32;
-    let ig = (255.999 * intensity.clamp(g)) as i32;
-    let ib = (255.999 * intensity.clamp(b)) as i32;
+    color
+        .e
+        .map(|component| (scale * intensity.clamp(linear_to_srgb(component))) as u16)
+}
 
-    println!("{} {} {}", ir, ig, ib);
+#[test]
+fn test_encode_color() {
+    let color = Vec3::from_rgb(0.25, -1., 4.);
+    assert_eq!(encode_color(color, 255), [137, 0, 255]);
+    assert_eq!(encode_color(color, 65535), [35199, 0, 65535]);
+    assert!((linear_to_srgb(0.001) - 0.01292).abs() < 1e-9);
+    assert!((linear_to_srgb(0.5) - 0.735357).abs() < 1e-6);
+    for x in [0.001, 0.2, 0.5, 1.] {
+        assert!((srgb_to_linear(linear_to_srgb(x)) - x).abs() < 1e-9);
+    }
 }
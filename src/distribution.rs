@@ -0,0 +1,134 @@
+// Piecewise-constant distribution over [0, 1) proportional to `func`, sampled by inverting its
+// cumulative distribution.
+#[derive(Debug, Clone)]
+pub struct Distribution1D {
+    func: Vec<f64>,
+    cdf: Vec<f64>, // One more entry than `func`, from 0 to 1
+    integral: f64, // Integral of the function over [0, 1)
+}
+
+impl Distribution1D {
+    pub fn new(func: Vec<f64>) -> Self {
+        assert!(!func.is_empty(), "empty distribution");
+        let func: Vec<f64> = func.into_iter().map(|f| f.max(0.)).collect();
+        let n = func.len() as f64;
+        let mut cdf = vec![0.; func.len() + 1];
+        for i in 0..func.len() {
+            cdf[i + 1] = cdf[i] + func[i] / n;
+        }
+        let integral = cdf[func.len()];
+        for (i, c) in cdf.iter_mut().enumerate() {
+            // A function that is zero everywhere is sampled uniformly.
+            *c = match integral > 0. {
+                true => *c / integral,
+                false => i as f64 / n,
+            };
+        }
+        Self {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    pub fn count(&self) -> usize {
+        self.func.len()
+    }
+
+    pub fn sample(&self, u: f64) -> (f64, f64, usize) {
+        // Returns the sampled point, its density and the index of its segment.
+        let offset = (self.cdf.partition_point(|&c| c <= u).max(1) - 1).min(self.count() - 1);
+        let width = self.cdf[offset + 1] - self.cdf[offset];
+        let du = match width > 0. {
+            true => (u - self.cdf[offset]) / width,
+            false => 0.,
+        };
+        let x = (offset as f64 + du) / self.count() as f64;
+        (x, self.pdf_at(offset), offset)
+    }
+
+    pub fn pdf(&self, x: f64) -> f64 {
+        let offset = ((x * self.count() as f64) as usize).min(self.count() - 1);
+        self.pdf_at(offset)
+    }
+
+    fn pdf_at(&self, offset: usize) -> f64 {
+        match self.integral > 0. {
+            true => self.func[offset] / self.integral,
+            false => 1.,
+        }
+    }
+}
+
+// Piecewise-constant distribution over [0, 1)^2 proportional to a row-major grid of values:
+// a row is chosen by the marginal distribution, then a column within it.
+#[derive(Debug, Clone)]
+pub struct Distribution2D {
+    conditional: Vec<Distribution1D>, // One per row
+    marginal: Distribution1D,         // Of the rows
+}
+
+impl Distribution2D {
+    pub fn new(func: &[f64], width: usize, height: usize) -> Self {
+        assert_eq!(func.len(), width * height);
+        let conditional: Vec<Distribution1D> = func
+            .chunks_exact(width)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(conditional.iter().map(|d| d.integral()).collect());
+        Self {
+            conditional,
+            marginal,
+        }
+    }
+
+    pub fn sample(&self, u: f64, v: f64) -> ([f64; 2], f64) {
+        // Returns the sampled point and its density.
+        let (y, pdf_y, row) = self.marginal.sample(v);
+        let (x, pdf_x, _) = self.conditional[row].sample(u);
+        ([x, y], pdf_x * pdf_y)
+    }
+
+    pub fn pdf(&self, x: f64, y: f64) -> f64 {
+        let row = ((y * self.marginal.count() as f64) as usize).min(self.marginal.count() - 1);
+        self.marginal.pdf(y) * self.conditional[row].pdf(x)
+    }
+}
+
+#[test]
+fn test_distribution() {
+    use crate::random::random_f64;
+
+    let d = Distribution1D::new(vec![1., 3., 0., 4.]);
+    assert_eq!(d.integral(), 2.);
+    assert_eq!(d.sample(0.), (0., 0.5, 0));
+    let (x, pdf, offset) = d.sample(0.3125);
+    assert_eq!((x, pdf, offset), (0.375, 1.5, 1));
+    // The empty segment is skipped.
+    assert_eq!(d.sample(0.5).2, 3);
+    assert_eq!(d.pdf(0.6), 0.);
+    assert_eq!(
+        Distribution1D::new(vec![0., 0.]).sample(0.75),
+        (0.75, 1., 1)
+    );
+
+    // Samples land in each cell proportionally to its value, with the density of the cell.
+    let func = [0., 1., 2., 3., 4., 5.];
+    let d = Distribution2D::new(&func, 3, 2);
+    let n = 30000;
+    let mut counts = [0.; 6];
+    for _ in 0..n {
+        let ([x, y], pdf) = d.sample(random_f64(), random_f64());
+        let cell = (y * 2.) as usize * 3 + (x * 3.) as usize;
+        counts[cell] += 1.;
+        assert!((pdf - d.pdf(x, y)).abs() < 1e-12);
+        assert!((pdf - func[cell] / 2.5).abs() < 1e-12);
+    }
+    for (count, f) in counts.iter().zip(func) {
+        assert!((count / n as f64 - f / 15.).abs() < 0.01);
+    }
+}
@@ -0,0 +1,146 @@
+use std::f64::consts::PI;
+use std::io;
+use std::path::Path;
+
+use crate::distribution::Distribution2D;
+use crate::image::Image;
+use crate::load_error::LoadError;
+use crate::random::random_f64;
+use crate::tone_map::luminance;
+use crate::vector::Vec3;
+
+// Distant light from an equirectangular (latitude-longitude) image surrounding the scene. The
+// top row is straight up (+y) and the center of the image looks towards -z, before `rotation`.
+// Directions are sampled proportionally to the luminance of the pixels.
+#[derive(Debug, Clone)]
+pub struct EnvironmentMap {
+    image: Image,
+    rotation: f64,  // Around the vertical axis, in radians
+    intensity: f64, // Scales the radiance of the image
+    distribution: Distribution2D,
+}
+
+impl EnvironmentMap {
+    pub fn new(image: Image, rotation: f64, intensity: f64) -> Self {
+        // Rows near the poles cover less solid angle, weighting by sin(theta) accounts for it.
+        let (width, height) = (image.width(), image.height());
+        let mut func = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let sin_theta = (PI * (y as f64 + 0.5) / height as f64).sin();
+            func.extend((0..width).map(|x| luminance(image.get(x, y)) * sin_theta));
+        }
+        Self {
+            distribution: Distribution2D::new(&func, width, height),
+            image,
+            rotation: rotation.to_radians(),
+            intensity,
+        }
+    }
+
+    pub fn load(path: &Path, rotation: f64, intensity: f64) -> Result<Self, LoadError> {
+        let image = Image::load(path, true)?;
+        if image.width() == 0 || image.height() == 0 {
+            return Err(LoadError::Io(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "empty environment map",
+            ))
+            .in_file(path));
+        }
+        Ok(EnvironmentMap::new(image, rotation, intensity))
+    }
+
+    fn direction_to_uv(&self, direction: Vec3) -> (f64, f64, f64) {
+        // Image coordinates of `direction` and the sine of its polar angle.
+        let d = direction.unit_vector();
+        let theta = d.y().clamp(-1., 1.).acos();
+        let phi = d.x().atan2(-d.z()) - self.rotation;
+        let u = (phi / (2. * PI) + 0.5).rem_euclid(1.);
+        (u, theta / PI, theta.sin())
+    }
+
+    fn uv_to_direction(&self, u: f64, v: f64) -> (Vec3, f64) {
+        let (theta, phi) = (PI * v, 2. * PI * (u - 0.5) + self.rotation);
+        let direction = Vec3::from_xyz(
+            theta.sin() * phi.sin(),
+            theta.cos(),
+            -theta.sin() * phi.cos(),
+        );
+        (direction, theta.sin())
+    }
+
+    pub fn radiance(&self, direction: Vec3) -> Vec3 {
+        let (u, v, _) = self.direction_to_uv(direction);
+        let x = ((u * self.image.width() as f64) as usize).min(self.image.width() - 1);
+        let y = ((v * self.image.height() as f64) as usize).min(self.image.height() - 1);
+        self.intensity * self.image.get(x, y)
+    }
+
+    pub fn sample(&self) -> Option<(Vec3, f64)> {
+        // Samples a direction and its density per solid angle.
+        let ([u, v], pdf) = self.distribution.sample(random_f64(), random_f64());
+        let (direction, sin_theta) = self.uv_to_direction(u, v);
+        if pdf <= 0. || sin_theta <= 0. {
+            return None;
+        }
+        Some((direction, pdf / (2. * PI * PI * sin_theta)))
+    }
+
+    pub fn pdf(&self, direction: Vec3) -> f64 {
+        let (u, v, sin_theta) = self.direction_to_uv(direction);
+        match sin_theta > 0. {
+            true => self.distribution.pdf(u, v) / (2. * PI * PI * sin_theta),
+            false => 0.,
+        }
+    }
+}
+
+#[test]
+fn test_environment_map() {
+    // A dark map with two bright pixels around the horizon, towards +x once rotated by 90
+    // degrees.
+    let mut image = Image::new(8, 4);
+    for y in 0..4 {
+        for x in 0..8 {
+            image.set(x, y, Vec3::from_rgb(0.1, 0.1, 0.1));
+        }
+    }
+    image.set(4, 1, Vec3::from_rgb(100., 100., 100.));
+    image.set(4, 2, Vec3::from_rgb(100., 100., 100.));
+    let env = EnvironmentMap::new(image, 90., 2.);
+    assert_eq!(
+        env.radiance(Vec3::from_xyz(1., 0., 0.)),
+        Vec3::from_rgb(200., 200., 200.)
+    );
+    assert_eq!(
+        env.radiance(Vec3::from_xyz(0., 0., -1.)),
+        Vec3::from_rgb(0.2, 0.2, 0.2)
+    );
+
+    // Samples head for the bright pixels, with densities that match `pdf` and integrate to 1
+    // over the sphere.
+    let n = 10000;
+    let mut bright = 0;
+    for _ in 0..n {
+        let (direction, pdf) = env.sample().unwrap();
+        assert!((pdf - env.pdf(direction)).abs() < 1e-6 * pdf);
+        if env.radiance(direction).x() > 1. {
+            bright += 1;
+        }
+    }
+    assert!(bright as f64 / n as f64 > 0.9);
+    let steps = 200;
+    let mut integral = 0.;
+    for i in 0..steps {
+        for j in 0..2 * steps {
+            let theta = PI * (i as f64 + 0.5) / steps as f64;
+            let phi = PI * (j as f64 + 0.5) / steps as f64;
+            let direction = Vec3::from_xyz(
+                theta.sin() * phi.cos(),
+                theta.cos(),
+                theta.sin() * phi.sin(),
+            );
+            integral += env.pdf(direction) * theta.sin() * (PI / steps as f64).powi(2);
+        }
+    }
+    assert!((integral - 1.).abs() < 0.01);
+}
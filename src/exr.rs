@@ -0,0 +1,506 @@
+use std::io::{self, Write};
+
+use miniz_oxide::inflate::decompress_to_vec_zlib_with_limit;
+
+use crate::image::Image;
+use crate::load_error::LoadError;
+use crate::vector::Vec3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PixelType {
+    Half,
+    Float,
+}
+
+impl PixelType {
+    fn id(&self) -> i32 {
+        match self {
+            PixelType::Half => 1,
+            PixelType::Float => 2,
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            PixelType::Half => 2,
+            PixelType::Float => 4,
+        }
+    }
+}
+
+// Compression methods that `read` supports, by their id in the header.
+const NO_COMPRESSION: u8 = 0;
+const RLE_COMPRESSION: u8 = 1;
+const ZIPS_COMPRESSION: u8 = 2;
+const ZIP_COMPRESSION: u8 = 3;
+
+pub fn f32_to_half(value: f32) -> u16 {
+    // IEEE 754 binary16 conversion with round to nearest, ties to even.
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent == 0xff {
+        // Infinity stays infinity, NaN stays a quiet NaN.
+        return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+    let half_exponent = exponent - 127 + 15;
+    if half_exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if half_exponent <= 0 {
+        // Subnormal half, or zero if even the implicit bit is shifted out.
+        if half_exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - half_exponent) as u32;
+        return sign | round_shift(mantissa, shift) as u16;
+    }
+    // A carry out of the mantissa correctly bumps the exponent, up to infinity.
+    let half = ((half_exponent as u32) << 23) | mantissa;
+    sign | round_shift(half, 13) as u16
+}
+
+pub fn half_to_f32(half: u16) -> f32 {
+    let sign = ((half as u32) & 0x8000) << 16;
+    let exponent = ((half >> 10) & 0x1f) as u32;
+    let mantissa = (half & 0x3ff) as u32;
+    let bits = match (exponent, mantissa) {
+        (0, 0) => sign,
+        (0, _) => {
+            // Subnormal half, normalized for the wider exponent range of f32.
+            let shift = mantissa.leading_zeros() - 21;
+            sign | ((113 - shift) << 23) | ((mantissa << shift) & 0x3ff) << 13
+        }
+        (0x1f, _) => sign | 0x7f80_0000 | (mantissa << 13),
+        _ => sign | ((exponent + 112) << 23) | (mantissa << 13),
+    };
+    f32::from_bits(bits)
+}
+
+fn round_shift(value: u32, shift: u32) -> u32 {
+    let shifted = value >> shift;
+    let remainder = value & ((1 << shift) - 1);
+    let halfway = 1 << (shift - 1);
+    match remainder > halfway || (remainder == halfway && shifted & 1 == 1) {
+        true => shifted + 1,
+        false => shifted,
+    }
+}
+
+fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+fn box2i(width: usize, height: usize) -> Vec<u8> {
+    [0, 0, width as i32 - 1, height as i32 - 1]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect()
+}
+
+pub fn write<W: Write>(out: &mut W, image: &Image, pixel_type: PixelType) -> io::Result<()> {
+    // Single-part scanline OpenEXR file without compression, one scanline per chunk.
+    let (width, height) = (image.width(), image.height());
+    // Channels are stored in alphabetical order.
+    let channels = ["B", "G", "R"];
+
+    let mut header = vec![0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0];
+    let mut chlist = Vec::new();
+    for name in channels {
+        chlist.extend_from_slice(name.as_bytes());
+        chlist.push(0);
+        chlist.extend_from_slice(&pixel_type.id().to_le_bytes());
+        chlist.extend_from_slice(&[0; 4]); // pLinear and reserved
+        chlist.extend_from_slice(&1i32.to_le_bytes()); // x sampling
+        chlist.extend_from_slice(&1i32.to_le_bytes()); // y sampling
+    }
+    chlist.push(0);
+    attribute(&mut header, "channels", "chlist", &chlist);
+    attribute(&mut header, "compression", "compression", &[0]);
+    attribute(&mut header, "dataWindow", "box2i", &box2i(width, height));
+    attribute(&mut header, "displayWindow", "box2i", &box2i(width, height));
+    attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    header.push(0);
+
+    // The offset table points at each chunk: y coordinate, data size and the channel rows.
+    let line_size = width * channels.len() * pixel_type.size();
+    let chunk_size = 8 + line_size;
+    let table_end = header.len() + 8 * height;
+    for y in 0..height {
+        header.extend_from_slice(&((table_end + y * chunk_size) as u64).to_le_bytes());
+    }
+    out.write_all(&header)?;
+
+    let mut chunk = Vec::with_capacity(chunk_size);
+    for y in 0..height {
+        chunk.clear();
+        chunk.extend_from_slice(&(y as i32).to_le_bytes());
+        chunk.extend_from_slice(&(line_size as i32).to_le_bytes());
+        for channel in [2, 1, 0] {
+            for x in 0..width {
+                let value = image.get(x, y).e[channel] as f32;
+                match pixel_type {
+                    PixelType::Half => chunk.extend_from_slice(&f32_to_half(value).to_le_bytes()),
+                    PixelType::Float => chunk.extend_from_slice(&value.to_le_bytes()),
+                }
+            }
+        }
+        out.write_all(&chunk)?;
+    }
+    Ok(())
+}
+
+struct Channel {
+    name: String,
+    pixel_type: i32, // 0 for u32, 1 for half and 2 for f32
+}
+
+impl Channel {
+    fn size(&self) -> usize {
+        match self.pixel_type {
+            1 => 2,
+            _ => 4,
+        }
+    }
+
+    fn value(&self, bytes: &[u8]) -> f64 {
+        match self.pixel_type {
+            0 => u32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+            1 => half_to_f32(u16::from_le_bytes(bytes.try_into().unwrap())) as f64,
+            _ => f32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+        }
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], LoadError> {
+        let bytes = self
+            .pos
+            .checked_add(count)
+            .and_then(|end| self.data.get(self.pos..end))
+            .ok_or_else(|| LoadError::at_byte(self.data.len(), "unexpected end of file"))?;
+        self.pos += count;
+        Ok(bytes)
+    }
+
+    fn i32(&mut self) -> Result<i32, LoadError> {
+        Ok(i32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> Result<String, LoadError> {
+        // Null-terminated attribute and channel names.
+        let end = self.data[self.pos..]
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(|| LoadError::at_byte(self.data.len(), "unexpected end of file"))?;
+        let name = String::from_utf8_lossy(self.bytes(end)?).into_owned();
+        self.pos += 1;
+        Ok(name)
+    }
+}
+
+pub fn read(data: &[u8]) -> Result<Image, LoadError> {
+    // Single-part scanline OpenEXR file, uncompressed or with RLE, ZIPS or ZIP compression.
+    let mut reader = Reader { data, pos: 0 };
+    if reader.bytes(4)? != [0x76, 0x2f, 0x31, 0x01] {
+        return Err(LoadError::at_byte(0, "expected an OpenEXR file"));
+    }
+    let version = reader.i32()?;
+    if version & 0xff != 2 {
+        return Err(LoadError::at_byte(
+            4,
+            format!("unsupported version {}", version & 0xff),
+        ));
+    }
+    if version & 0x1200 != 0 {
+        return Err(LoadError::at_byte(
+            4,
+            "tiled and multi-part files are not supported",
+        ));
+    }
+
+    let mut channels = Vec::new();
+    let mut compression = None;
+    let mut data_window = None;
+    loop {
+        let start = reader.pos;
+        let name = reader.string()?;
+        if name.is_empty() {
+            break;
+        }
+        let _kind = reader.string()?;
+        let size = reader.i32()?.max(0) as usize;
+        let value = reader.bytes(size)?;
+        let mut attribute = Reader {
+            data: value,
+            pos: 0,
+        };
+        match name.as_str() {
+            "channels" => loop {
+                let name = attribute.string()?;
+                if name.is_empty() {
+                    break;
+                }
+                let pixel_type = attribute.i32()?;
+                attribute.bytes(4)?; // pLinear and reserved
+                if attribute.i32()? != 1 || attribute.i32()? != 1 {
+                    return Err(LoadError::at_byte(
+                        start,
+                        format!("subsampled channel `{}` is not supported", name),
+                    ));
+                }
+                channels.push(Channel { name, pixel_type });
+            },
+            "compression" => compression = Some((start, attribute.bytes(1)?[0])),
+            "dataWindow" => {
+                let [x_min, y_min, x_max, y_max] = [(); 4].map(|_| attribute.i32());
+                data_window = Some([x_min?, y_min?, x_max?, y_max?]);
+            }
+            _ => {}
+        }
+    }
+    let missing = |name: &str| LoadError::at_byte(reader.pos, format!("missing `{}`", name));
+    let (compression_start, compression) = compression.ok_or_else(|| missing("compression"))?;
+    let [x_min, y_min, x_max, y_max] = data_window.ok_or_else(|| missing("dataWindow"))?;
+    // Largest expansion of the data by each compression, which bounds the size of the image.
+    let (lines_per_chunk, max_ratio) = match compression {
+        NO_COMPRESSION => (1, 1),
+        RLE_COMPRESSION => (1, 64),
+        ZIPS_COMPRESSION => (1, 1032),
+        ZIP_COMPRESSION => (16, 1032),
+        other => {
+            return Err(LoadError::at_byte(
+                compression_start,
+                format!("unsupported compression {}", other),
+            ))
+        }
+    };
+    // Color from the R, G and B channels, or gray from Y.
+    let find = |name: &str| channels.iter().position(|channel| channel.name == name);
+    let rgb = match (find("R"), find("G"), find("B"), find("Y")) {
+        (Some(r), Some(g), Some(b), _) => [r, g, b],
+        (_, _, _, Some(y)) => [y; 3],
+        _ => return Err(missing("R, G and B channels")),
+    };
+
+    if x_max < x_min || y_max < y_min {
+        return Err(LoadError::at_byte(reader.pos, "empty data window"));
+    }
+    let width = (x_max as i64 - x_min as i64) as usize + 1;
+    let height = (y_max as i64 - y_min as i64) as usize + 1;
+    // Each line stores the channels one after the other.
+    let mut starts = Vec::with_capacity(channels.len());
+    let mut line_size = 0;
+    for channel in channels.iter() {
+        starts.push(line_size);
+        line_size += channel.size() * width;
+    }
+    let remaining = data.len() - reader.pos;
+    if line_size.saturating_mul(height) > remaining.saturating_mul(max_ratio) {
+        return Err(LoadError::at_byte(
+            reader.pos,
+            "data window too large for the file",
+        ));
+    }
+    let mut image = Image::new(width, height);
+    let chunk_count = height.div_ceil(lines_per_chunk);
+    let offsets = reader.bytes(8 * chunk_count)?;
+    for offset in offsets.chunks_exact(8) {
+        let offset = u64::from_le_bytes(offset.try_into().unwrap()) as usize;
+        let mut chunk = Reader { data, pos: offset };
+        let y_start = chunk.i32()? as i64 - y_min as i64;
+        let size = chunk.i32()?.max(0) as usize;
+        let packed = chunk.bytes(size)?;
+        let lines = match y_start >= 0 && (y_start as usize) < height {
+            true => lines_per_chunk.min(height - y_start as usize),
+            false => {
+                return Err(LoadError::at_byte(
+                    offset,
+                    "chunk outside of the data window",
+                ))
+            }
+        };
+        let expected = lines * line_size;
+        let pixels = match size == expected {
+            // Chunks that don't shrink are stored uncompressed.
+            true => packed.to_vec(),
+            false => decompress(packed, compression, expected)
+                .ok_or_else(|| LoadError::at_byte(offset, "invalid compressed data"))?,
+        };
+        if pixels.len() != expected {
+            return Err(LoadError::at_byte(offset, "invalid chunk size"));
+        }
+
+        for (line, bytes) in pixels.chunks_exact(line_size).enumerate() {
+            for x in 0..width {
+                let [r, g, b] = rgb.map(|c| {
+                    let size = channels[c].size();
+                    let at = starts[c] + x * size;
+                    channels[c].value(&bytes[at..at + size])
+                });
+                image.set(x, y_start as usize + line, Vec3::from_rgb(r, g, b));
+            }
+        }
+    }
+    Ok(image)
+}
+
+fn decompress(packed: &[u8], compression: u8, expected: usize) -> Option<Vec<u8>> {
+    let mut bytes = match compression {
+        RLE_COMPRESSION => {
+            // Negative counts precede literals, others repeat the next byte count + 1 times.
+            let mut bytes = Vec::with_capacity(expected);
+            let mut pos = 0;
+            while pos < packed.len() {
+                let count = packed[pos] as i8;
+                pos += 1;
+                if count < 0 {
+                    let count = -(count as isize) as usize;
+                    bytes.extend_from_slice(packed.get(pos..pos + count)?);
+                    pos += count;
+                } else {
+                    let value = *packed.get(pos)?;
+                    bytes.extend(std::iter::repeat_n(value, count as usize + 1));
+                    pos += 1;
+                }
+                if bytes.len() > expected {
+                    return None;
+                }
+            }
+            bytes
+        }
+        ZIPS_COMPRESSION | ZIP_COMPRESSION => {
+            decompress_to_vec_zlib_with_limit(packed, expected).ok()?
+        }
+        _ => return None,
+    };
+    // Undo the delta predictor, then interleave the two halves of the bytes again.
+    for i in 1..bytes.len() {
+        bytes[i] = bytes[i - 1].wrapping_add(bytes[i]).wrapping_sub(128);
+    }
+    let half = bytes.len().div_ceil(2);
+    let mut interleaved = Vec::with_capacity(bytes.len());
+    for i in 0..half {
+        interleaved.push(bytes[i]);
+        if let Some(&odd) = bytes.get(half + i) {
+            interleaved.push(odd);
+        }
+    }
+    Some(interleaved)
+}
+
+#[test]
+fn test_write_exr() {
+    assert_eq!(f32_to_half(1.), 0x3c00);
+    assert_eq!(f32_to_half(-2.), 0xc000);
+    assert_eq!(f32_to_half(0.1), 0x2e66);
+    assert_eq!(f32_to_half(65504.), 0x7bff);
+    assert_eq!(f32_to_half(1e6), 0x7c00);
+    assert_eq!(f32_to_half(2f32.powi(-24)), 0x0001);
+    assert_eq!(f32_to_half(2f32.powi(-26)), 0);
+    assert_eq!(f32_to_half(f32::NAN) & 0x7e00, 0x7e00);
+
+    let mut image = Image::new(2, 1);
+    image.set(1, 0, Vec3::from_rgb(1., 2., 4.));
+    for (pixel_type, size) in [(PixelType::Half, 2), (PixelType::Float, 4)] {
+        let mut exr = Vec::new();
+        write(&mut exr, &image, pixel_type).unwrap();
+        assert_eq!(&exr[..8], [0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0]);
+        // The offset table is the last thing before the only chunk.
+        let chunk_start = exr.len() - 8 - 6 * size;
+        let offset = u64::from_le_bytes(exr[chunk_start - 8..chunk_start].try_into().unwrap());
+        assert_eq!(offset as usize, chunk_start);
+        let chunk = &exr[chunk_start..];
+        assert_eq!(&chunk[..8], [0, 0, 0, 0, 6 * size as u8, 0, 0, 0]);
+        // Second pixel of the blue channel.
+        let blue = &chunk[8 + size..8 + 2 * size];
+        match pixel_type {
+            PixelType::Half => assert_eq!(blue, 0x4400u16.to_le_bytes()),
+            PixelType::Float => assert_eq!(blue, 4f32.to_le_bytes()),
+        }
+    }
+}
+
+#[test]
+fn test_read_exr() {
+    assert_eq!(half_to_f32(0x3c00), 1.);
+    assert_eq!(half_to_f32(0xc000), -2.);
+    assert_eq!(half_to_f32(0x0001), 2f32.powi(-24));
+    assert_eq!(half_to_f32(0x7c00), f32::INFINITY);
+    for value in [0.1f32, 65504., 3e-6] {
+        assert_eq!(
+            f32_to_half(half_to_f32(f32_to_half(value))),
+            f32_to_half(value)
+        );
+    }
+
+    let mut image = Image::new(2, 3);
+    image.set(1, 0, Vec3::from_rgb(1., 2., 4.));
+    image.set(0, 2, Vec3::from_rgb(0.5, 0.25, 8.));
+    for pixel_type in [PixelType::Half, PixelType::Float] {
+        let mut exr = Vec::new();
+        write(&mut exr, &image, pixel_type).unwrap();
+        let read = read(&exr).unwrap();
+        assert_eq!((read.width(), read.height()), (2, 3));
+        assert_eq!(read.pixels(), image.pixels());
+    }
+
+    // Compressed bytes 1, 2, 3, 4, 5 are split into 1, 3, 5, 2, 4 and stored as differences.
+    let predicted = [1, 130, 130, 125, 130];
+    let zip = miniz_oxide::deflate::compress_to_vec_zlib(&predicted, 6);
+    assert_eq!(
+        decompress(&zip, ZIP_COMPRESSION, 5),
+        Some(vec![1, 2, 3, 4, 5])
+    );
+    let rle = [0, 1, 1, 130, 0xfe, 125, 130];
+    assert_eq!(
+        decompress(&rle, RLE_COMPRESSION, 5),
+        Some(vec![1, 2, 3, 4, 5])
+    );
+    assert_eq!(decompress(&[5, 0], RLE_COMPRESSION, 5), None);
+
+    assert!(read(b"v/1\x01\x02\x02\0\0").is_err());
+
+    // Data windows that the file can't hold fail before allocating the image, even when their
+    // size overflows.
+    let mut exr = Vec::new();
+    write(&mut exr, &image, PixelType::Half).unwrap();
+    let name = b"dataWindow\0box2i\0";
+    let at = exr.windows(name.len()).position(|w| w == name).unwrap() + name.len() + 4;
+    for window in [[0, 0, 99999, 99999], [i32::MIN, 0, i32::MAX, 0]] {
+        let mut exr = exr.clone();
+        for (i, v) in window.iter().enumerate() {
+            exr[at + 4 * i..at + 4 * i + 4].copy_from_slice(&v.to_le_bytes());
+        }
+        assert!(read(&exr)
+            .unwrap_err()
+            .to_string()
+            .ends_with("data window too large for the file"));
+    }
+}
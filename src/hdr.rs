@@ -0,0 +1,213 @@
+use std::io::{self, Write};
+
+use crate::image::Image;
+use crate::load_error::LoadError;
+use crate::vector::Vec3;
+
+pub fn to_rgbe(color: Vec3) -> [u8; 4] {
+    // Shared exponent encoding, negative components cannot be represented and are clamped to 0.
+    let [r, g, b] = color.e.map(|component| component.max(0.));
+    let max = r.max(g).max(b);
+    if max < 1e-32 {
+        return [0; 4];
+    }
+    // max = mantissa * 2^exponent with mantissa in [0.5, 1).
+    let exponent = max.log2().floor() as i32 + 1;
+    let scale = 256. / 2f64.powi(exponent);
+    [
+        (r * scale).min(255.) as u8,
+        (g * scale).min(255.) as u8,
+        (b * scale).min(255.) as u8,
+        (exponent + 128).clamp(0, 255) as u8,
+    ]
+}
+
+pub fn from_rgbe(rgbe: [u8; 4]) -> Vec3 {
+    // Mantissas are decoded at the middle of their quantization step.
+    if rgbe[3] == 0 {
+        return Vec3::zeros();
+    }
+    let scale = 2f64.powi(rgbe[3] as i32 - 136);
+    Vec3::from_rgb(
+        (rgbe[0] as f64 + 0.5) * scale,
+        (rgbe[1] as f64 + 0.5) * scale,
+        (rgbe[2] as f64 + 0.5) * scale,
+    )
+}
+
+pub fn read(data: &[u8]) -> Result<Image, LoadError> {
+    // Radiance RGBE picture stored top to bottom, with flat or run-length encoded scanlines.
+    if !data.starts_with(b"#?") {
+        return Err(LoadError::at_byte(0, "expected a Radiance picture"));
+    }
+    let mut pos = 0;
+    let mut line = || {
+        let start = pos;
+        let end = data[start..].iter().position(|&b| b == b'\n')? + start;
+        pos = end + 1;
+        Some((
+            start,
+            String::from_utf8_lossy(&data[start..end]).into_owned(),
+        ))
+    };
+    // Header variables end at an empty line.
+    loop {
+        let (start, text) =
+            line().ok_or_else(|| LoadError::at_byte(data.len(), "unexpected end of header"))?;
+        if text.is_empty() {
+            break;
+        }
+        if let Some(format) = text.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return Err(LoadError::at_byte(
+                    start,
+                    format!("unsupported pixel format `{}`", format),
+                ));
+            }
+        }
+    }
+    let (start, resolution) =
+        line().ok_or_else(|| LoadError::at_byte(data.len(), "missing resolution"))?;
+    let (width, height): (usize, usize) = match resolution.split_whitespace().collect::<Vec<_>>()[..]
+    {
+        ["-Y", height, "+X", width] => match (width.parse(), height.parse()) {
+            (Ok(width), Ok(height)) => (width, height),
+            _ => return Err(LoadError::at_byte(start, "invalid resolution")),
+        },
+        _ => {
+            return Err(LoadError::at_byte(
+                start,
+                format!(
+                    "unsupported resolution `{}`, expected -Y height +X width",
+                    resolution
+                ),
+            ))
+        }
+    };
+
+    if width == 0 || height == 0 {
+        return Err(LoadError::at_byte(start, "invalid resolution"));
+    }
+    // Scanlines take at least 4 bytes per pixel, or 2 bytes per run of up to 127 pixels and
+    // channel when run-length encoded, which bounds the size of the image by the data left.
+    let flat_size = width.saturating_mul(4);
+    let line_size = match (8..0x8000).contains(&width) {
+        true => flat_size.min(4 + 8 * width.div_ceil(127)),
+        false => flat_size,
+    };
+    if line_size.saturating_mul(height) > data.len() - pos {
+        return Err(LoadError::at_byte(data.len(), "unexpected end of file"));
+    }
+    let mut image = Image::new(width, height);
+    let truncated = |pos: usize| LoadError::at_byte(pos, "unexpected end of file");
+    let mut scanline = vec![[0u8; 4]; width];
+    for y in 0..height {
+        let rle = (8..0x8000).contains(&width)
+            && data.get(pos..pos + 4).is_some_and(|b| {
+                b[0] == 2 && b[1] == 2 && (b[2] as usize) << 8 | b[3] as usize == width
+            });
+        if rle {
+            // Each component is stored separately as runs (count above 128) and literals.
+            pos += 4;
+            for channel in 0..4 {
+                let mut x = 0;
+                while x < width {
+                    let count = *data.get(pos).ok_or_else(|| truncated(pos))? as usize;
+                    let (count, run) = match count > 128 {
+                        true => (count - 128, true),
+                        false => (count, false),
+                    };
+                    if count == 0 || x + count > width {
+                        return Err(LoadError::at_byte(pos, "invalid run length"));
+                    }
+                    pos += 1;
+                    let size = if run { 1 } else { count };
+                    let bytes = data.get(pos..pos + size).ok_or_else(|| truncated(pos))?;
+                    for (i, pixel) in scanline[x..x + count].iter_mut().enumerate() {
+                        pixel[channel] = bytes[if run { 0 } else { i }];
+                    }
+                    pos += size;
+                    x += count;
+                }
+            }
+        } else {
+            let bytes = data
+                .get(pos..pos + 4 * width)
+                .ok_or_else(|| truncated(data.len()))?;
+            for (pixel, rgbe) in scanline.iter_mut().zip(bytes.chunks_exact(4)) {
+                *pixel = rgbe.try_into().unwrap();
+            }
+            pos += 4 * width;
+        }
+        for (x, rgbe) in scanline.iter().enumerate() {
+            image.set(x, y, from_rgbe(*rgbe));
+        }
+    }
+    Ok(image)
+}
+
+pub fn write<W: Write>(out: &mut W, image: &Image) -> io::Result<()> {
+    // Radiance RGBE picture with uncompressed scanlines from top to bottom.
+    write!(
+        out,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        image.height(),
+        image.width()
+    )?;
+    let data: Vec<u8> = image.pixels().iter().flat_map(|p| to_rgbe(*p)).collect();
+    out.write_all(&data)
+}
+
+#[test]
+fn test_write_hdr() {
+    assert_eq!(to_rgbe(Vec3::from_rgb(1., 0.5, 0.)), [128, 64, 0, 129]);
+    assert_eq!(to_rgbe(Vec3::from_rgb(-1., 1000., 0.)), [0, 250, 0, 138]);
+    assert_eq!(to_rgbe(Vec3::zeros()), [0; 4]);
+
+    let mut image = Image::new(2, 1);
+    image.set(0, 0, Vec3::from_rgb(0.75, 0.75, 0.75));
+    let mut hdr = Vec::new();
+    write(&mut hdr, &image).unwrap();
+    let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 2\n";
+    assert_eq!(&hdr[..header.len()], header);
+    assert_eq!(&hdr[header.len()..], [192, 192, 192, 128, 0, 0, 0, 0]);
+}
+
+#[test]
+fn test_read_hdr() {
+    let mut image = Image::new(2, 1);
+    image.set(0, 0, Vec3::from_rgb(0.75, 2., 0.));
+    let mut hdr = Vec::new();
+    write(&mut hdr, &image).unwrap();
+    let read = read(&hdr).unwrap();
+    assert_eq!((read.width(), read.height()), (2, 1));
+    assert!((read.get(0, 0) - image.get(0, 0)).length() < 0.02);
+    assert_eq!(read.get(1, 0), Vec3::zeros());
+
+    // Run-length encoded scanline of 8 pixels: a run for red, literals for green and blue and
+    // a run of two lengths for the exponent.
+    let mut rle = b"#?RADIANCE\n\n-Y 1 +X 8\n".to_vec();
+    rle.extend_from_slice(&[2, 2, 0, 8, 136, 128]);
+    rle.push(8);
+    rle.extend_from_slice(&[0, 16, 32, 48, 64, 80, 96, 112]);
+    rle.push(8);
+    rle.extend_from_slice(&[0; 8]);
+    rle.extend_from_slice(&[132, 129, 132, 0]);
+    let read = self::read(&rle).unwrap();
+    assert_eq!(read.get(2, 0), from_rgbe([128, 32, 0, 129]));
+    assert_eq!(read.get(7, 0), Vec3::zeros());
+
+    assert_eq!(
+        self::read(&rle[..rle.len() - 2]).err().unwrap().to_string(),
+        "byte 48: unexpected end of file"
+    );
+    assert!(self::read(b"#?RADIANCE\n\n+Y 1 +X 1\n\0\0\0\0").is_err());
+    // Resolutions that the data can't hold fail before allocating the image.
+    for resolution in ["-Y 100000 +X 100000", "-Y 1 +X 18446744073709551615"] {
+        let hdr = format!("#?RADIANCE\n\n{}\n", resolution);
+        assert_eq!(
+            self::read(hdr.as_bytes()).err().unwrap().to_string(),
+            format!("byte {}: unexpected end of file", hdr.len())
+        );
+    }
+}
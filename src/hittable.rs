@@ -1,3 +1,5 @@
+use crate::aabb::Aabb;
+use crate::hittable_list::HittableList;
 use crate::interval::Interval;
 use crate::material::{Lambertian, Material};
 use crate::ray::Ray;
@@ -6,10 +8,17 @@ use crate::vector::Vec3;
 #[derive(Debug, Clone)]
 pub struct HitRecord {
     pub p: Vec3,
-    pub normal: Vec3,
+    pub normal: Vec3,           // Shading normal, against the ray
+    pub geometric_normal: Vec3, // Normal of the actual surface, against the ray
     pub mat: Material,
     pub t: f64,
     pub front_face: bool,
+    pub u: f64, // Surface coordinates for texture lookups
+    pub v: f64,
+    pub dpdu: Vec3,          // Surface tangent, the derivative of p along u
+    pub dpdv: Vec3,          // Surface bitangent, the derivative of p along v
+    pub barycentric: Vec3,   // Barycentric weights of the hit point, only set by triangles
+    pub color: Option<Vec3>, // Interpolated vertex color, replaces the albedo of diffuse surfaces
 }
 
 impl HitRecord {
@@ -17,9 +26,16 @@ impl HitRecord {
         Self {
             p: Vec3::zeros(),
             normal: Vec3::zeros(),
+            geometric_normal: Vec3::zeros(),
             mat: Material::Lambertian(Lambertian::new(Vec3::zeros())),
             t: 0.0,
             front_face: false,
+            u: 0.,
+            v: 0.,
+            dpdu: Vec3::zeros(),
+            dpdv: Vec3::zeros(),
+            barycentric: Vec3::zeros(),
+            color: None,
         }
     }
 
@@ -32,10 +48,59 @@ impl HitRecord {
             true => outward_normal,
             false => -outward_normal,
         };
+        // Shading may perturb `normal` later on, the geometric normal stays.
+        self.geometric_normal = self.normal;
+        // Surfaces without vertex colors use their own albedo.
+        self.color = None;
         // normal = front_face ? outward_normal : -outward_normal;
     }
 }
 
-pub trait Hittable {
+impl Default for HitRecord {
+    fn default() -> Self {
+        HitRecord::new()
+    }
+}
+
+// Point sampled on the surface of a light.
+#[derive(Debug, Clone, Copy)]
+pub struct LightSample {
+    pub p: Vec3,
+    pub pdf: f64, // Density with respect to solid angle as seen from the sampling origin
+}
+
+// Primitives are shared between rayon worker threads while rendering.
+pub trait Hittable: Send + Sync {
     fn hit(&self, r: &Ray, r_t: Interval, rec: &mut HitRecord) -> bool;
+
+    fn bounding_box(&self) -> Aabb;
+
+    fn is_light(&self) -> bool {
+        // Emissive primitives that support `sample` and `pdf_value`.
+        false
+    }
+
+    fn collect_lights(&self, _lights: &mut HittableList) {
+        // Aggregates add their lights to `lights`.
+    }
+
+    fn sample(&self, _origin: Vec3) -> Option<LightSample> {
+        // Samples a point on the surface visible from `origin`.
+        None
+    }
+
+    fn pdf_value(&self, _origin: Vec3, _direction: Vec3, _r_t: Interval) -> f64 {
+        // Solid angle density of `sample` choosing `direction`, if the surface is hit within `r_t`.
+        0.
+    }
+
+    fn transmittance(&self, r: &Ray, r_t: Interval) -> f64 {
+        // Fraction of the light passing along `r` within `r_t`. Surfaces block it, media
+        // estimate it with less noise than a sampled collision.
+        let mut rec = HitRecord::new();
+        match self.hit(r, r_t, &mut rec) {
+            true => 0.,
+            false => 1.,
+        }
+    }
 }
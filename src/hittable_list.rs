@@ -1,25 +1,40 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
 use crate::hittable::{HitRecord, Hittable};
 use crate::interval::Interval;
 use crate::ray::Ray;
-use crate::sphere::Sphere;
 
+#[derive(Clone)]
 pub struct HittableList {
-    spheres: Vec<Sphere>,
+    objects: Vec<Arc<dyn Hittable>>,
+    bbox: Aabb,
 }
 
 impl HittableList {
     pub fn new() -> Self {
         Self {
-            spheres: Vec::new(),
+            objects: Vec::new(),
+            bbox: Aabb::empty(),
         }
     }
 
-    pub fn add(&mut self, sphere: Sphere) {
-        self.spheres.push(sphere);
+    pub fn add<H: Hittable + 'static>(&mut self, object: H) {
+        self.add_shared(Arc::new(object));
+    }
+
+    pub fn add_shared(&mut self, object: Arc<dyn Hittable>) {
+        // Adds an object that may also be referenced from elsewhere in the scene.
+        self.bbox = Aabb::surrounding(self.bbox, object.bounding_box());
+        self.objects.push(object);
+    }
+
+    pub fn objects(&self) -> &[Arc<dyn Hittable>] {
+        &self.objects
     }
 
     pub fn len(&self) -> usize {
-        self.spheres.len()
+        self.objects.len()
     }
 
     pub fn is_empty(&self) -> bool {
@@ -27,7 +42,14 @@ impl HittableList {
     }
 
     pub fn clear(&mut self) {
-        self.spheres.clear();
+        self.objects.clear();
+        self.bbox = Aabb::empty();
+    }
+}
+
+impl Default for HittableList {
+    fn default() -> Self {
+        HittableList::new()
     }
 }
 
@@ -37,8 +59,8 @@ impl Hittable for HittableList {
         let mut hit_anything = false;
         let mut closest_so_far = r_t.max();
 
-        for sphere in self.spheres.iter() {
-            if sphere.hit(r, Interval::new(r_t.min(), closest_so_far), &mut temp_rec) {
+        for object in self.objects.iter() {
+            if object.hit(r, Interval::new(r_t.min(), closest_so_far), &mut temp_rec) {
                 hit_anything = true;
                 closest_so_far = temp_rec.t;
                 *rec = temp_rec.clone();
@@ -52,11 +74,41 @@ impl Hittable for HittableList {
 
         hit_anything
     }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn transmittance(&self, r: &Ray, r_t: Interval) -> f64 {
+        transmittance(&self.objects, r, r_t)
+    }
+
+    fn collect_lights(&self, lights: &mut HittableList) {
+        for object in self.objects.iter() {
+            match object.is_light() {
+                true => lights.add_shared(object.clone()),
+                false => object.collect_lights(lights),
+            }
+        }
+    }
+}
+
+pub(crate) fn transmittance(objects: &[Arc<dyn Hittable>], r: &Ray, r_t: Interval) -> f64 {
+    // Product over all objects, stopping at the first opaque one.
+    let mut transmittance = 1.;
+    for object in objects.iter() {
+        transmittance *= object.transmittance(r, r_t);
+        if transmittance <= 0. {
+            return 0.;
+        }
+    }
+    transmittance
 }
 
 #[test]
 fn test_hittable_list() {
     use crate::material::Material;
+    use crate::sphere::Sphere;
     use crate::vector::Vec3;
 
     let mut world = HittableList::new();
@@ -70,3 +122,28 @@ fn test_hittable_list() {
     world.clear();
     assert!(world.is_empty())
 }
+
+#[test]
+fn test_nested_hittable_list() {
+    use crate::bvh::BvhNode;
+    use crate::material::Material;
+    use crate::sphere::Sphere;
+    use crate::vector::Vec3;
+
+    let mat = Material::lambertian(Vec3::zeros());
+    let mut inner = HittableList::new();
+    inner.add(Sphere::new(Vec3::from_xyz(0., 0., -5.), 1., mat.clone()));
+    inner.add(Sphere::new(Vec3::from_xyz(0., 0., -10.), 1., mat.clone()));
+
+    let mut world = HittableList::new();
+    world.add(Sphere::new(Vec3::from_xyz(0., 0., -20.), 1., mat.clone()));
+    world.add(BvhNode::new(&inner));
+    world.add(inner);
+    assert_eq!(world.len(), 3);
+    assert_eq!(world.bounding_box().min(), Vec3::from_xyz(-1., -1., -21.));
+
+    let r = Ray::new(Vec3::zeros(), Vec3::from_xyz(0., 0., -1.));
+    let mut rec = HitRecord::new();
+    assert!(world.hit(&r, Interval::new(1e-3, f64::INFINITY), &mut rec));
+    assert_eq!(rec.t, 4.);
+}
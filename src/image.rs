@@ -0,0 +1,485 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use rayon::prelude::*;
+
+use crate::color::{encode_color, srgb_to_linear};
+use crate::exr::{self, PixelType};
+use crate::hdr;
+use crate::load_error::LoadError;
+use crate::vector::Vec3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BitDepth {
+    Eight,
+    Sixteen,
+    ThirtyTwo,
+}
+
+impl BitDepth {
+    pub fn bits(&self) -> usize {
+        match self {
+            BitDepth::Eight => 8,
+            BitDepth::Sixteen => 16,
+            BitDepth::ThirtyTwo => 32,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    Ppm,
+    Png,
+    Hdr, // Radiance RGBE
+    Pfm, // Portable float map
+    Exr, // OpenEXR
+}
+
+impl ImageFormat {
+    pub fn from_path(path: &Path) -> io::Result<ImageFormat> {
+        // The file format is chosen by the extension of `path`.
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("ppm") => Ok(ImageFormat::Ppm),
+            Some("png") => Ok(ImageFormat::Png),
+            Some("hdr") => Ok(ImageFormat::Hdr),
+            Some("pfm") => Ok(ImageFormat::Pfm),
+            Some("exr") => Ok(ImageFormat::Exr),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "unsupported image format, expected a .png, .ppm, .hdr, .pfm or .exr extension",
+            )),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ImageFormat::Ppm => "PPM",
+            ImageFormat::Png => "PNG",
+            ImageFormat::Hdr => "Radiance HDR",
+            ImageFormat::Pfm => "PFM",
+            ImageFormat::Exr => "OpenEXR",
+        }
+    }
+
+    pub fn is_hdr(&self) -> bool {
+        // HDR formats store the linear radiance without clamping or gamma encoding.
+        matches!(self, ImageFormat::Hdr | ImageFormat::Pfm | ImageFormat::Exr)
+    }
+
+    fn bit_depths(&self) -> &'static [BitDepth] {
+        // Supported bits per channel, the first one is the default.
+        match self {
+            ImageFormat::Ppm | ImageFormat::Png => &[BitDepth::Eight, BitDepth::Sixteen],
+            ImageFormat::Exr => &[BitDepth::ThirtyTwo, BitDepth::Sixteen],
+            ImageFormat::Hdr | ImageFormat::Pfm => &[BitDepth::ThirtyTwo],
+        }
+    }
+
+    pub fn bit_depth(&self, requested: Option<BitDepth>) -> io::Result<BitDepth> {
+        let supported = self.bit_depths();
+        match requested {
+            None => Ok(supported[0]),
+            Some(bit_depth) if supported.contains(&bit_depth) => Ok(bit_depth),
+            Some(bit_depth) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{} images do not support {}-bit channels, expected {}",
+                    self.name(),
+                    bit_depth.bits(),
+                    supported
+                        .iter()
+                        .map(|b| format!("{}-bit", b.bits()))
+                        .collect::<Vec<_>>()
+                        .join(" or ")
+                ),
+            )),
+        }
+    }
+}
+
+// Framebuffer of linear radiance values, stored row by row from the top left pixel.
+#[derive(Debug, Clone)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<Vec3>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Vec3::zeros(); width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[Vec3] {
+        &self.pixels
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Vec3 {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: Vec3) {
+        self.pixels[y * self.width + x] = color;
+    }
+
+    pub fn set_row(&mut self, y: usize, row: &[Vec3]) {
+        self.pixels[y * self.width..(y + 1) * self.width].copy_from_slice(row);
+    }
+
+    pub fn map_pixels<F: Fn(Vec3) -> Vec3 + Sync>(&mut self, f: F) {
+        self.pixels
+            .par_iter_mut()
+            .for_each(|pixel| *pixel = f(*pixel));
+    }
+
+    pub fn load(path: &Path, srgb: bool) -> Result<Self, LoadError> {
+        // Reads an 8 or 16-bit PNG or PPM file, or a linear Radiance HDR or OpenEXR file.
+        // Samples are decoded from sRGB when `srgb` is set (color textures) and only normalized
+        // otherwise (data such as normal maps).
+        let data = fs::read(path).map_err(|err| LoadError::from(err).in_file(path))?;
+        let image = match ImageFormat::from_path(path)? {
+            ImageFormat::Png => Image::read_png(&data, srgb),
+            ImageFormat::Ppm => Image::read_ppm(&data, srgb),
+            ImageFormat::Hdr => hdr::read(&data),
+            ImageFormat::Exr => exr::read(&data),
+            format => Err(LoadError::from(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "cannot read {} images, expected a .png, .ppm, .hdr or .exr file",
+                    format.name()
+                ),
+            ))),
+        };
+        image.map_err(|err| err.in_file(path))
+    }
+
+    fn from_samples(width: usize, height: usize, samples: &[f64], channels: usize) -> Self {
+        // Builds an image from interleaved samples in [0, 1]. Gray images are replicated to RGB
+        // and any alpha channel is dropped.
+        let pixels = samples
+            .chunks_exact(channels)
+            .map(|px| match channels {
+                1 | 2 => Vec3::from_rgb(px[0], px[0], px[0]),
+                _ => Vec3::from_rgb(px[0], px[1], px[2]),
+            })
+            .collect();
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn read_png(data: &[u8], srgb: bool) -> Result<Self, LoadError> {
+        let invalid = |err: png::DecodingError| io::Error::new(io::ErrorKind::InvalidData, err);
+        let mut decoder = png::Decoder::new(data);
+        // Palettes and sub-byte gray levels are expanded to 8-bit samples.
+        decoder.set_transformations(png::Transformations::EXPAND);
+        let mut reader = decoder.read_info().map_err(invalid)?;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).map_err(invalid)?;
+        let buf = &buf[..info.buffer_size()];
+
+        let decode = |x: f64| if srgb { srgb_to_linear(x) } else { x };
+        let samples: Vec<f64> = match info.bit_depth {
+            png::BitDepth::Sixteen => buf
+                .chunks_exact(2)
+                .map(|b| decode(u16::from_be_bytes([b[0], b[1]]) as f64 / u16::MAX as f64))
+                .collect(),
+            _ => buf
+                .iter()
+                .map(|&b| decode(b as f64 / u8::MAX as f64))
+                .collect(),
+        };
+        Ok(Image::from_samples(
+            info.width as usize,
+            info.height as usize,
+            &samples,
+            info.color_type.samples(),
+        ))
+    }
+
+    pub fn read_ppm(data: &[u8], srgb: bool) -> Result<Self, LoadError> {
+        // Binary (P6) or ASCII (P3) portable pixmap.
+        let binary = match &data[..data.len().min(2)] {
+            b"P6" => true,
+            b"P3" => false,
+            _ => return Err(LoadError::at_byte(0, "expected a P3 or P6 pixmap")),
+        };
+        let mut pos = 2;
+        let mut number = || ppm_number(data, &mut pos);
+        let (width, height, max_value) = (number()?, number()?, number()?);
+        if max_value == 0 || max_value > u16::MAX as usize {
+            return Err(LoadError::at_byte(
+                pos,
+                format!("invalid maximum value {}", max_value),
+            ));
+        }
+
+        // Sizes are checked against the data before allocating, so bogus headers can't
+        // overflow or exhaust memory.
+        let truncated = || LoadError::at_byte(data.len(), "unexpected end of file");
+        let count = width
+            .checked_mul(height)
+            .and_then(|n| n.checked_mul(3))
+            .ok_or_else(truncated)?;
+        let samples: Vec<usize> = if binary {
+            // A single whitespace character separates the header from the samples.
+            pos += 1;
+            let size = if max_value < 256 { 1 } else { 2 };
+            let end = count
+                .checked_mul(size)
+                .and_then(|n| n.checked_add(pos))
+                .filter(|&end| end <= data.len())
+                .ok_or_else(truncated)?;
+            data[pos..end]
+                .chunks_exact(size)
+                .map(|b| match size {
+                    1 => b[0] as usize,
+                    _ => u16::from_be_bytes([b[0], b[1]]) as usize,
+                })
+                .collect()
+        } else {
+            // Every ASCII sample takes at least one digit and one separator.
+            let mut samples = Vec::with_capacity(count.min(data.len().saturating_sub(pos) / 2 + 1));
+            for _ in 0..count {
+                samples.push(ppm_number(data, &mut pos)?);
+            }
+            samples
+        };
+
+        let samples: Vec<f64> = samples
+            .into_iter()
+            .map(|s| {
+                let x = s.min(max_value) as f64 / max_value as f64;
+                if srgb {
+                    srgb_to_linear(x)
+                } else {
+                    x
+                }
+            })
+            .collect();
+        Ok(Image::from_samples(width, height, &samples, 3))
+    }
+
+    fn encode(&self, bit_depth: BitDepth) -> Vec<u8> {
+        // Interleaved RGB samples, 16-bit samples are big-endian as both PPM and PNG expect.
+        let max_value = match bit_depth {
+            BitDepth::Eight => u8::MAX as u16,
+            _ => u16::MAX,
+        };
+        let mut data = Vec::with_capacity(self.pixels.len() * 3 * bit_depth.bits() / 8);
+        for pixel in self.pixels.iter() {
+            for sample in encode_color(*pixel, max_value) {
+                match bit_depth {
+                    BitDepth::Eight => data.push(sample as u8),
+                    _ => data.extend_from_slice(&sample.to_be_bytes()),
+                }
+            }
+        }
+        data
+    }
+
+    pub fn write_ppm<W: Write>(&self, out: &mut W, bit_depth: BitDepth) -> io::Result<()> {
+        // Binary (P6) portable pixmap.
+        let bit_depth = ImageFormat::Ppm.bit_depth(Some(bit_depth))?;
+        write!(
+            out,
+            "P6\n{} {}\n{}\n",
+            self.width,
+            self.height,
+            (1u32 << bit_depth.bits()) - 1
+        )?;
+        out.write_all(&self.encode(bit_depth))
+    }
+
+    pub fn write_png<W: Write>(&self, out: &mut W, bit_depth: BitDepth) -> io::Result<()> {
+        let bit_depth = ImageFormat::Png.bit_depth(Some(bit_depth))?;
+        let mut encoder = png::Encoder::new(out, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(match bit_depth {
+            BitDepth::Eight => png::BitDepth::Eight,
+            _ => png::BitDepth::Sixteen,
+        });
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.encode(bit_depth))?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    pub fn write_pfm<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        // Color portable float map, a negative scale marks little-endian samples.
+        // Scanlines are stored from the bottom to the top of the image.
+        write!(out, "PF\n{} {}\n-1.0\n", self.width, self.height)?;
+        let mut data = Vec::with_capacity(self.pixels.len() * 12);
+        for row in self.pixels.chunks(self.width.max(1)).rev() {
+            for pixel in row {
+                for component in pixel.e {
+                    data.extend_from_slice(&(component as f32).to_le_bytes());
+                }
+            }
+        }
+        out.write_all(&data)
+    }
+
+    pub fn write<W: Write>(
+        &self,
+        out: &mut W,
+        format: ImageFormat,
+        bit_depth: Option<BitDepth>,
+    ) -> io::Result<()> {
+        let bit_depth = format.bit_depth(bit_depth)?;
+        match format {
+            ImageFormat::Ppm => self.write_ppm(out, bit_depth),
+            ImageFormat::Png => self.write_png(out, bit_depth),
+            ImageFormat::Hdr => hdr::write(out, self),
+            ImageFormat::Pfm => self.write_pfm(out),
+            ImageFormat::Exr => match bit_depth {
+                BitDepth::Sixteen => exr::write(out, self, PixelType::Half),
+                _ => exr::write(out, self, PixelType::Float),
+            },
+        }
+    }
+
+    pub fn save(&self, path: &Path, bit_depth: Option<BitDepth>) -> io::Result<()> {
+        let format = ImageFormat::from_path(path)?;
+        format.bit_depth(bit_depth)?;
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write(&mut out, format, bit_depth)?;
+        out.flush()
+    }
+}
+
+fn ppm_number(data: &[u8], pos: &mut usize) -> Result<usize, LoadError> {
+    // Skips whitespace and comments, then reads one decimal header or ASCII sample token.
+    loop {
+        match data.get(*pos) {
+            Some(b) if b.is_ascii_whitespace() => *pos += 1,
+            Some(b'#') => {
+                while data.get(*pos).is_some_and(|&b| b != b'\n') {
+                    *pos += 1;
+                }
+            }
+            Some(_) => break,
+            None => return Err(LoadError::at_byte(*pos, "unexpected end of file")),
+        }
+    }
+    let start = *pos;
+    while data.get(*pos).is_some_and(|b| !b.is_ascii_whitespace()) {
+        *pos += 1;
+    }
+    let token = String::from_utf8_lossy(&data[start..*pos]);
+    token
+        .parse()
+        .map_err(|_| LoadError::at_byte(start, format!("invalid number `{}`", token)))
+}
+
+#[test]
+fn test_write_image() {
+    let mut image = Image::new(2, 1);
+    image.set(1, 0, Vec3::from_rgb(1., 0.25, 0.));
+    assert_eq!(image.get(1, 0), Vec3::from_rgb(1., 0.25, 0.));
+
+    let mut ppm = Vec::new();
+    image.write_ppm(&mut ppm, BitDepth::Eight).unwrap();
+    assert_eq!(ppm, b"P6\n2 1\n255\n\0\0\0\xff\x89\0");
+
+    let mut ppm = Vec::new();
+    image.write_ppm(&mut ppm, BitDepth::Sixteen).unwrap();
+    assert_eq!(&ppm[..13], b"P6\n2 1\n65535\n");
+    assert_eq!(&ppm[19..], b"\xff\xff\x89\x7f\0\0");
+
+    let mut png = Vec::new();
+    image.write_png(&mut png, BitDepth::Sixteen).unwrap();
+    let decoder = png::Decoder::new(png.as_slice());
+    let mut reader = decoder.read_info().unwrap();
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).unwrap();
+    assert_eq!((info.width, info.height), (2, 1));
+    assert_eq!(info.bit_depth, png::BitDepth::Sixteen);
+    assert_eq!(&buf[6..], b"\xff\xff\x89\x7f\0\0");
+
+    assert_eq!(
+        ImageFormat::from_path(Path::new("out.PNG")).unwrap(),
+        ImageFormat::Png
+    );
+    assert!(ImageFormat::from_path(Path::new("out.jpg")).is_err());
+
+    assert_eq!(
+        ImageFormat::Exr.bit_depth(None).unwrap(),
+        BitDepth::ThirtyTwo
+    );
+    assert!(ImageFormat::Png
+        .bit_depth(Some(BitDepth::ThirtyTwo))
+        .is_err());
+    assert!(image
+        .write_ppm(&mut Vec::new(), BitDepth::ThirtyTwo)
+        .is_err());
+
+    let mut image = Image::new(1, 2);
+    image.set(0, 1, Vec3::from_rgb(2., 0., -1.));
+    let mut pfm = Vec::new();
+    image.write_pfm(&mut pfm).unwrap();
+    assert_eq!(&pfm[..12], b"PF\n1 2\n-1.0\n");
+    // The bottom row comes first.
+    assert_eq!(&pfm[12..16], 2f32.to_le_bytes());
+    assert_eq!(&pfm[20..24], (-1f32).to_le_bytes());
+}
+
+#[test]
+fn test_read_image() {
+    let mut image = Image::new(2, 1);
+    image.set(1, 0, Vec3::from_rgb(1., 0.25, 0.));
+    for bit_depth in [BitDepth::Eight, BitDepth::Sixteen] {
+        let mut png = Vec::new();
+        image.write_png(&mut png, bit_depth).unwrap();
+        let mut ppm = Vec::new();
+        image.write_ppm(&mut ppm, bit_depth).unwrap();
+        for read in [
+            Image::read_png(&png, true).unwrap(),
+            Image::read_ppm(&ppm, true).unwrap(),
+        ] {
+            assert_eq!((read.width(), read.height()), (2, 1));
+            assert!((read.get(1, 0) - image.get(1, 0)).length() < 2e-3);
+        }
+    }
+
+    let ascii = Image::read_ppm(b"P3\n# comment\n1 1\n4\n4 2 0\n", false).unwrap();
+    assert_eq!(ascii.get(0, 0), Vec3::from_rgb(1., 0.5, 0.));
+    assert_eq!(
+        Image::read_ppm(b"P6\n2 2\n255\n\0\0\0", false)
+            .err()
+            .unwrap()
+            .to_string(),
+        "byte 14: unexpected end of file"
+    );
+    assert!(Image::read_ppm(b"P5\n1 1\n255\n\0", false).is_err());
+    // Huge dimensions fail without overflowing or allocating.
+    for ppm in [
+        &b"P6\n4000000000 4000000000\n255\n\0"[..],
+        b"P6\n100000 100000\n255\n\0",
+        b"P3\n100000 100000\n255\n0",
+    ] {
+        assert_eq!(
+            Image::read_ppm(ppm, false).unwrap_err().to_string(),
+            format!("byte {}: unexpected end of file", ppm.len())
+        );
+    }
+}
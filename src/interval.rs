@@ -1,3 +1,4 @@
+#[derive(Debug, Clone, Copy)]
 pub struct Interval {
     min: f64,
     max: f64,
@@ -8,6 +9,19 @@ impl Interval {
         Self { min, max }
     }
 
+    pub fn empty() -> Self {
+        Self::new(f64::INFINITY, f64::NEG_INFINITY)
+    }
+
+    pub fn universe() -> Self {
+        Self::new(f64::NEG_INFINITY, f64::INFINITY)
+    }
+
+    pub fn enclosing(a: Interval, b: Interval) -> Self {
+        // Create the interval tightly enclosing the two input intervals.
+        Self::new(a.min.min(b.min), a.max.max(b.max))
+    }
+
     pub fn size(&self) -> f64 {
         self.max - self.min
     }
@@ -37,6 +51,11 @@ impl Interval {
     pub fn surrounds(&self, x: f64) -> bool {
         self.min < x && x < self.max
     }
+
+    pub fn expand(&self, delta: f64) -> Interval {
+        let padding = delta / 2.;
+        Interval::new(self.min - padding, self.max + padding)
+    }
 }
 
 #[test]
@@ -52,4 +71,11 @@ fn test_interval() {
     assert!(i.clamp(0.5) == 0.5);
     assert!(i.clamp(-1.) == 0.);
     assert!(i.clamp(2.) == 1.);
+
+    let e = Interval::enclosing(Interval::new(-1., 0.5), i);
+    assert_eq!(e.min(), -1.);
+    assert_eq!(e.max(), 1.);
+    assert_eq!(i.expand(1.).size(), 2.);
+    assert!(Interval::empty().size() < 0.);
+    assert!(Interval::universe().contains(1e300));
 }
@@ -0,0 +1,37 @@
+pub mod aabb;
+pub mod bvh;
+pub mod camera;
+pub mod color;
+pub mod distribution;
+pub mod environment;
+pub mod exr;
+pub mod hdr;
+pub mod hittable;
+pub mod hittable_list;
+pub mod image;
+pub mod interval;
+pub mod light;
+pub mod load_error;
+pub mod material;
+pub mod medium;
+pub mod mesh;
+pub mod microfacet;
+pub mod mtl;
+pub mod normal_map;
+pub mod obj;
+pub mod onb;
+pub mod perlin;
+pub mod ply;
+pub mod presets;
+pub mod quad;
+pub mod random;
+pub mod ray;
+pub mod scene;
+pub mod spectrum;
+pub mod sphere;
+pub mod stl;
+pub mod texture;
+pub mod tone_map;
+pub mod triangle;
+pub mod vector;
+pub mod voxel_grid;
@@ -0,0 +1,102 @@
+use crate::bvh::BvhNode;
+use crate::hittable::{Hittable, LightSample};
+use crate::hittable_list::HittableList;
+use crate::interval::Interval;
+use crate::random::random_f64;
+use crate::vector::Vec3;
+
+// Emissive primitives of a scene, sampled for direct lighting.
+#[derive(Clone, Default)]
+pub struct LightList {
+    lights: HittableList,
+    bvh: Option<BvhNode>, // Over `lights`, so densities only visit lights along a direction
+}
+
+impl LightList {
+    pub fn new(world: &HittableList) -> Self {
+        let mut lights = HittableList::new();
+        world.collect_lights(&mut lights);
+        let bvh = (!lights.is_empty()).then(|| BvhNode::new(&lights));
+        Self { lights, bvh }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+
+    pub fn sample(&self, origin: Vec3) -> Option<(&dyn Hittable, LightSample)> {
+        // Picks one light uniformly, the returned pdf includes the selection probability.
+        if self.is_empty() {
+            return None;
+        }
+        let index = ((random_f64() * self.len() as f64) as usize).min(self.len() - 1);
+        let light = self.lights.objects()[index].as_ref();
+        let mut sample = light.sample(origin)?;
+        sample.pdf /= self.len() as f64;
+        Some((light, sample))
+    }
+
+    pub fn pdf_value(&self, origin: Vec3, direction: Vec3, r_t: Interval) -> f64 {
+        // Density of `sample` choosing `direction` and reaching a light within `r_t`.
+        match &self.bvh {
+            Some(bvh) => bvh.pdf_value(origin, direction, r_t) / self.len() as f64,
+            None => 0.,
+        }
+    }
+}
+
+pub fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    // Multiple importance sampling weight of a sample drawn with `pdf`.
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    match a + b > 0. {
+        true => a / (a + b),
+        false => 0.,
+    }
+}
+
+#[test]
+fn test_light_list() {
+    use crate::material::Material;
+    use crate::quad::{cuboid, Quad};
+    use crate::sphere::Sphere;
+
+    let mut world = HittableList::new();
+    let white = Material::lambertian(Vec3::ones());
+    let light = Material::diffuse_light(Vec3::ones());
+    world.add(Sphere::new(Vec3::from_xyz(0., 0., -5.), 1., white.clone()));
+    world.add(Sphere::new(Vec3::from_xyz(0., 5., 0.), 1., light.clone()));
+    world.add(cuboid(
+        Vec3::from_xyz(-1., -1., 4.),
+        Vec3::from_xyz(1., 1., 6.),
+        0.,
+        light,
+    ));
+    world.add(Quad::new(
+        Vec3::zeros(),
+        Vec3::ones(),
+        Vec3::from_xyz(0., 1., 0.),
+        white,
+    ));
+
+    let lights = LightList::new(&world);
+    assert_eq!(lights.len(), 7);
+    let r_t = Interval::new(1e-3, f64::INFINITY);
+    for _ in 0..16 {
+        let (light, sample) = lights.sample(Vec3::zeros()).unwrap();
+        assert!(light.is_light());
+        // Only the sphere is seen in this direction, the box is behind the origin.
+        let pdf = lights.pdf_value(Vec3::zeros(), sample.p, r_t);
+        if sample.p.y() > 3. {
+            assert!((pdf - sample.pdf).abs() < 1e-9);
+        }
+    }
+    assert!(LightList::default().sample(Vec3::zeros()).is_none());
+
+    assert_eq!(power_heuristic(1., 0.), 1.);
+    assert_eq!(power_heuristic(1., 1.), 0.5);
+    assert_eq!(power_heuristic(0., 0.), 0.);
+}
@@ -0,0 +1,105 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Location {
+    Line(usize),              // 1-based line number in a text file
+    LineColumn(usize, usize), // 1-based line and column in a text file
+    Byte(usize),              // Byte offset in a binary file
+}
+
+impl Location {
+    pub fn from_offset(src: &str, offset: usize) -> Self {
+        // Converts a byte offset into `src` to a line and column.
+        let offset = offset.min(src.len());
+        let before = &src[..offset];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let column = src[line_start..offset].chars().count() + 1;
+        Location::LineColumn(line, column)
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Location::Line(line) => write!(f, "line {}", line),
+            Location::LineColumn(line, column) => write!(f, "line {}, column {}", line, column),
+            Location::Byte(offset) => write!(f, "byte {}", offset),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Parse {
+        location: Location,
+        message: String,
+    },
+    InFile {
+        path: PathBuf,
+        source: Box<LoadError>,
+    },
+}
+
+impl LoadError {
+    pub fn parse(location: Location, message: impl Into<String>) -> Self {
+        LoadError::Parse {
+            location,
+            message: message.into(),
+        }
+    }
+
+    pub fn at_line(line: usize, message: impl Into<String>) -> Self {
+        LoadError::parse(Location::Line(line), message)
+    }
+
+    pub fn at_byte(offset: usize, message: impl Into<String>) -> Self {
+        LoadError::parse(Location::Byte(offset), message)
+    }
+
+    pub fn in_file(self, path: &Path) -> Self {
+        // Attach the offending file name so errors from nested files (e.g. material libraries)
+        // can be told apart.
+        LoadError::InFile {
+            path: path.to_path_buf(),
+            source: Box::new(self),
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(err) => write!(f, "{}", err),
+            LoadError::Parse { location, message } => write!(f, "{}: {}", location, message),
+            LoadError::InFile { path, source } => write!(f, "{}: {}", path.display(), source),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Io(err) => Some(err),
+            LoadError::Parse { .. } => None,
+            LoadError::InFile { source, .. } => Some(source.as_ref()),
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(err: io::Error) -> Self {
+        LoadError::Io(err)
+    }
+}
+
+pub(crate) fn parse_token<T: FromStr>(token: &str, line_no: usize) -> Result<T, LoadError> {
+    // Parses a single whitespace-separated token of a text format.
+    token
+        .parse::<T>()
+        .map_err(|_| LoadError::at_line(line_no, format!("invalid number `{}`", token)))
+}
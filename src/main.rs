@@ -1,108 +1,193 @@
-use camera::CameraBuilder;
-use hittable_list::HittableList;
-use material::Material;
-use vector::Vec3;
-
-mod camera;
-mod color;
-mod hittable;
-mod hittable_list;
-mod interval;
-mod material;
-mod ray;
-mod sphere;
-mod vector;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::process;
+
+use clap::{Parser, ValueEnum};
+
+use raytracing_rs::bvh::BvhNode;
+use raytracing_rs::image::{BitDepth, ImageFormat};
+use raytracing_rs::light::LightList;
+use raytracing_rs::presets::{self, PRESETS};
+use raytracing_rs::random;
+use raytracing_rs::scene::Scene;
+use raytracing_rs::tone_map::ToneMap;
+
+#[derive(Parser)]
+#[command(version, about = "Render a scene file or a built-in preset")]
+struct Args {
+    /// Scene file to render (see README.md for the format)
+    scene: Option<PathBuf>,
+
+    /// Render a built-in preset instead of a scene file
+    #[arg(long, conflicts_with = "scene")]
+    preset: Option<String>,
+
+    /// List the built-in presets and exit
+    #[arg(long)]
+    list_presets: bool,
+
+    /// Output image path (.png, .ppm, .hdr, .pfm or .exr), defaults to a PPM on stdout
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Bits per color channel of the output image, defaults to 8 (32 for HDR formats)
+    #[arg(long, value_enum)]
+    bit_depth: Option<Depth>,
+
+    /// Exposure compensation in stops (EV)
+    #[arg(long, allow_hyphen_values = true)]
+    exposure: Option<f64>,
+
+    /// Tone mapper for PNG and PPM output: clamp, reinhard, reinhard-extended, aces, hable or agx
+    #[arg(long)]
+    tone_map: Option<ToneMap>,
+
+    /// Radiance mapped to white by the reinhard-extended tone mapper
+    #[arg(long)]
+    white_point: Option<f64>,
+
+    /// Image width in pixels
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    width: Option<u64>,
+
+    /// Image height in pixels
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    height: Option<u64>,
+
+    /// Count of random samples for each pixel
+    #[arg(short, long, value_parser = clap::value_parser!(u64).range(1..))]
+    samples: Option<u64>,
+
+    /// Maximum number of bounces of a path
+    #[arg(long)]
+    max_depth: Option<usize>,
+
+    /// Bounces before Russian roulette may terminate a path
+    #[arg(long)]
+    min_depth: Option<usize>,
+
+    /// Trace one sampled wavelength per path, for dispersion in glasses
+    #[arg(long)]
+    spectral: bool,
+
+    /// Number of render threads, defaults to the number of CPUs
+    #[arg(short = 'j', long)]
+    threads: Option<usize>,
+
+    /// Seed for the random number generators
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Depth {
+    #[value(name = "8")]
+    Eight,
+    #[value(name = "16")]
+    Sixteen,
+    #[value(name = "32")]
+    ThirtyTwo,
+}
+
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(1);
+}
 
 fn main() {
-    // World
-    let mut world = HittableList::new();
-
-    // Material
-    let material_ground = Material::lambertian(Vec3::from_xyz(0.5, 0.5, 0.5));
-    world.add(sphere::Sphere::new(
-        Vec3::from_xyz(0., -1000., 0.),
-        1000.,
-        material_ground,
-    ));
-
-    for a in -11..11 {
-        for b in -11..11 {
-            let choose_mat = rand::random::<f64>();
-            let center = Vec3::from_xyz(
-                a as f64 + 0.9 * rand::random::<f64>(),
-                0.2,
-                b as f64 + 0.9 * rand::random::<f64>(),
-            );
-            if (center - Vec3::from_xyz(4.0, 0.2, 0.0)).length() > 0.9 {
-                let sphere_material: Material;
-                if choose_mat < 0.8 {
-                    // diffuse
-                    let albedo = Vec3::random() * Vec3::random();
-                    sphere_material = Material::lambertian(albedo);
-                    world.add(sphere::Sphere::new(center, 0.2, sphere_material));
-                } else if choose_mat < 0.95 {
-                    // metal
-                    let albedo = Vec3::random_range(0.5, 1.0);
-                    let fuzz = rand::random::<f64>() * 0.5;
-                    sphere_material = Material::metal(albedo, fuzz);
-                    world.add(sphere::Sphere::new(center, 0.2, sphere_material));
-                } else {
-                    // glass
-                    sphere_material = Material::dielectric(1.5);
-                    world.add(sphere::Sphere::new(center, 0.2, sphere_material));
-                }
-            }
+    let args = Args::parse();
+
+    if args.list_presets {
+        for preset in PRESETS {
+            println!("{:<20} {}", preset.name, preset.description);
         }
+        return;
     }
 
-    let material1 = Material::dielectric(1.5);
-    world.add(sphere::Sphere::new(
-        Vec3::from_xyz(0., 1., 0.),
-        1.0,
-        material1,
-    ));
-
-    let material2 = Material::lambertian(Vec3::from_xyz(0.4, 0.2, 0.1));
-    world.add(sphere::Sphere::new(
-        Vec3::from_xyz(-4., 1., 0.),
-        1.0,
-        material2,
-    ));
-
-    let material3 = Material::metal(Vec3::from_xyz(0.7, 0.6, 0.5), 0.0);
-    world.add(sphere::Sphere::new(
-        Vec3::from_xyz(4., 1., 0.),
-        1.0,
-        material3,
-    ));
-
-    // Image
-    let image_width: usize = 640;
-    let image_height: usize = 360;
-    let samples_per_pixel: usize = 10;
-    let max_depth: usize = 50;
-
-    // Camera
-    let vfov: f64 = 20.0;
-    let look_from = Vec3::from_xyz(13.0, 2.0, 3.0);
-    let look_at = Vec3::from_xyz(0.0, 0.0, 0.0);
-    let vup = Vec3::from_xyz(0., 1., 0.);
-
-    let defocus_angle = 0.6;
-    let focus_dist = 10.0;
-
-    let mut cam = CameraBuilder::default()
-        .image_size(image_width, image_height)
-        .samples_per_pixel(samples_per_pixel)
-        .max_depth(max_depth)
-        .vfov(vfov)
-        .look_from(look_from)
-        .look_at(look_at)
-        .vup(vup)
-        .defocus_angle(defocus_angle)
-        .focus_dist(focus_dist)
-        .build()
-        .unwrap();
+    if let Some(threads) = args.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+            .unwrap_or_else(|err| fail(err));
+    }
+    // Presets generate their geometry randomly, seed them as well.
+    let seed = args.seed.unwrap_or(0);
+    random::seed(seed);
+
+    let mut scene = match (&args.scene, &args.preset) {
+        (Some(path), _) => Scene::load(path).unwrap_or_else(|err| fail(err)),
+        (None, Some(name)) => match presets::find(name) {
+            Some(preset) => (preset.build)(),
+            None => fail(format!(
+                "unknown preset `{}`, see --list-presets for the available presets",
+                name
+            )),
+        },
+        (None, None) => presets::random_spheres(),
+    };
+
+    if let Some(width) = args.width {
+        scene.camera.image_width(width as usize);
+    }
+    if let Some(height) = args.height {
+        scene.camera.image_height(height as usize);
+    }
+    if let Some(samples) = args.samples {
+        scene.camera.samples_per_pixel(samples as usize);
+    }
+    if let Some(max_depth) = args.max_depth {
+        scene.camera.max_depth(max_depth);
+    }
+    if let Some(min_depth) = args.min_depth {
+        scene.camera.min_depth(min_depth);
+    }
+    if let Some(exposure) = args.exposure {
+        scene.camera.exposure(exposure);
+    }
+    if let Some(tone_map) = args.tone_map {
+        scene.camera.tone_map(tone_map);
+    }
+    if let Some(white_point) = args.white_point {
+        scene.camera.white_point(white_point);
+    }
+    if args.spectral {
+        scene.camera.spectral(true);
+    }
+    scene.camera.seed(seed);
+
+    let bit_depth = args.bit_depth.map(|depth| match depth {
+        Depth::Eight => BitDepth::Eight,
+        Depth::Sixteen => BitDepth::Sixteen,
+        Depth::ThirtyTwo => BitDepth::ThirtyTwo,
+    });
+    // Reject unsupported output formats before spending time on the render.
+    let format = match &args.output {
+        Some(path) => ImageFormat::from_path(path)
+            .unwrap_or_else(|err| fail(format!("{}: {}", path.display(), err))),
+        None => ImageFormat::Ppm,
+    };
+    format.bit_depth(bit_depth).unwrap_or_else(|err| fail(err));
+
+    let mut cam = scene.camera.build().unwrap_or_else(|err| fail(err));
     cam = cam.initialize();
-    cam.render(&world);
+    let world = BvhNode::new(&scene.world);
+
+    let lights = LightList::new(&scene.world);
+    let mut image = cam.render(&world, &lights);
+    // HDR formats keep the linear radiance for post-processing.
+    if !format.is_hdr() {
+        cam.apply_tone_map(&mut image);
+    }
+    match &args.output {
+        Some(path) => image
+            .save(path, bit_depth)
+            .unwrap_or_else(|err| fail(format!("{}: {}", path.display(), err))),
+        None => {
+            let mut out = BufWriter::new(io::stdout().lock());
+            image
+                .write(&mut out, format, bit_depth)
+                .and_then(|_| out.flush())
+                .unwrap_or_else(|err| fail(err))
+        }
+    }
 }
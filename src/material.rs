@@ -1,83 +1,419 @@
+use std::f64::consts::PI;
+
+use std::sync::Arc;
+
+use derive_builder::Builder;
+
 use crate::hittable::HitRecord;
+use crate::medium::PhaseFunction;
+use crate::microfacet::{fresnel_complex, fresnel_dielectric, reflect, refract, TrowbridgeReitz};
+use crate::normal_map::NormalMap;
+use crate::onb::Onb;
+use crate::random::random_f64;
 use crate::ray::Ray;
+use crate::spectrum::Dispersion;
+use crate::texture::Texture;
+use crate::tone_map::luminance;
 use crate::vector::Vec3;
 
+// Scattered direction drawn by `Scatterable::sample`.
+#[derive(Debug, Clone, Copy)]
+pub struct BsdfSample {
+    pub direction: Vec3,
+    pub f: Vec3,        // BSDF value times |cos theta| of `direction`
+    pub pdf: f64,       // Solid angle density of `direction`, 1 for delta samples
+    pub is_delta: bool, // Sampled from a delta distribution that `eval` and `pdf` can't reproduce
+}
+
+impl BsdfSample {
+    pub fn weight(&self) -> Vec3 {
+        // Path throughput factor of the sample.
+        self.f / self.pdf
+    }
+}
+
 pub trait Scatterable {
-    fn scatter(
-        &self,
-        r_in: &Ray,
-        rec: &mut HitRecord,
-        attenuation: &mut Vec3,
-        scattered: &mut Ray,
-    ) -> bool;
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample>;
+
+    fn eval(&self, _r_in: &Ray, _rec: &HitRecord, _direction: Vec3) -> Vec3 {
+        // BSDF value times |cos theta| for scattering towards `direction`, zero for delta
+        // distributions.
+        Vec3::zeros()
+    }
+
+    fn pdf(&self, _r_in: &Ray, _rec: &HitRecord, _direction: Vec3) -> f64 {
+        // Solid angle density of `sample` choosing `direction`, zero for delta distributions.
+        0.
+    }
+
+    fn emitted(&self, _r_in: &Ray, _rec: &HitRecord) -> Vec3 {
+        // Radiance emitted by the surface towards the incoming ray, materials are not emissive
+        // by default.
+        Vec3::zeros()
+    }
+
+    fn absorption(&self) -> Vec3 {
+        // Beer-Lambert absorption coefficient per unit length inside the closed surface, for
+        // rays transmitted through it. Interiors are clear by default.
+        Vec3::zeros()
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Lambertian {
-    albedo: Vec3,
+    albedo: Texture,
 }
 
 impl Lambertian {
-    pub fn new(albedo: Vec3) -> Self {
-        Self { albedo }
+    pub fn new(albedo: impl Into<Texture>) -> Self {
+        Self {
+            albedo: albedo.into(),
+        }
     }
 }
 
 impl Scatterable for Lambertian {
-    fn scatter(
-        &self,
-        r_in: &Ray,
-        rec: &mut HitRecord,
-        attenuation: &mut Vec3,
-        scattered: &mut Ray,
-    ) -> bool {
-        let mut scatter_direction = rec.normal + Vec3::random().unit_vector();
-        if scatter_direction.near_zero() {
-            scatter_direction = rec.normal;
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        // Cosine-weighted hemisphere around the normal.
+        let uvw = Onb::new(rec.normal);
+        let direction = uvw.transform(Vec3::random_cosine_direction());
+        let pdf = self.pdf(r_in, rec, direction);
+        if pdf <= 0. {
+            return None;
         }
-        *scattered = Ray::new(rec.p, scatter_direction);
-        *attenuation = self.albedo.clone();
-        true
+        Some(BsdfSample {
+            direction,
+            f: self.eval(r_in, rec, direction),
+            pdf,
+            is_delta: false,
+        })
+    }
+
+    fn eval(&self, _r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Vec3 {
+        let cos_theta = rec.normal.dot(direction.unit_vector());
+        let albedo = match rec.color {
+            Some(color) => color,
+            None => self.albedo.value(rec.u, rec.v, rec.p),
+        };
+        albedo * (cos_theta.max(0.) / PI)
+    }
+
+    fn pdf(&self, _r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f64 {
+        let cos_theta = rec.normal.dot(direction.unit_vector());
+        cos_theta.max(0.) / PI
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct Metal {
-    albedo: Vec3,
+    albedo: Texture,
     fuzz: f64,
 }
 
 impl Metal {
-    pub fn new(albedo: Vec3, fuzz: f64) -> Self {
-        let fuzz = fuzz.min(1.).max(0.);
-        Self { albedo, fuzz }
+    pub fn new(albedo: impl Into<Texture>, fuzz: f64) -> Self {
+        let fuzz = fuzz.clamp(0., 1.);
+        Self {
+            albedo: albedo.into(),
+            fuzz,
+        }
     }
 }
 
 impl Scatterable for Metal {
-    fn scatter(
-        &self,
-        r_in: &Ray,
-        rec: &mut HitRecord,
-        attenuation: &mut Vec3,
-        scattered: &mut Ray,
-    ) -> bool {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        // The fuzzy reflection has no closed-form density, it is treated like a perfect mirror.
         let mut reflected = r_in.direction().reflect(rec.normal);
         reflected = reflected.unit_vector() + self.fuzz * Vec3::random_in_unit_sphere();
-        *scattered = Ray::new(rec.p, reflected);
-        *attenuation = self.albedo;
-        true
+        // Fuzz pushed the reflection below the surface, absorb it.
+        if reflected.dot(rec.normal) <= 0. {
+            return None;
+        }
+        Some(BsdfSample {
+            direction: reflected,
+            f: self.albedo.value(rec.u, rec.v, rec.p),
+            pdf: 1.,
+            is_delta: true,
+        })
+    }
+}
+
+fn local_frame(r_in: &Ray, rec: &HitRecord) -> (Onb, Vec3) {
+    // Shading frame around the normal and the outgoing direction in it, towards the viewer.
+    let uvw = Onb::new(rec.normal);
+    let wo = uvw.to_local(-r_in.direction().unit_vector());
+    (uvw, wo)
+}
+
+// Complex index of refraction eta + ik of common metals, sampled at the rgb primaries.
+pub const CONDUCTORS: &[(&str, [f64; 3], [f64; 3])] = &[
+    (
+        "gold",
+        [0.143119, 0.374957, 1.44248],
+        [3.98316, 2.38572, 1.60322],
+    ),
+    (
+        "copper",
+        [0.200438, 0.924033, 1.10221],
+        [3.91295, 2.45285, 2.14219],
+    ),
+    (
+        "aluminium",
+        [1.65746, 0.880369, 0.521229],
+        [9.22387, 6.26952, 4.837],
+    ),
+];
+
+// Metal with a GGX microfacet surface and the Fresnel reflectance of its complex index of
+// refraction.
+#[derive(Debug, Clone)]
+pub struct Conductor {
+    eta: Vec3,
+    k: Vec3,
+    distribution: TrowbridgeReitz,
+}
+
+impl Conductor {
+    pub fn new(eta: Vec3, k: Vec3, roughness: f64) -> Self {
+        Self {
+            eta,
+            k,
+            distribution: TrowbridgeReitz::new(roughness),
+        }
+    }
+
+    pub fn preset(name: &str, roughness: f64) -> Option<Self> {
+        CONDUCTORS
+            .iter()
+            .find(|(n, _, _)| *n == name)
+            .map(|(_, eta, k)| Conductor::new(Vec3::new(*eta), Vec3::new(*k), roughness))
+    }
+
+    fn eval_local(&self, wo: Vec3, wi: Vec3) -> Vec3 {
+        if wo.z() <= 0. || wi.z() <= 0. {
+            return Vec3::zeros();
+        }
+        let wm = wo + wi;
+        if wm.near_zero() {
+            return Vec3::zeros();
+        }
+        let wm = wm.unit_vector();
+        let fresnel = fresnel_complex(wo.dot(wm), self.eta, self.k);
+        // D G F / (4 cos_o cos_i), times cos_i.
+        let distribution = &self.distribution;
+        fresnel * (distribution.d(wm) * distribution.g(wo, wi) / (4. * wo.z()))
+    }
+
+    fn pdf_local(&self, wo: Vec3, wi: Vec3) -> f64 {
+        if wo.z() <= 0. || wi.z() <= 0. {
+            return 0.;
+        }
+        let wm = wo + wi;
+        if wm.near_zero() {
+            return 0.;
+        }
+        let wm = wm.unit_vector();
+        // Reflection maps the visible normal density to directions by 1 / (4 |wo . wm|).
+        self.distribution.pdf(wo, wm) / (4. * wo.dot(wm).abs())
+    }
+}
+
+impl Scatterable for Conductor {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        let (uvw, wo) = local_frame(r_in, rec);
+        if wo.z() <= 0. {
+            return None;
+        }
+        if self.distribution.is_smooth() {
+            let wi = Vec3::from_xyz(-wo.x(), -wo.y(), wo.z());
+            return Some(BsdfSample {
+                direction: uvw.transform(wi),
+                f: fresnel_complex(wo.z(), self.eta, self.k),
+                pdf: 1.,
+                is_delta: true,
+            });
+        }
+
+        let wm = self
+            .distribution
+            .sample_wm(wo, (random_f64(), random_f64()));
+        let wi = reflect(wo, wm);
+        let pdf = self.pdf_local(wo, wi);
+        if pdf <= 0. {
+            return None;
+        }
+        Some(BsdfSample {
+            direction: uvw.transform(wi),
+            f: self.eval_local(wo, wi),
+            pdf,
+            is_delta: false,
+        })
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Vec3 {
+        if self.distribution.is_smooth() {
+            return Vec3::zeros();
+        }
+        let (uvw, wo) = local_frame(r_in, rec);
+        self.eval_local(wo, uvw.to_local(direction.unit_vector()))
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f64 {
+        if self.distribution.is_smooth() {
+            return 0.;
+        }
+        let (uvw, wo) = local_frame(r_in, rec);
+        self.pdf_local(wo, uvw.to_local(direction.unit_vector()))
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct Dielectric {
     ref_idx: f64,
+    distribution: TrowbridgeReitz,
+    absorption: Vec3,
+    dispersion: Option<Dispersion>, // Index of refraction of spectral renders
 }
 
 impl Dielectric {
     pub fn new(ref_idx: f64) -> Self {
-        Self { ref_idx }
+        Self {
+            ref_idx,
+            distribution: TrowbridgeReitz::new(0.),
+            absorption: Vec3::zeros(),
+            dispersion: None,
+        }
+    }
+
+    pub fn dispersive(dispersion: Dispersion) -> Self {
+        // RGB renders use the index at the d line.
+        Self {
+            dispersion: Some(dispersion),
+            ..Dielectric::new(dispersion.d_line_ior())
+        }
+    }
+
+    pub fn with_absorption(mut self, absorption: Vec3) -> Self {
+        self.absorption = Vec3::new(absorption.e.map(|a| a.max(0.)));
+        self
+    }
+
+    pub fn with_transmittance(self, color: Vec3, distance: f64) -> Self {
+        // Absorption that leaves `color` of the light after traveling `distance` inside.
+        debug_assert!(distance > 0., "transmittance distance must be positive");
+        let absorption = color.e.map(|c| -c.clamp(1e-6, 1.).ln() / distance);
+        self.with_absorption(Vec3::new(absorption))
+    }
+
+    pub fn with_roughness(mut self, roughness: f64) -> Self {
+        // Rough interfaces scatter through a GGX microfacet surface.
+        self.distribution = TrowbridgeReitz::new(roughness);
+        self
+    }
+
+    fn ior(&self, r_in: &Ray) -> f64 {
+        match (self.dispersion, r_in.wavelength()) {
+            (Some(dispersion), Some(lambda)) => dispersion.ior(lambda),
+            _ => self.ref_idx,
+        }
+    }
+
+    fn eta(&self, r_in: &Ray, rec: &HitRecord) -> f64 {
+        // Index of refraction of the far side relative to the side of the incoming ray.
+        match rec.front_face {
+            true => self.ior(r_in),
+            false => 1. / self.ior(r_in),
+        }
+    }
+
+    fn half_vector(wo: Vec3, wi: Vec3, eta: f64) -> Option<Vec3> {
+        // Microfacet normal that reflects or refracts `wo` into `wi`, facing the viewer side.
+        if wo.z() <= 0. || wi.z() == 0. {
+            return None;
+        }
+        let etap = match wi.z() > 0. {
+            true => 1.,
+            false => eta,
+        };
+        let wm = wi * etap + wo;
+        if wm.near_zero() {
+            return None;
+        }
+        let wm = match wm.z() < 0. {
+            true => -wm.unit_vector(),
+            false => wm.unit_vector(),
+        };
+        // Discard microfacets seen from behind.
+        match wm.dot(wi) * wi.z() < 0. || wm.dot(wo) * wo.z() < 0. {
+            true => None,
+            false => Some(wm),
+        }
+    }
+
+    fn eval_rough(&self, wo: Vec3, wi: Vec3, eta: f64) -> Vec3 {
+        let Some(wm) = Dielectric::half_vector(wo, wi, eta) else {
+            return Vec3::zeros();
+        };
+        let distribution = &self.distribution;
+        let fresnel = fresnel_dielectric(wo.dot(wm), eta);
+        let dg = distribution.d(wm) * distribution.g(wo, wi);
+        // Like the smooth interface, the 1 / eta^2 radiance scaling is left out, it cancels
+        // for paths that leave the medium again. Both lobes include the |cos_i| factor.
+        let f = match wi.z() > 0. {
+            true => dg * fresnel / (4. * wo.z()),
+            false => {
+                let denom = (wi.dot(wm) + wo.dot(wm) / eta).powi(2);
+                dg * (1. - fresnel) * (wi.dot(wm) * wo.dot(wm) / (wo.z() * denom)).abs()
+            }
+        };
+        Vec3::ones() * f
+    }
+
+    fn pdf_rough(&self, wo: Vec3, wi: Vec3, eta: f64) -> f64 {
+        let Some(wm) = Dielectric::half_vector(wo, wi, eta) else {
+            return 0.;
+        };
+        // The lobes are chosen by their Fresnel weight.
+        let fresnel = fresnel_dielectric(wo.dot(wm), eta);
+        let visible = self.distribution.pdf(wo, wm);
+        match wi.z() > 0. {
+            true => visible / (4. * wo.dot(wm).abs()) * fresnel,
+            false => {
+                let denom = (wi.dot(wm) + wo.dot(wm) / eta).powi(2);
+                visible * wi.dot(wm).abs() / denom * (1. - fresnel)
+            }
+        }
+    }
+
+    fn sample_wi(&self, wo: Vec3, eta: f64) -> Option<Vec3> {
+        // Reflect or refract about a visible microfacet normal, chosen by its Fresnel weight.
+        let wm = self
+            .distribution
+            .sample_wm(wo, (random_f64(), random_f64()));
+        // Directions that end up on the wrong side of the surface are rejected.
+        match random_f64() < fresnel_dielectric(wo.dot(wm), eta) {
+            true => Some(reflect(wo, wm)).filter(|wi| wi.z() > 0.),
+            false => refract(wo, wm, eta).filter(|wi| wi.z() < 0.),
+        }
+    }
+
+    fn sample_rough(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        let (uvw, wo) = local_frame(r_in, rec);
+        if wo.z() <= 0. {
+            return None;
+        }
+        let eta = self.eta(r_in, rec);
+        let wi = self.sample_wi(wo, eta)?;
+        let pdf = self.pdf_rough(wo, wi, eta);
+        if pdf <= 0. {
+            return None;
+        }
+        Some(BsdfSample {
+            direction: uvw.transform(wi),
+            f: self.eval_rough(wo, wi, eta),
+            pdf,
+            is_delta: false,
+        })
     }
 
     pub fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
@@ -87,18 +423,11 @@ impl Dielectric {
 }
 
 impl Scatterable for Dielectric {
-    fn scatter(
-        &self,
-        r_in: &Ray,
-        rec: &mut HitRecord,
-        attenuation: &mut Vec3,
-        scattered: &mut Ray,
-    ) -> bool {
-        let ri = if rec.front_face {
-            1. / self.ref_idx
-        } else {
-            self.ref_idx
-        };
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        if !self.distribution.is_smooth() {
+            return self.sample_rough(r_in, rec);
+        }
+        let ri = 1. / self.eta(r_in, rec);
 
         let unit_direction = r_in.direction().unit_vector();
         let cos_theta = (-unit_direction).dot(rec.normal).min(1.);
@@ -106,16 +435,361 @@ impl Scatterable for Dielectric {
 
         let cannot_refract = ri * sin_theta > 1.0;
 
-        let direction = match cannot_refract
-            || (&Dielectric::reflectance(cos_theta, ri) > &rand::random::<f64>())
-        {
-            true => unit_direction.reflect(rec.normal),
-            false => unit_direction.refract(rec.normal, ri),
+        let direction =
+            match cannot_refract || (Dielectric::reflectance(cos_theta, ri) > random_f64()) {
+                true => unit_direction.reflect(rec.normal),
+                false => unit_direction.refract(rec.normal, ri),
+            };
+
+        Some(BsdfSample {
+            direction,
+            f: Vec3::ones(),
+            pdf: 1.,
+            is_delta: true,
+        })
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Vec3 {
+        if self.distribution.is_smooth() {
+            return Vec3::zeros();
+        }
+        let (uvw, wo) = local_frame(r_in, rec);
+        self.eval_rough(
+            wo,
+            uvw.to_local(direction.unit_vector()),
+            self.eta(r_in, rec),
+        )
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f64 {
+        if self.distribution.is_smooth() {
+            return 0.;
+        }
+        let (uvw, wo) = local_frame(r_in, rec);
+        self.pdf_rough(
+            wo,
+            uvw.to_local(direction.unit_vector()),
+            self.eta(r_in, rec),
+        )
+    }
+
+    fn absorption(&self) -> Vec3 {
+        self.absorption
+    }
+}
+
+// Smallest principled roughness, which keeps every lobe away from a delta distribution so
+// `eval` and `pdf` reproduce all samples.
+const MIN_ROUGHNESS: f64 = 0.04;
+
+fn schlick(f0: Vec3, cosine: f64) -> Vec3 {
+    f0 + (Vec3::ones() - f0) * (1. - cosine.clamp(0., 1.)).powi(5)
+}
+
+// Uber material with the parameters of the Disney principled BRDF and glTF PBR materials. A
+// dielectric base layer mixes a diffuse lobe, a specular lobe and rough glass by `transmission`,
+// `metallic` fades it to a tinted metal, and a clear coat can cover both.
+#[derive(Builder, Debug, Clone)]
+pub struct Principled {
+    #[builder(setter(into), default = "Texture::solid(Vec3::ones())")]
+    base_color: Texture, // Diffuse albedo, reflectance of metals and tint of transmission
+    #[builder(setter(into, strip_option), default)]
+    metallic_roughness: Option<Texture>, // glTF texture scaling metallic (blue) and roughness (green)
+    #[builder(default)]
+    metallic: f64, // Blend from a dielectric (0) to a metal (1)
+    #[builder(default = "0.5")]
+    roughness: f64, // Perceptual roughness of the specular and transmission lobes
+    #[builder(default = "0.5")]
+    specular: f64, // Dielectric reflectance at normal incidence, 0.5 is 4%
+    #[builder(default = "1.5")]
+    ior: f64, // Index of refraction of the transmission lobe
+    #[builder(default)]
+    transmission: f64, // Blend from the diffuse lobe (0) to rough glass (1)
+    #[builder(default)]
+    clearcoat: f64, // Strength of a colorless specular coat with an index of refraction of 1.5
+    #[builder(default = "0.1")]
+    clearcoat_roughness: f64, // Perceptual roughness of the coat
+    #[builder(default)]
+    sheen: f64, // Extra diffuse reflection at grazing angles, for cloth
+    #[builder(default = "0.5")]
+    sheen_tint: f64, // Blend of the sheen from white (0) to the hue of the base color (1)
+}
+
+// Principled parameters evaluated at a hit, for the outgoing direction `wo`.
+struct PrincipledLobes {
+    base: Vec3,
+    metallic: f64, // Weights below are clamped to [0, 1], larger ones could go negative
+    roughness: f64,
+    specular: f64,
+    transmission: f64,
+    clearcoat: f64,
+    sheen: f64,
+    sheen_tint: f64,
+    glass: Dielectric, // Transmission lobe, its distribution is shared by the specular lobe
+    coat: TrowbridgeReitz,
+    eta: f64,
+    probabilities: [f64; 4], // Of sampling the coat, specular, diffuse and transmission lobes
+}
+
+impl Principled {
+    fn lobes(&self, r_in: &Ray, rec: &HitRecord, wo: Vec3) -> Option<PrincipledLobes> {
+        let base = self.base_color.value(rec.u, rec.v, rec.p);
+        let (mut metallic, mut roughness) = (self.metallic, self.roughness);
+        if let Some(texture) = &self.metallic_roughness {
+            let c = texture.value(rec.u, rec.v, rec.p);
+            roughness *= c.y();
+            metallic *= c.z();
+        }
+        let metallic = metallic.clamp(0., 1.);
+        let roughness = roughness.clamp(MIN_ROUGHNESS, 1.);
+        let transmission = self.transmission.clamp(0., 1.);
+        let specular = self.specular.clamp(0., 1.);
+        let clearcoat = self.clearcoat.clamp(0., 1.);
+        let glass = Dielectric {
+            ref_idx: self.ior,
+            distribution: TrowbridgeReitz::new(roughness),
+            absorption: Vec3::zeros(),
+            dispersion: None,
+        };
+        let eta = glass.eta(r_in, rec);
+
+        // Pick lobes roughly by the energy they reflect towards `wo`.
+        let cos_o = wo.z();
+        let coat = clearcoat * schlick(Vec3::ones() * 0.04, cos_o).x();
+        let fresnel = schlick(Vec3::ones() * 0.08 * specular, cos_o).x();
+        let metal = luminance(schlick(base, cos_o));
+        let dielectric = (1. - coat) * (1. - metallic);
+        let weights = [
+            coat,
+            (1. - coat) * metallic * metal + dielectric * (1. - transmission) * fresnel,
+            dielectric * (1. - transmission) * (1. - fresnel),
+            dielectric * transmission,
+        ];
+        let total: f64 = weights.iter().sum();
+        if total <= 0. {
+            return None;
+        }
+        Some(PrincipledLobes {
+            base,
+            metallic,
+            roughness,
+            specular,
+            transmission,
+            clearcoat,
+            sheen: self.sheen.clamp(0., 1.),
+            sheen_tint: self.sheen_tint.clamp(0., 1.),
+            glass,
+            coat: TrowbridgeReitz::new(self.clearcoat_roughness.max(MIN_ROUGHNESS)),
+            eta,
+            probabilities: weights.map(|w| w / total),
+        })
+    }
+
+    fn eval_local(&self, lobes: &PrincipledLobes, wo: Vec3, wi: Vec3) -> Vec3 {
+        let cos_o = wo.z();
+        if cos_o <= 0. || wi.z() == 0. {
+            return Vec3::zeros();
+        }
+        let transmission = lobes.transmission;
+        let coat = lobes.clearcoat * schlick(Vec3::ones() * 0.04, cos_o).x();
+        let dielectric = (1. - coat) * (1. - lobes.metallic);
+        let glass = lobes.glass.eval_rough(wo, wi, lobes.eta);
+        if wi.z() < 0. {
+            // Only the glass transmits, tinted by the base color.
+            return lobes.base * glass * (dielectric * transmission);
+        }
+
+        let cos_i = wi.z();
+        let wm = (wo + wi).unit_vector();
+        let cos_d = wi.dot(wm);
+        // Microfacet reflection D G / (4 cos_o cos_i), times cos_i.
+        let reflection = |distribution: &TrowbridgeReitz| {
+            distribution.d(wm) * distribution.g(wo, wi) / (4. * cos_o)
+        };
+        let specular = reflection(&lobes.glass.distribution);
+
+        // Burley diffuse with retro-reflection at grazing angles, plus sheen.
+        let fd90 = 0.5 + 2. * lobes.roughness * cos_d * cos_d;
+        let burley =
+            (1. + (fd90 - 1.) * (1. - cos_i).powi(5)) * (1. + (fd90 - 1.) * (1. - cos_o).powi(5));
+        let tint = match luminance(lobes.base) > 0. {
+            true => lobes.base / luminance(lobes.base),
+            false => Vec3::ones(),
+        };
+        let sheen_color = (1. - lobes.sheen_tint) * Vec3::ones() + lobes.sheen_tint * tint;
+        let sheen = sheen_color * (lobes.sheen * (1. - cos_d).powi(5));
+        let diffuse = (lobes.base * (burley / PI) + sheen) * cos_i;
+
+        let fresnel = schlick(Vec3::ones() * 0.08 * lobes.specular, cos_d).x();
+        let base_layer = ((1. - fresnel) * diffuse + Vec3::ones() * (fresnel * specular))
+            * (1. - transmission)
+            + glass * transmission;
+        let metal = schlick(lobes.base, cos_d) * specular;
+        let clearcoat = lobes.clearcoat * schlick(Vec3::ones() * 0.04, cos_d).x();
+        base_layer * dielectric
+            + metal * ((1. - coat) * lobes.metallic)
+            + Vec3::ones() * (clearcoat * reflection(&lobes.coat))
+    }
+
+    fn pdf_local(&self, lobes: &PrincipledLobes, wo: Vec3, wi: Vec3) -> f64 {
+        if wo.z() <= 0. || wi.z() == 0. {
+            return 0.;
+        }
+        let [coat, specular, diffuse, transmission] = lobes.probabilities;
+        let glass = transmission * lobes.glass.pdf_rough(wo, wi, lobes.eta);
+        if wi.z() < 0. {
+            return glass;
+        }
+        let wm = (wo + wi).unit_vector();
+        let reflection =
+            |distribution: &TrowbridgeReitz| distribution.pdf(wo, wm) / (4. * wo.dot(wm).abs());
+        coat * reflection(&lobes.coat)
+            + specular * reflection(&lobes.glass.distribution)
+            + diffuse * wi.z() / PI
+            + glass
+    }
+}
+
+impl Scatterable for Principled {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        let (uvw, wo) = local_frame(r_in, rec);
+        if wo.z() <= 0. {
+            return None;
+        }
+        let lobes = self.lobes(r_in, rec, wo)?;
+        let [coat, specular, diffuse, _] = lobes.probabilities;
+        let u = random_f64();
+        let wi = if u < coat {
+            reflect(wo, lobes.coat.sample_wm(wo, (random_f64(), random_f64())))
+        } else if u < coat + specular {
+            let distribution = &lobes.glass.distribution;
+            reflect(wo, distribution.sample_wm(wo, (random_f64(), random_f64())))
+        } else if u < coat + specular + diffuse {
+            Vec3::random_cosine_direction()
+        } else {
+            lobes.glass.sample_wi(wo, lobes.eta)?
         };
+        // Reflections that land below the horizon are rejected.
+        if u < coat + specular && wi.z() <= 0. {
+            return None;
+        }
+        let pdf = self.pdf_local(&lobes, wo, wi);
+        if pdf <= 0. {
+            return None;
+        }
+        Some(BsdfSample {
+            direction: uvw.transform(wi),
+            f: self.eval_local(&lobes, wo, wi),
+            pdf,
+            is_delta: false,
+        })
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Vec3 {
+        let (uvw, wo) = local_frame(r_in, rec);
+        match self.lobes(r_in, rec, wo) {
+            Some(lobes) => self.eval_local(&lobes, wo, uvw.to_local(direction.unit_vector())),
+            None => Vec3::zeros(),
+        }
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f64 {
+        let (uvw, wo) = local_frame(r_in, rec);
+        match self.lobes(r_in, rec, wo) {
+            Some(lobes) => self.pdf_local(&lobes, wo, uvw.to_local(direction.unit_vector())),
+            None => 0.,
+        }
+    }
+}
+
+// Scattering by the particles of a participating medium, by a phase function instead of a
+// BSDF. Media have no surface, so there is no cosine term.
+#[derive(Debug, Clone)]
+pub struct Volumetric {
+    albedo: Texture, // Fraction of the light scattered rather than absorbed
+    phase: PhaseFunction,
+}
+
+impl Volumetric {
+    pub fn new(albedo: impl Into<Texture>, phase: PhaseFunction) -> Self {
+        Self {
+            albedo: albedo.into(),
+            phase,
+        }
+    }
+}
+
+impl Scatterable for Volumetric {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        let direction = self.phase.sample(r_in.direction());
+        Some(BsdfSample {
+            direction,
+            f: self.eval(r_in, rec, direction),
+            pdf: self.pdf(r_in, rec, direction),
+            is_delta: false,
+        })
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Vec3 {
+        self.albedo.value(rec.u, rec.v, rec.p) * self.phase.value(r_in.direction(), direction)
+    }
+
+    fn pdf(&self, r_in: &Ray, _rec: &HitRecord, direction: Vec3) -> f64 {
+        self.phase.value(r_in.direction(), direction)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DiffuseLight {
+    emit: Vec3,
+}
 
-        *attenuation = Vec3::ones();
-        *scattered = Ray::new(rec.p, direction);
-        true
+impl DiffuseLight {
+    pub fn new(emit: Vec3) -> Self {
+        Self { emit }
+    }
+}
+
+impl Scatterable for DiffuseLight {
+    fn sample(&self, _r_in: &Ray, _rec: &HitRecord) -> Option<BsdfSample> {
+        None
+    }
+
+    fn emitted(&self, _r_in: &Ray, rec: &HitRecord) -> Vec3 {
+        // Lights only emit from their front face.
+        match rec.front_face {
+            true => self.emit,
+            false => Vec3::zeros(),
+        }
+    }
+}
+
+// Material whose shading normal is perturbed by a bump or normal map.
+#[derive(Debug, Clone)]
+pub struct NormalMapped {
+    base: Material,
+    map: NormalMap,
+}
+
+impl Scatterable for NormalMapped {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        self.base.sample(r_in, rec)
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Vec3 {
+        self.base.eval(r_in, rec, direction)
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f64 {
+        self.base.pdf(r_in, rec, direction)
+    }
+
+    fn emitted(&self, r_in: &Ray, rec: &HitRecord) -> Vec3 {
+        self.base.emitted(r_in, rec)
+    }
+
+    fn absorption(&self) -> Vec3 {
+        self.base.absorption()
     }
 }
 
@@ -123,39 +797,248 @@ impl Scatterable for Dielectric {
 pub enum Material {
     Lambertian(Lambertian),
     Metal(Metal),
+    Conductor(Conductor),
     Dielectric(Dielectric),
+    DiffuseLight(DiffuseLight),
+    Principled(Arc<Principled>),
+    Volumetric(Volumetric),
+    NormalMapped(Arc<NormalMapped>),
 }
 
 impl Material {
-    pub fn lambertian(albedo: Vec3) -> Self {
+    pub fn lambertian(albedo: impl Into<Texture>) -> Self {
         Material::Lambertian(Lambertian::new(albedo))
     }
 
-    pub fn metal(albedo: Vec3, fuzz: f64) -> Self {
+    pub fn metal(albedo: impl Into<Texture>, fuzz: f64) -> Self {
         Material::Metal(Metal::new(albedo, fuzz))
     }
 
+    pub fn conductor(eta: Vec3, k: Vec3, roughness: f64) -> Self {
+        Material::Conductor(Conductor::new(eta, k, roughness))
+    }
+
     pub fn dielectric(ref_idx: f64) -> Self {
         Material::Dielectric(Dielectric::new(ref_idx))
     }
+
+    pub fn rough_dielectric(ref_idx: f64, roughness: f64) -> Self {
+        Material::Dielectric(Dielectric::new(ref_idx).with_roughness(roughness))
+    }
+
+    pub fn principled(principled: Principled) -> Self {
+        Material::Principled(Arc::new(principled))
+    }
+
+    pub fn isotropic(albedo: impl Into<Texture>) -> Self {
+        Material::Volumetric(Volumetric::new(albedo, PhaseFunction::Isotropic))
+    }
+
+    pub fn henyey_greenstein(albedo: impl Into<Texture>, g: f64) -> Self {
+        Material::Volumetric(Volumetric::new(albedo, PhaseFunction::henyey_greenstein(g)))
+    }
+
+    pub fn diffuse_light(emit: Vec3) -> Self {
+        Material::DiffuseLight(DiffuseLight::new(emit))
+    }
+
+    pub fn with_normal_map(self, map: NormalMap) -> Self {
+        Material::NormalMapped(Arc::new(NormalMapped { base: self, map }))
+    }
+
+    pub fn is_emissive(&self) -> bool {
+        match self {
+            Material::DiffuseLight(_) => true,
+            Material::NormalMapped(mapped) => mapped.base.is_emissive(),
+            _ => false,
+        }
+    }
+
+    pub fn perturb_normal(&self, rec: &mut HitRecord) {
+        // Applies the bump or normal map, if any, to a hit on this material. Must be called
+        // once per hit before scattering.
+        if let Material::NormalMapped(mapped) = self {
+            mapped.base.perturb_normal(rec);
+            mapped.map.apply(rec);
+        }
+    }
+
+    fn as_scatterable(&self) -> &dyn Scatterable {
+        match &self {
+            Material::Lambertian(lambertian) => lambertian,
+            Material::Metal(metal) => metal,
+            Material::Conductor(conductor) => conductor,
+            Material::Dielectric(dielectric) => dielectric,
+            Material::DiffuseLight(light) => light,
+            Material::Principled(principled) => principled.as_ref(),
+            Material::Volumetric(volumetric) => volumetric,
+            Material::NormalMapped(mapped) => mapped.as_ref(),
+        }
+    }
 }
 
 impl Scatterable for Material {
-    fn scatter(
-        &self,
-        r_in: &Ray,
-        rec: &mut HitRecord,
-        attenuation: &mut Vec3,
-        scattered: &mut Ray,
-    ) -> bool {
-        match &self {
-            Material::Lambertian(lambertian) => {
-                lambertian.scatter(r_in, rec, attenuation, scattered)
-            }
-            Material::Metal(metal) => metal.scatter(r_in, rec, attenuation, scattered),
-            Material::Dielectric(dielectric) => {
-                dielectric.scatter(r_in, rec, attenuation, scattered)
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        self.as_scatterable().sample(r_in, rec)
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Vec3 {
+        self.as_scatterable().eval(r_in, rec, direction)
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f64 {
+        self.as_scatterable().pdf(r_in, rec, direction)
+    }
+
+    fn emitted(&self, r_in: &Ray, rec: &HitRecord) -> Vec3 {
+        self.as_scatterable().emitted(r_in, rec)
+    }
+
+    fn absorption(&self) -> Vec3 {
+        self.as_scatterable().absorption()
+    }
+}
+
+#[test]
+fn test_scatterable() {
+    let mut rec = HitRecord::new();
+    rec.normal = Vec3::from_xyz(0., 1., 0.);
+    rec.front_face = true;
+    let r_in = Ray::new(Vec3::from_xyz(0., 1., -1.), Vec3::from_xyz(0., -1., 1.));
+
+    let lambertian = Material::lambertian(Vec3::from_rgb(0.5, 0.5, 0.5));
+    for _ in 0..16 {
+        let sample = lambertian.sample(&r_in, &rec).unwrap();
+        assert!(!sample.is_delta);
+        assert!(sample.direction.dot(rec.normal) > 0.);
+        assert!((sample.pdf - lambertian.pdf(&r_in, &rec, sample.direction)).abs() < 1e-12);
+        // Cosine sampling cancels everything but the albedo.
+        assert!((sample.weight() - Vec3::from_rgb(0.5, 0.5, 0.5)).near_zero());
+    }
+    let below = Vec3::from_xyz(0., -1., 0.);
+    assert_eq!(lambertian.pdf(&r_in, &rec, below), 0.);
+    assert_eq!(lambertian.eval(&r_in, &rec, below), Vec3::zeros());
+
+    let mirror = Material::metal(Vec3::ones(), 0.);
+    let sample = mirror.sample(&r_in, &rec).unwrap();
+    assert!(sample.is_delta);
+    assert!((sample.direction - Vec3::from_xyz(0., 1., 1.).unit_vector()).near_zero());
+    assert_eq!(mirror.pdf(&r_in, &rec, sample.direction), 0.);
+
+    let glass = Material::dielectric(1.5);
+    assert!(glass.sample(&r_in, &rec).unwrap().is_delta);
+    assert_eq!(glass.absorption(), Vec3::zeros());
+    // Green glass keeps half of the green light over 2 units of distance.
+    let green = Dielectric::new(1.5).with_transmittance(Vec3::from_rgb(0.1, 0.5, 1.), 2.);
+    let transmitted = (-2. * green.absorption().y()).exp();
+    assert!((transmitted - 0.5).abs() < 1e-12);
+    assert_eq!(green.absorption().z(), 0.);
+
+    // A smooth conductor is a mirror tinted by its Fresnel reflectance.
+    let gold = Material::Conductor(Conductor::preset("gold", 0.).unwrap());
+    let sample = gold.sample(&r_in, &rec).unwrap();
+    assert!(sample.is_delta);
+    assert!((sample.direction - Vec3::from_xyz(0., 1., 1.).unit_vector()).near_zero());
+    assert!(sample.f.x() > sample.f.z());
+    assert!(Conductor::preset("unobtainium", 0.).is_none());
+
+    // Rough lobes return the same value and density from sampling as from evaluation, and
+    // never reflect more energy than comes in.
+    let rough = [
+        Material::Conductor(Conductor::preset("aluminium", 0.4).unwrap()),
+        Material::rough_dielectric(1.5, 0.3),
+    ];
+    for material in rough {
+        let mut transmitted = 0;
+        let mut total = Vec3::zeros();
+        let n = 4000;
+        for _ in 0..n {
+            let Some(sample) = material.sample(&r_in, &rec) else {
+                continue;
+            };
+            assert!(!sample.is_delta);
+            let pdf = material.pdf(&r_in, &rec, sample.direction);
+            assert!((sample.pdf - pdf).abs() < 1e-6 * pdf.max(1.));
+            let f = material.eval(&r_in, &rec, sample.direction);
+            assert!((sample.f - f).length() < 1e-6 * f.length().max(1.));
+            if sample.direction.dot(rec.normal) < 0. {
+                transmitted += 1;
             }
+            total += sample.weight();
         }
+        assert!(total.x() / (n as f64) < 1.02);
+        assert_eq!(transmitted > 0, matches!(material, Material::Dielectric(_)));
     }
+
+    // Principled lobes mix consistently, whichever lobe drew the sample.
+    let red = Vec3::from_rgb(0.8, 0.1, 0.1);
+    let plastic = PrincipledBuilder::default()
+        .base_color(red)
+        .roughness(0.3)
+        .clearcoat(1.)
+        .sheen(0.5)
+        .build()
+        .unwrap();
+    let metal = PrincipledBuilder::default()
+        .base_color(red)
+        .metallic(1.)
+        .roughness(0.2)
+        .build()
+        .unwrap();
+    let glass = PrincipledBuilder::default()
+        .transmission(1.)
+        .roughness(0.)
+        .build()
+        .unwrap();
+    for principled in [plastic, metal, glass] {
+        let material = Material::principled(principled.clone());
+        let mut total = Vec3::zeros();
+        let n = 4000;
+        for _ in 0..n {
+            let Some(sample) = material.sample(&r_in, &rec) else {
+                continue;
+            };
+            assert!(!sample.is_delta);
+            let pdf = material.pdf(&r_in, &rec, sample.direction);
+            assert!((sample.pdf - pdf).abs() < 1e-6 * pdf.max(1.));
+            let f = material.eval(&r_in, &rec, sample.direction);
+            assert!((sample.f - f).length() < 1e-6 * f.length().max(1.));
+            total += sample.weight();
+        }
+        let albedo = total / n as f64;
+        assert!(albedo.x() < 1.05);
+        // Plastic and metal reflect the red base color, clear glass is neutral.
+        assert_eq!(albedo.x() > 2. * albedo.y(), principled.transmission == 0.);
+    }
+
+    // Out of range weights are clamped, so even at grazing angles the lobes stay non-negative
+    // and sampling agrees with the density.
+    let extreme = PrincipledBuilder::default()
+        .base_color(red)
+        .clearcoat(2.)
+        .specular(5.)
+        .sheen(3.)
+        .sheen_tint(-1.)
+        .build()
+        .unwrap();
+    let material = Material::principled(extreme);
+    let grazing = Ray::new(Vec3::from_xyz(0., 1., -1.), Vec3::from_xyz(0., -0.05, 1.));
+    for r_in in [&r_in, &grazing] {
+        for _ in 0..1000 {
+            let direction = Vec3::random_unit_vector();
+            let f = material.eval(r_in, &rec, direction);
+            assert!(f.e.iter().all(|c| *c >= 0. && c.is_finite()));
+            assert!(material.pdf(r_in, &rec, direction) >= 0.);
+            let Some(sample) = material.sample(r_in, &rec) else {
+                continue;
+            };
+            let pdf = material.pdf(r_in, &rec, sample.direction);
+            assert!((sample.pdf - pdf).abs() < 1e-6 * pdf.max(1.));
+            assert!(sample.weight().e.iter().all(|c| *c >= 0. && c.is_finite()));
+        }
+    }
+
+    let light = Material::diffuse_light(Vec3::ones());
+    assert!(light.sample(&r_in, &rec).is_none());
+    assert_eq!(light.emitted(&r_in, &rec), Vec3::ones());
 }
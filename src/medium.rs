@@ -0,0 +1,378 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::{Material, Volumetric};
+use crate::onb::Onb;
+use crate::random::random_f64;
+use crate::ray::Ray;
+use crate::vector::Vec3;
+use crate::voxel_grid::VoxelGrid;
+
+// Angular distribution of the light scattered by particles of a medium. Directions are the
+// propagation direction `d` of the incoming light and the scattered direction `wi`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PhaseFunction {
+    Isotropic,
+    HenyeyGreenstein { g: f64 }, // Mean cosine of the scattering angle, > 0 scatters forward
+}
+
+impl PhaseFunction {
+    pub fn henyey_greenstein(g: f64) -> Self {
+        PhaseFunction::HenyeyGreenstein {
+            g: g.clamp(-0.99, 0.99),
+        }
+    }
+
+    pub fn value(&self, d: Vec3, wi: Vec3) -> f64 {
+        // Density per solid angle, which is also the density of `sample`.
+        match self {
+            PhaseFunction::Isotropic => 1. / (4. * PI),
+            PhaseFunction::HenyeyGreenstein { g } => {
+                let cos_theta = d.unit_vector().dot(wi.unit_vector());
+                let denom = 1. + g * g - 2. * g * cos_theta;
+                (1. - g * g) / (4. * PI * denom * denom.max(0.).sqrt())
+            }
+        }
+    }
+
+    pub fn sample(&self, d: Vec3) -> Vec3 {
+        let (u1, u2) = (random_f64(), random_f64());
+        let cos_theta = match self {
+            PhaseFunction::HenyeyGreenstein { g } if g.abs() >= 1e-3 => {
+                // Inverted cumulative distribution of the scattering angle.
+                let s = (1. - g * g) / (1. - g + 2. * g * u1);
+                (1. + g * g - s * s) / (2. * g)
+            }
+            _ => 1. - 2. * u1,
+        };
+        let cos_theta = cos_theta.clamp(-1., 1.);
+        let sin_theta = (1. - cos_theta * cos_theta).sqrt();
+        let phi = 2. * PI * u2;
+        Onb::new(d.unit_vector()).transform(Vec3::from_xyz(
+            sin_theta * phi.cos(),
+            sin_theta * phi.sin(),
+            cos_theta,
+        ))
+    }
+}
+
+// Homogeneous participating medium filling a closed boundary, such as smoke or fog. Rays
+// scatter off it after exponentially distributed free-flight distances.
+pub struct ConstantMedium {
+    boundary: Arc<dyn Hittable>,
+    neg_inv_density: f64,
+    phase: Material, // Scatters the light at the sampled points, usually `Material::isotropic`
+}
+
+impl ConstantMedium {
+    pub fn new(boundary: impl Hittable + 'static, density: f64, phase: Material) -> Self {
+        assert!(density > 0., "medium density must be positive");
+        Self {
+            boundary: Arc::new(boundary),
+            neg_inv_density: -1. / density,
+            phase,
+        }
+    }
+}
+
+impl ConstantMedium {
+    fn segment(&self, r: &Ray, r_t: Interval) -> Option<Interval> {
+        // Entry and exit of the boundary along the whole line, so rays starting inside the
+        // medium are handled as well. The boundary is assumed convex.
+        let mut rec1 = HitRecord::new();
+        let mut rec2 = HitRecord::new();
+        if !self.boundary.hit(r, Interval::universe(), &mut rec1) {
+            return None;
+        }
+        if !self
+            .boundary
+            .hit(r, Interval::new(rec1.t + 1e-4, f64::INFINITY), &mut rec2)
+        {
+            return None;
+        }
+        let t_enter = rec1.t.max(r_t.min()).max(0.);
+        let t_exit = rec2.t.min(r_t.max());
+        match t_enter < t_exit {
+            true => Some(Interval::new(t_enter, t_exit)),
+            false => None,
+        }
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, r: &Ray, r_t: Interval, rec: &mut HitRecord) -> bool {
+        let Some(inside) = self.segment(r, r_t) else {
+            return false;
+        };
+        let ray_length = r.direction().length();
+        let distance_inside = inside.size() * ray_length;
+        let hit_distance = self.neg_inv_density * random_f64().ln();
+        if hit_distance > distance_inside {
+            return false;
+        }
+
+        set_collision(
+            rec,
+            r,
+            inside.min() + hit_distance / ray_length,
+            &self.phase,
+        );
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+
+    fn transmittance(&self, r: &Ray, r_t: Interval) -> f64 {
+        match self.segment(r, r_t) {
+            Some(inside) => (inside.size() * r.direction().length() / self.neg_inv_density).exp(),
+            None => 1.,
+        }
+    }
+}
+
+// Participating medium with a density varying in space, given by a voxel grid stretched over
+// a box. Collisions are sampled by delta tracking against the largest density, and shadow rays
+// use ratio tracking.
+pub struct GridMedium {
+    bbox: Aabb,
+    grid: Arc<VoxelGrid>,
+    density: f64, // Scales the grid values
+    phase: Material,
+}
+
+impl GridMedium {
+    pub fn new(grid: Arc<VoxelGrid>, bbox: Aabb, density: f64, phase: Material) -> Self {
+        Self {
+            bbox,
+            grid,
+            density,
+            phase,
+        }
+    }
+
+    fn density_at(&self, p: Vec3) -> f64 {
+        let (min, max) = (self.bbox.min(), self.bbox.max());
+        let local = Vec3::new([0, 1, 2].map(|k| (p.e[k] - min.e[k]) / (max.e[k] - min.e[k])));
+        self.density * self.grid.density(local)
+    }
+
+    fn majorant(&self) -> f64 {
+        self.density * self.grid.max()
+    }
+
+    fn track(&self, r: &Ray, r_t: Interval, mut collide: impl FnMut(f64, f64) -> bool) {
+        // Steps through the box with exponential free flights against the majorant. `collide`
+        // gets each tentative collision and its ratio of real density, and stops on true.
+        let majorant = self.majorant();
+        let Some(inside) = self.bbox.clip(r, r_t) else {
+            return;
+        };
+        // Infinite or NaN majorants would never advance along the ray.
+        if majorant <= 0. || !majorant.is_finite() {
+            return;
+        }
+        let step = -1. / (majorant * r.direction().length());
+        let mut t = inside.min();
+        loop {
+            t += step * random_f64().ln();
+            if t >= inside.max() {
+                return;
+            }
+            if collide(t, self.density_at(r.at(t)) / majorant) {
+                return;
+            }
+        }
+    }
+}
+
+impl Hittable for GridMedium {
+    fn hit(&self, r: &Ray, r_t: Interval, rec: &mut HitRecord) -> bool {
+        // Delta tracking: tentative collisions are real with the probability of the density
+        // ratio, the others are null collisions that leave the ray unchanged.
+        let mut t_hit = None;
+        self.track(r, r_t, |t, ratio| {
+            let real = random_f64() < ratio;
+            if real {
+                t_hit = Some(t);
+            }
+            real
+        });
+        match t_hit {
+            Some(t) => {
+                set_collision(rec, r, t, &self.phase);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn transmittance(&self, r: &Ray, r_t: Interval) -> f64 {
+        // Ratio tracking: weights every tentative collision by its null fraction instead of
+        // stopping at the first real one, with Russian roulette once little light is left.
+        let mut transmittance = 1.;
+        self.track(r, r_t, |_, ratio| {
+            transmittance *= 1. - ratio.min(1.);
+            if transmittance < 0.1 {
+                if random_f64() < 0.5 {
+                    transmittance = 0.;
+                    return true;
+                }
+                transmittance *= 2.;
+            }
+            false
+        });
+        transmittance
+    }
+}
+
+// Homogeneous medium filling the bounds of the whole scene, such as fog or haze. It lives on the
+// camera rather than in the world, so rays escaping to the background pass through it too.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fog {
+    density: f64,
+    albedo: Vec3, // Fraction of the light scattered rather than absorbed
+    phase: PhaseFunction,
+}
+
+impl Fog {
+    pub fn new(density: f64, albedo: Vec3, g: f64) -> Self {
+        Self {
+            density: density.max(0.),
+            albedo,
+            phase: PhaseFunction::henyey_greenstein(g),
+        }
+    }
+
+    pub fn sample(&self, r: &Ray, bounds: Aabb, t_max: f64) -> Option<f64> {
+        // Free-flight distance to a scattering event before `t_max`, if any.
+        let inside = bounds.clip(r, Interval::new(1e-3, t_max))?;
+        let t = inside.min() - random_f64().ln() / (self.density * r.direction().length());
+        (t < inside.max()).then_some(t)
+    }
+
+    pub fn transmittance(&self, r: &Ray, bounds: Aabb, r_t: Interval) -> f64 {
+        match bounds.clip(r, r_t) {
+            Some(inside) => (-self.density * inside.size() * r.direction().length()).exp(),
+            None => 1.,
+        }
+    }
+
+    pub fn collide(&self, rec: &mut HitRecord, r: &Ray, t: f64) {
+        let phase = Material::Volumetric(Volumetric::new(self.albedo, self.phase));
+        set_collision(rec, r, t, &phase);
+    }
+}
+
+fn set_collision(rec: &mut HitRecord, r: &Ray, t: f64, phase: &Material) {
+    rec.t = t;
+    rec.p = r.at(t);
+    // Media have no surface. A zero normal keeps surface logic such as normal maps and the
+    // tracking of dielectric interiors out.
+    rec.normal = Vec3::zeros();
+    rec.geometric_normal = Vec3::zeros();
+    rec.front_face = true;
+    (rec.u, rec.v) = (0., 0.);
+    (rec.dpdu, rec.dpdv) = (Vec3::zeros(), Vec3::zeros());
+    rec.color = None;
+    rec.mat = phase.clone();
+}
+
+#[test]
+fn test_medium() {
+    use crate::sphere::Sphere;
+
+    // Henyey-Greenstein samples have the mean cosine g, and the density integrates to 1.
+    let d = Vec3::from_xyz(0., 0., -1.);
+    for phase in [
+        PhaseFunction::Isotropic,
+        PhaseFunction::henyey_greenstein(0.6),
+    ] {
+        let n = 20000;
+        let mean: f64 = (0..n).map(|_| phase.sample(d).dot(d)).sum::<f64>() / n as f64;
+        let g = match phase {
+            PhaseFunction::Isotropic => 0.,
+            PhaseFunction::HenyeyGreenstein { g } => g,
+        };
+        assert!((mean - g).abs() < 0.02);
+        let steps = 2000;
+        let integral: f64 = (0..steps)
+            .map(|i| {
+                let cos_theta = -1. + 2. * (i as f64 + 0.5) / steps as f64;
+                let wi = Vec3::from_xyz((1. - cos_theta * cos_theta).sqrt(), 0., -cos_theta);
+                phase.value(d, wi) * 2. * PI * 2. / steps as f64
+            })
+            .sum();
+        assert!((integral - 1.).abs() < 1e-3);
+    }
+
+    // The fraction of rays crossing a unit sphere unscattered is exp(-density * 2).
+    let boundary = Sphere::new(Vec3::zeros(), 1., Material::isotropic(Vec3::ones()));
+    let medium = ConstantMedium::new(boundary, 0.5, Material::isotropic(Vec3::ones()));
+    let r = Ray::new(Vec3::from_xyz(0., 0., 5.), d);
+    let n = 20000;
+    let mut rec = HitRecord::new();
+    let passed = (0..n)
+        .filter(|_| !medium.hit(&r, Interval::new(1e-3, f64::INFINITY), &mut rec))
+        .count();
+    assert!((passed as f64 / n as f64 - (-1f64).exp()).abs() < 0.02);
+    // Rays starting inside only travel the rest of the way.
+    let inside = Ray::new(Vec3::zeros(), d);
+    let mut t_max: f64 = 0.;
+    for _ in 0..1000 {
+        if medium.hit(&inside, Interval::new(1e-3, f64::INFINITY), &mut rec) {
+            t_max = t_max.max(rec.t);
+            assert_eq!(rec.normal, Vec3::zeros());
+        }
+    }
+    assert!(t_max <= 1.);
+    let expected = (-1f64).exp();
+    let transmittance = medium.transmittance(&r, Interval::new(1e-3, f64::INFINITY));
+    assert!((transmittance - expected).abs() < 1e-9);
+
+    // A uniform grid behaves like a constant medium, through delta and ratio tracking alike.
+    let grid = Arc::new(VoxelGrid::new([2, 2, 2], vec![0.25; 8]));
+    let bbox = Aabb::from_points(Vec3::from_xyz(-1., -1., -1.), Vec3::ones());
+    let medium = GridMedium::new(grid, bbox, 2., Material::isotropic(Vec3::ones()));
+    let passed = (0..n)
+        .filter(|_| !medium.hit(&r, Interval::new(1e-3, f64::INFINITY), &mut rec))
+        .count();
+    assert!((passed as f64 / n as f64 - expected).abs() < 0.02);
+    let mean: f64 = (0..n)
+        .map(|_| medium.transmittance(&r, Interval::new(1e-3, f64::INFINITY)))
+        .sum::<f64>()
+        / n as f64;
+    assert!((mean - expected).abs() < 0.02);
+    assert_eq!(medium.transmittance(&r, Interval::new(1e-3, 3.)), 1.);
+    // Degenerate densities leave the volume empty rather than stepping forever.
+    for density in [f64::INFINITY, f64::NAN] {
+        let grid = Arc::new(VoxelGrid::new([1, 1, 1], vec![1.]));
+        let medium = GridMedium::new(grid, bbox, density, Material::isotropic(Vec3::ones()));
+        assert!(!medium.hit(&r, Interval::new(1e-3, f64::INFINITY), &mut rec));
+    }
+
+    // Fog only fills its bounds.
+    let fog = Fog::new(0.5, Vec3::ones(), 0.);
+    let transmittance = fog.transmittance(&r, bbox, Interval::new(1e-3, f64::INFINITY));
+    assert!((transmittance - expected).abs() < 1e-9);
+    let passed = (0..n)
+        .filter(|_| fog.sample(&r, bbox, f64::INFINITY).is_none())
+        .count();
+    assert!((passed as f64 / n as f64 - expected).abs() < 0.02);
+    assert!(fog.sample(&r, bbox, 3.).is_none());
+    // Its transmittance is exp(-density * distance) over the part of the ray inside the bounds,
+    // measured in world units rather than in ray parameters.
+    let fast = Ray::new(Vec3::from_xyz(0., 0., 5.), d * 2.);
+    let transmittance = fog.transmittance(&fast, bbox, Interval::new(1e-3, 2.75));
+    assert!((transmittance - (-0.5f64 * 1.5).exp()).abs() < 1e-9);
+    let transmittance = fog.transmittance(&inside, bbox, Interval::new(0., f64::INFINITY));
+    assert!((transmittance - (-0.5f64 * 1.).exp()).abs() < 1e-9);
+}
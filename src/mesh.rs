@@ -0,0 +1,331 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::bvh::BvhNode;
+use crate::hittable::{HitRecord, Hittable, LightSample};
+use crate::hittable_list::HittableList;
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::triangle;
+use crate::vector::Vec3;
+
+// Vertex buffers shared by every face of a mesh.
+#[derive(Debug)]
+struct MeshData {
+    positions: Vec<Vec3>,
+    normals: Option<Vec<Vec3>>,
+    uvs: Option<Vec<(f64, f64)>>,
+    colors: Option<Vec<Vec3>>, // Diffuse albedo of each vertex
+    indices: Vec<[usize; 3]>,
+    mat: Material,
+}
+
+impl MeshData {
+    fn vertices(&self, face: usize) -> [Vec3; 3] {
+        let [i0, i1, i2] = self.indices[face];
+        [self.positions[i0], self.positions[i1], self.positions[i2]]
+    }
+
+    fn normals(&self, face: usize) -> Option<[Vec3; 3]> {
+        let [i0, i1, i2] = self.indices[face];
+        self.normals.as_ref().map(|n| [n[i0], n[i1], n[i2]])
+    }
+
+    fn uvs(&self, face: usize) -> Option<[(f64, f64); 3]> {
+        let [i0, i1, i2] = self.indices[face];
+        self.uvs.as_ref().map(|uv| [uv[i0], uv[i1], uv[i2]])
+    }
+
+    fn color(&self, face: usize, barycentric: Vec3) -> Option<Vec3> {
+        // Vertex colors are interpolated across the face into a diffuse albedo.
+        let [i0, i1, i2] = self.indices[face];
+        self.colors
+            .as_ref()
+            .map(|c| barycentric.x() * c[i0] + barycentric.y() * c[i1] + barycentric.z() * c[i2])
+    }
+}
+
+struct MeshTriangle {
+    mesh: Arc<MeshData>,
+    face: usize,
+    bbox: Aabb,
+}
+
+impl MeshTriangle {
+    fn new(mesh: Arc<MeshData>, face: usize) -> Self {
+        let [v0, v1, v2] = mesh.vertices(face);
+        let bbox = Aabb::surrounding(Aabb::from_points(v0, v1), Aabb::from_points(v2, v2));
+        Self {
+            mesh,
+            face,
+            bbox: bbox.pad(),
+        }
+    }
+}
+
+impl Hittable for MeshTriangle {
+    fn hit(&self, r: &Ray, r_t: Interval, rec: &mut HitRecord) -> bool {
+        let vertices = self.mesh.vertices(self.face);
+        match triangle::intersect(r, r_t, vertices) {
+            Some(hit) => {
+                let face = self.face;
+                let (normals, uvs) = (self.mesh.normals(face), self.mesh.uvs(face));
+                triangle::set_hit_record(r, hit, vertices, normals, uvs, rec);
+                rec.mat = self.mesh.mat.clone();
+                rec.color = self.mesh.color(face, rec.barycentric);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn is_light(&self) -> bool {
+        self.mesh.mat.is_emissive()
+    }
+
+    fn sample(&self, origin: Vec3) -> Option<LightSample> {
+        triangle::sample(self.mesh.vertices(self.face), origin)
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3, r_t: Interval) -> f64 {
+        triangle::pdf_value(self.mesh.vertices(self.face), origin, direction, r_t)
+    }
+}
+
+#[derive(Clone)]
+pub struct TriangleMesh {
+    mesh: Arc<MeshData>,
+    bvh: BvhNode,
+}
+
+impl TriangleMesh {
+    pub fn new(positions: Vec<Vec3>, indices: Vec<[usize; 3]>, mat: Material) -> Self {
+        TriangleMesh::from_buffers(positions, None, None, None, indices, mat)
+    }
+
+    pub fn with_normals(
+        positions: Vec<Vec3>,
+        normals: Vec<Vec3>,
+        indices: Vec<[usize; 3]>,
+        mat: Material,
+    ) -> Self {
+        TriangleMesh::from_buffers(positions, Some(normals), None, None, indices, mat)
+    }
+
+    pub fn from_buffers(
+        positions: Vec<Vec3>,
+        normals: Option<Vec<Vec3>>,
+        uvs: Option<Vec<(f64, f64)>>,
+        colors: Option<Vec<Vec3>>,
+        indices: Vec<[usize; 3]>,
+        mat: Material,
+    ) -> Self {
+        // Vertex normals, texture coordinates and colors are indexed like positions and
+        // interpolated across each face.
+        if let Some(normals) = &normals {
+            assert_eq!(
+                positions.len(),
+                normals.len(),
+                "mesh needs exactly one normal per vertex"
+            );
+        }
+        if let Some(uvs) = &uvs {
+            assert_eq!(
+                positions.len(),
+                uvs.len(),
+                "mesh needs exactly one texture coordinate per vertex"
+            );
+        }
+        if let Some(colors) = &colors {
+            assert_eq!(
+                positions.len(),
+                colors.len(),
+                "mesh needs exactly one color per vertex"
+            );
+        }
+        let normals = normals.map(|n| n.into_iter().map(|n| n.unit_vector()).collect());
+        // Vertex colors replace the material with a diffuse one, shared by all faces so hits
+        // only need to interpolate the color.
+        let mat = match colors {
+            Some(_) => Material::lambertian(Vec3::ones()),
+            None => mat,
+        };
+        assert!(
+            indices.iter().flatten().all(|&i| i < positions.len()),
+            "mesh face references a vertex out of range"
+        );
+        let mesh = Arc::new(MeshData {
+            positions,
+            normals,
+            uvs,
+            colors,
+            indices,
+            mat,
+        });
+
+        let mut faces = HittableList::new();
+        for face in 0..mesh.indices.len() {
+            faces.add(MeshTriangle::new(mesh.clone(), face));
+        }
+        let bvh = BvhNode::new(&faces);
+        Self { mesh, bvh }
+    }
+
+    pub fn num_vertices(&self) -> usize {
+        self.mesh.positions.len()
+    }
+
+    pub fn num_faces(&self) -> usize {
+        self.mesh.indices.len()
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, r: &Ray, r_t: Interval, rec: &mut HitRecord) -> bool {
+        self.bvh.hit(r, r_t, rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bvh.bounding_box()
+    }
+
+    fn collect_lights(&self, lights: &mut HittableList) {
+        // Emissive meshes are sampled face by face, like separate triangles.
+        if self.mesh.mat.is_emissive() {
+            for face in 0..self.num_faces() {
+                lights.add(MeshTriangle::new(self.mesh.clone(), face));
+            }
+        }
+    }
+}
+
+pub(crate) fn triangulate(points: &[Vec3]) -> Vec<[usize; 3]> {
+    // Ear-clipping triangulation of a (possibly concave) planar polygon.
+    let n = points.len();
+    if n == 3 {
+        return vec![[0, 1, 2]];
+    }
+
+    // Project onto the plane of the polygon, dropping the dominant axis of its Newell normal.
+    let mut normal = Vec3::zeros();
+    for i in 0..n {
+        let (a, b) = (points[i], points[(i + 1) % n]);
+        normal += a.cross(b);
+    }
+    let axis = (0..3)
+        .max_by(|&a, &b| normal.e[a].abs().total_cmp(&normal.e[b].abs()))
+        .unwrap();
+    let (ax0, ax1) = ((axis + 1) % 3, (axis + 2) % 3);
+    let winding = normal.e[axis].signum();
+    let project = |i: usize| (points[i].e[ax0], points[i].e[ax1]);
+    let cross = |o: (f64, f64), a: (f64, f64), b: (f64, f64)| {
+        winding * ((a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0))
+    };
+
+    let mut remaining: Vec<usize> = (0..n).collect();
+    let mut triangles = Vec::with_capacity(n - 2);
+    while remaining.len() > 3 {
+        let m = remaining.len();
+        let ear = (0..m).find(|&k| {
+            let (ia, ib, ic) = (
+                remaining[(k + m - 1) % m],
+                remaining[k],
+                remaining[(k + 1) % m],
+            );
+            let (a, b, c) = (project(ia), project(ib), project(ic));
+            if cross(a, b, c) <= 0. {
+                return false;
+            }
+            // No other vertex may lie inside the candidate ear.
+            !remaining.iter().any(|&j| {
+                let p = project(j);
+                j != ia
+                    && j != ib
+                    && j != ic
+                    && cross(a, b, p) >= 0.
+                    && cross(b, c, p) >= 0.
+                    && cross(c, a, p) >= 0.
+            })
+        });
+        match ear {
+            Some(k) => {
+                triangles.push([
+                    remaining[(k + m - 1) % m],
+                    remaining[k],
+                    remaining[(k + 1) % m],
+                ]);
+                remaining.remove(k);
+            }
+            // Degenerate or self-intersecting polygon, fall back to a fan.
+            None => break,
+        }
+    }
+    for k in 1..remaining.len() - 1 {
+        triangles.push([remaining[0], remaining[k], remaining[k + 1]]);
+    }
+    triangles
+}
+
+#[test]
+fn test_triangle_mesh() {
+    // A unit quad in the z = -1 plane made of two triangles.
+    let positions = vec![
+        Vec3::from_xyz(0., 0., -1.),
+        Vec3::from_xyz(1., 0., -1.),
+        Vec3::from_xyz(1., 1., -1.),
+        Vec3::from_xyz(0., 1., -1.),
+    ];
+    let indices = vec![[0, 1, 2], [0, 2, 3]];
+    let mesh = TriangleMesh::new(positions, indices, Material::lambertian(Vec3::zeros()));
+    assert_eq!(mesh.num_vertices(), 4);
+    assert_eq!(mesh.num_faces(), 2);
+
+    let r_t = Interval::new(1e-3, f64::INFINITY);
+    let mut rec = HitRecord::new();
+    for (x, y) in [(0.75, 0.25), (0.25, 0.75)] {
+        let r = Ray::new(Vec3::from_xyz(x, y, 0.), Vec3::from_xyz(0., 0., -1.));
+        assert!(mesh.hit(&r, r_t, &mut rec));
+        assert_eq!(rec.p, Vec3::from_xyz(x, y, -1.));
+    }
+    let r = Ray::new(Vec3::from_xyz(1.5, 0.5, 0.), Vec3::from_xyz(0., 0., -1.));
+    assert!(!mesh.hit(&r, r_t, &mut rec));
+
+    // Emissive meshes contribute each of their faces as a light.
+    let mut lights = HittableList::new();
+    mesh.collect_lights(&mut lights);
+    assert!(lights.is_empty());
+    let light = TriangleMesh::new(
+        mesh.mesh.positions.clone(),
+        mesh.mesh.indices.clone(),
+        Material::diffuse_light(Vec3::ones()),
+    );
+    light.collect_lights(&mut lights);
+    assert_eq!(lights.len(), 2);
+    for face in lights.objects() {
+        let sample = face.sample(Vec3::zeros()).unwrap();
+        let pdf = face.pdf_value(Vec3::zeros(), sample.p, r_t);
+        assert!((pdf - sample.pdf).abs() < 1e-9 * pdf);
+    }
+
+    // Vertex colors go into the hit record and don't leak into later hits on other surfaces.
+    let colored = TriangleMesh::from_buffers(
+        mesh.mesh.positions.clone(),
+        None,
+        None,
+        Some(vec![Vec3::ones(); 4]),
+        mesh.mesh.indices.clone(),
+        Material::diffuse_light(Vec3::ones()),
+    );
+    colored.collect_lights(&mut lights);
+    assert_eq!(lights.len(), 2);
+    let r = Ray::new(Vec3::from_xyz(0.25, 0.75, 0.), Vec3::from_xyz(0., 0., -1.));
+    assert!(colored.hit(&r, r_t, &mut rec));
+    assert_eq!(rec.color, Some(Vec3::ones()));
+    assert!(mesh.hit(&r, r_t, &mut rec));
+    assert_eq!(rec.color, None);
+}
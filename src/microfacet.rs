@@ -0,0 +1,290 @@
+use std::f64::consts::PI;
+
+use crate::vector::Vec3;
+
+// Directions below are in the local shading frame, with the surface normal along +z.
+
+// Trowbridge-Reitz (GGX) distribution of microfacet normals.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrowbridgeReitz {
+    alpha: f64,
+}
+
+impl TrowbridgeReitz {
+    pub fn new(roughness: f64) -> Self {
+        // Perceptual roughness in [0, 1], squared to the width of the distribution.
+        let roughness = roughness.clamp(0., 1.);
+        Self {
+            alpha: roughness * roughness,
+        }
+    }
+
+    pub fn alpha(&self) -> f64 {
+        self.alpha
+    }
+
+    pub fn is_smooth(&self) -> bool {
+        // Narrow lobes are handled as perfect specular to avoid numerical trouble.
+        self.alpha < 1e-3
+    }
+
+    pub fn d(&self, wm: Vec3) -> f64 {
+        // Density of microfacet normals `wm`, projected onto the macro surface.
+        let cos2_theta = wm.z() * wm.z();
+        if cos2_theta <= 0. {
+            return 0.;
+        }
+        let tan2_theta = (1. - cos2_theta).max(0.) / cos2_theta;
+        let a2 = self.alpha * self.alpha;
+        let e = 1. + tan2_theta / a2;
+        1. / (PI * a2 * cos2_theta * cos2_theta * e * e)
+    }
+
+    fn lambda(&self, w: Vec3) -> f64 {
+        let cos2_theta = w.z() * w.z();
+        if cos2_theta <= 0. {
+            return f64::INFINITY;
+        }
+        let tan2_theta = (1. - cos2_theta).max(0.) / cos2_theta;
+        ((1. + self.alpha * self.alpha * tan2_theta).sqrt() - 1.) / 2.
+    }
+
+    pub fn g1(&self, w: Vec3) -> f64 {
+        // Smith masking of the microsurface seen from `w`.
+        1. / (1. + self.lambda(w))
+    }
+
+    pub fn g(&self, wo: Vec3, wi: Vec3) -> f64 {
+        // Height-correlated Smith masking and shadowing.
+        1. / (1. + self.lambda(wo) + self.lambda(wi))
+    }
+
+    pub fn d_visible(&self, w: Vec3, wm: Vec3) -> f64 {
+        // Density of the microfacet normals visible from `w`, which face towards it.
+        let cos_theta = w.z().abs();
+        if cos_theta <= 0. {
+            return 0.;
+        }
+        self.g1(w) / cos_theta * self.d(wm) * w.dot(wm).max(0.)
+    }
+
+    pub fn sample_wm(&self, w: Vec3, u: (f64, f64)) -> Vec3 {
+        // Samples a visible normal with density `d_visible` (Heitz 2018): stretch `w` to the
+        // hemisphere configuration, sample the projected disk, then unstretch.
+        let w = match w.z() < 0. {
+            true => -w,
+            false => w,
+        };
+        let wh = Vec3::from_xyz(self.alpha * w.x(), self.alpha * w.y(), w.z()).unit_vector();
+        let t1 = match wh.z() < 0.99999 {
+            true => Vec3::from_xyz(0., 0., 1.).cross(wh).unit_vector(),
+            false => Vec3::from_xyz(1., 0., 0.),
+        };
+        let t2 = wh.cross(t1);
+
+        let r = u.0.sqrt();
+        let phi = 2. * PI * u.1;
+        let (px, py) = (r * phi.cos(), r * phi.sin());
+        // Warp the disk sample to the visible half of it.
+        let s = 0.5 * (1. + wh.z());
+        let h = (1. - px * px).max(0.).sqrt();
+        let py = (1. - s) * h + s * py;
+        let pz = (1. - px * px - py * py).max(0.).sqrt();
+
+        let nh = px * t1 + py * t2 + pz * wh;
+        Vec3::from_xyz(self.alpha * nh.x(), self.alpha * nh.y(), nh.z().max(1e-6)).unit_vector()
+    }
+
+    pub fn pdf(&self, w: Vec3, wm: Vec3) -> f64 {
+        self.d_visible(w, wm)
+    }
+}
+
+pub fn reflect(wo: Vec3, n: Vec3) -> Vec3 {
+    // Mirror of `wo` about `n`, both pointing away from the surface.
+    -wo + 2. * wo.dot(n) * n
+}
+
+pub fn refract(wi: Vec3, n: Vec3, eta: f64) -> Option<Vec3> {
+    // Refracts `wi` (pointing away from the surface, on the side of `n`) into the medium with
+    // relative index of refraction `eta`. None on total internal reflection.
+    let cos_theta_i = n.dot(wi);
+    let sin2_theta_i = (1. - cos_theta_i * cos_theta_i).max(0.);
+    let sin2_theta_t = sin2_theta_i / (eta * eta);
+    if sin2_theta_t >= 1. {
+        return None;
+    }
+    let cos_theta_t = (1. - sin2_theta_t).sqrt();
+    Some(-wi / eta + (cos_theta_i / eta - cos_theta_t) * n)
+}
+
+pub fn fresnel_dielectric(cos_theta_i: f64, eta: f64) -> f64 {
+    // Unpolarized Fresnel reflectance of a dielectric interface, `eta` is the index of
+    // refraction of the far side relative to the side of the normal.
+    let (cos_theta_i, eta) = match cos_theta_i < 0. {
+        true => (-cos_theta_i.max(-1.), 1. / eta),
+        false => (cos_theta_i.min(1.), eta),
+    };
+    let sin2_theta_t = (1. - cos_theta_i * cos_theta_i) / (eta * eta);
+    if sin2_theta_t >= 1. {
+        return 1.;
+    }
+    let cos_theta_t = (1. - sin2_theta_t).max(0.).sqrt();
+    let r_parl = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    let r_perp = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+    (r_parl * r_parl + r_perp * r_perp) / 2.
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    fn add(self, o: Complex) -> Complex {
+        Complex::new(self.re + o.re, self.im + o.im)
+    }
+
+    fn sub(self, o: Complex) -> Complex {
+        Complex::new(self.re - o.re, self.im - o.im)
+    }
+
+    fn mul(self, o: Complex) -> Complex {
+        Complex::new(
+            self.re * o.re - self.im * o.im,
+            self.re * o.im + self.im * o.re,
+        )
+    }
+
+    fn div(self, o: Complex) -> Complex {
+        let scale = 1. / (o.re * o.re + o.im * o.im);
+        Complex::new(
+            scale * (self.re * o.re + self.im * o.im),
+            scale * (self.im * o.re - self.re * o.im),
+        )
+    }
+
+    fn norm(self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+
+    fn sqrt(self) -> Complex {
+        // Principal square root.
+        let n = self.norm().sqrt();
+        if n == 0. {
+            return Complex::new(0., 0.);
+        }
+        let t1 = (0.5 * (n + self.re.abs())).sqrt();
+        let t2 = 0.5 * self.im / t1;
+        match self.re >= 0. {
+            true => Complex::new(t1, t2),
+            false => Complex::new(t2.abs(), t1.copysign(self.im)),
+        }
+    }
+}
+
+fn fresnel_complex_channel(cos_theta_i: f64, eta: f64, k: f64) -> f64 {
+    let cos_theta_i = Complex::new(cos_theta_i.clamp(0., 1.), 0.);
+    let eta = Complex::new(eta, k);
+    let one = Complex::new(1., 0.);
+    let sin2_theta_i = one.sub(cos_theta_i.mul(cos_theta_i));
+    let sin2_theta_t = sin2_theta_i.div(eta.mul(eta));
+    let cos_theta_t = one.sub(sin2_theta_t).sqrt();
+
+    let r_parl = eta
+        .mul(cos_theta_i)
+        .sub(cos_theta_t)
+        .div(eta.mul(cos_theta_i).add(cos_theta_t));
+    let r_perp = cos_theta_i
+        .sub(eta.mul(cos_theta_t))
+        .div(cos_theta_i.add(eta.mul(cos_theta_t)));
+    (r_parl.norm() + r_perp.norm()) / 2.
+}
+
+pub fn fresnel_complex(cos_theta_i: f64, eta: Vec3, k: Vec3) -> Vec3 {
+    // Fresnel reflectance of a conductor with complex index of refraction eta + ik per channel.
+    Vec3::from_rgb(
+        fresnel_complex_channel(cos_theta_i, eta.x(), k.x()),
+        fresnel_complex_channel(cos_theta_i, eta.y(), k.y()),
+        fresnel_complex_channel(cos_theta_i, eta.z(), k.z()),
+    )
+}
+
+#[test]
+fn test_microfacet() {
+    use crate::random::{random_f64, seed};
+
+    // The projected normal distribution integrates to 1 over the hemisphere.
+    let ggx = TrowbridgeReitz::new(0.5);
+    let n = 4096;
+    let mut integral = 0.;
+    for i in 0..n {
+        let cos_theta = (i as f64 + 0.5) / n as f64;
+        // Uniform in cos(theta), the azimuth integrates to 2 pi.
+        let wm = Vec3::from_xyz((1. - cos_theta * cos_theta).sqrt(), 0., cos_theta);
+        integral += ggx.d(wm) * cos_theta * 2. * PI / n as f64;
+    }
+    assert!((integral - 1.).abs() < 1e-3);
+
+    // Sampled visible normals face the viewer and follow their density: the sample mean of
+    // the normals matches the mean under `d_visible`, integrated over the hemisphere.
+    seed(3);
+    let wo = Vec3::from_xyz(0.6, 0.2, 0.5).unit_vector();
+    let samples = 100_000;
+    let mut mean = Vec3::zeros();
+    for _ in 0..samples {
+        let wm = ggx.sample_wm(wo, (random_f64(), random_f64()));
+        assert!(wm.z() > 0. && (wm.length() - 1.).abs() < 1e-9);
+        assert!(ggx.pdf(wo, wm) > 0.);
+        mean += wm / samples as f64;
+    }
+    let steps = 400;
+    let (mut total, mut expected) = (0., Vec3::zeros());
+    for i in 0..steps {
+        for j in 0..steps {
+            // Uniform in cos(theta) and the azimuth.
+            let cos_theta = (i as f64 + 0.5) / steps as f64;
+            let phi = 2. * PI * (j as f64 + 0.5) / steps as f64;
+            let r = (1. - cos_theta * cos_theta).sqrt();
+            let wm = Vec3::from_xyz(r * phi.cos(), r * phi.sin(), cos_theta);
+            let weight = ggx.d_visible(wo, wm) * 2. * PI / (steps * steps) as f64;
+            total += weight;
+            expected += weight * wm;
+        }
+    }
+    assert!((total - 1.).abs() < 1e-3);
+    assert!((mean - expected).length() < 3e-3);
+
+    // Masking is 1 at normal incidence and smaller at grazing angles.
+    assert_eq!(ggx.g1(Vec3::from_xyz(0., 0., 1.)), 1.);
+    assert!(ggx.g1(Vec3::from_xyz(0.99, 0., 0.1).unit_vector()) < 0.9);
+    assert!(TrowbridgeReitz::new(0.01).is_smooth());
+
+    // Dielectric Fresnel: 4% for glass at normal incidence, total internal reflection inside.
+    assert!((fresnel_dielectric(1., 1.5) - 0.04).abs() < 1e-12);
+    assert_eq!(fresnel_dielectric(0.1, 1. / 1.5), 1.);
+    assert_eq!(fresnel_dielectric(-0.1, 1.5), 1.);
+    assert!((fresnel_dielectric(0., 1.5) - 1.).abs() < 1e-12);
+
+    // Without absorption the complex Fresnel equations reduce to the dielectric ones.
+    for cos_theta in [1., 0.7, 0.2] {
+        let f = fresnel_complex(cos_theta, Vec3::ones() * 1.5, Vec3::zeros());
+        assert!((f.x() - fresnel_dielectric(cos_theta, 1.5)).abs() < 1e-12);
+    }
+    // Normal incidence on a conductor: ((n - 1)^2 + k^2) / ((n + 1)^2 + k^2).
+    let f = fresnel_complex(1., Vec3::ones() * 0.2, Vec3::ones() * 3.);
+    assert!((f.x() - (0.64 + 9.) / (1.44 + 9.)).abs() < 1e-12);
+
+    let wo = Vec3::from_xyz(0.3, 0., 0.8).unit_vector();
+    let n = Vec3::from_xyz(0., 0., 1.);
+    assert!((reflect(wo, n) - Vec3::from_xyz(-wo.x(), 0., wo.z())).near_zero());
+    let wt = refract(wo, n, 1.5).unwrap();
+    // Snell's law: sin(theta_i) = eta sin(theta_t).
+    assert!((wo.x() - 1.5 * -wt.x()).abs() < 1e-12 && wt.z() < 0.);
+    assert!(refract(Vec3::from_xyz(0.9, 0., 0.1).unit_vector(), n, 1. / 1.5).is_none());
+}
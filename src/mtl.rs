@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::load_error::{parse_token, LoadError};
+use crate::material::Material;
+use crate::vector::Vec3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MtlMaterial {
+    pub name: String,
+    pub kd: Vec3,   // Diffuse color
+    pub ks: Vec3,   // Specular color
+    pub ke: Vec3,   // Emissive color
+    pub ns: f64,    // Specular exponent
+    pub ni: f64,    // Index of refraction
+    pub d: f64,     // Dissolve (opacity)
+    pub illum: u32, // Illumination model
+}
+
+impl MtlMaterial {
+    pub fn new(name: &str) -> Self {
+        // Defaults follow the MTL specification.
+        Self {
+            name: name.to_string(),
+            kd: Vec3::from_rgb(0.8, 0.8, 0.8),
+            ks: Vec3::zeros(),
+            ke: Vec3::zeros(),
+            ns: 0.,
+            ni: 1.,
+            d: 1.,
+            illum: 2,
+        }
+    }
+
+    pub fn to_material(&self) -> Material {
+        // Map the Phong-style parameters onto the closest renderer material:
+        // transparent surfaces become glass, specular-dominated surfaces become metal and
+        // everything else is diffuse. Emissive surfaces become lights.
+        let max_component = |c: Vec3| c.x().max(c.y()).max(c.z());
+
+        if max_component(self.ke) > 0. {
+            return Material::diffuse_light(self.ke);
+        }
+        let transparent = self.d < 1. || matches!(self.illum, 4 | 6 | 7 | 9);
+        if transparent {
+            return Material::dielectric(self.ni.max(1.));
+        }
+        if max_component(self.ks) > max_component(self.kd) {
+            // Convert the Phong exponent to an approximate roughness.
+            let fuzz = (2. / (self.ns + 2.)).sqrt();
+            return Material::metal(self.ks, fuzz);
+        }
+        Material::lambertian(self.kd)
+    }
+}
+
+pub fn load_mtl(path: &Path) -> Result<HashMap<String, MtlMaterial>, LoadError> {
+    let file = File::open(path).map_err(|err| LoadError::from(err).in_file(path))?;
+    parse_mtl(BufReader::new(file)).map_err(|err| err.in_file(path))
+}
+
+pub fn parse_mtl<R: BufRead>(reader: R) -> Result<HashMap<String, MtlMaterial>, LoadError> {
+    let mut materials = HashMap::new();
+    let mut current: Option<MtlMaterial> = None;
+
+    for (i, line) in reader.lines().enumerate() {
+        let line_no = i + 1;
+        let line = line?;
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(k) if !k.starts_with('#') => k,
+            _ => continue,
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            if args.is_empty() {
+                return Err(LoadError::at_line(
+                    line_no,
+                    "`newmtl` needs a material name",
+                ));
+            }
+            if let Some(mtl) = current.take() {
+                materials.insert(mtl.name.clone(), mtl);
+            }
+            current = Some(MtlMaterial::new(&args.join(" ")));
+            continue;
+        }
+
+        let mtl = match keyword {
+            "Kd" | "Ks" | "Ke" | "Ns" | "Ni" | "d" | "Tr" | "illum" => {
+                current.as_mut().ok_or_else(|| {
+                    LoadError::at_line(line_no, format!("`{}` before any `newmtl`", keyword))
+                })?
+            }
+            // Other statements (Ka, texture maps, ...) are not used by the renderer.
+            _ => continue,
+        };
+        match keyword {
+            "Kd" => mtl.kd = parse_color(&args, line_no)?,
+            "Ks" => mtl.ks = parse_color(&args, line_no)?,
+            "Ke" => mtl.ke = parse_color(&args, line_no)?,
+            "Ns" => mtl.ns = parse_scalar(&args, line_no)?,
+            "Ni" => mtl.ni = parse_scalar(&args, line_no)?,
+            "d" => mtl.d = parse_scalar(&args, line_no)?,
+            "Tr" => mtl.d = 1. - parse_scalar(&args, line_no)?,
+            "illum" => match args[..] {
+                [x] => mtl.illum = parse_token(x, line_no)?,
+                _ => return Err(LoadError::at_line(line_no, "`illum` needs 1 value")),
+            },
+            _ => unreachable!(),
+        }
+    }
+    if let Some(mtl) = current.take() {
+        materials.insert(mtl.name.clone(), mtl);
+    }
+    Ok(materials)
+}
+
+fn parse_scalar(args: &[&str], line_no: usize) -> Result<f64, LoadError> {
+    match args {
+        [x] => parse_token(x, line_no),
+        _ => Err(LoadError::at_line(
+            line_no,
+            format!("expected 1 value, found {}", args.len()),
+        )),
+    }
+}
+
+fn parse_color(args: &[&str], line_no: usize) -> Result<Vec3, LoadError> {
+    match args {
+        // A single value sets all three channels.
+        [x] => {
+            let x = parse_token(x, line_no)?;
+            Ok(Vec3::from_rgb(x, x, x))
+        }
+        [r, g, b] => Ok(Vec3::from_rgb(
+            parse_token(r, line_no)?,
+            parse_token(g, line_no)?,
+            parse_token(b, line_no)?,
+        )),
+        _ => Err(LoadError::at_line(
+            line_no,
+            format!("expected 1 or 3 color values, found {}", args.len()),
+        )),
+    }
+}
+
+#[test]
+fn test_parse_mtl() {
+    let src = "\
+# materials
+newmtl red
+Kd 0.8 0.1 0.1
+
+newmtl gold
+Kd 0.1 0.1 0.1
+Ks 1.0 0.8 0.3
+Ns 1000
+
+newmtl glass
+Ni 1.5
+d 0.1
+
+newmtl lamp
+Kd 0.8 0.8 0.8
+Ke 4 4 4
+";
+    let materials = parse_mtl(src.as_bytes()).unwrap();
+    assert_eq!(materials.len(), 4);
+    assert_eq!(materials["red"].kd, Vec3::from_rgb(0.8, 0.1, 0.1));
+    assert!(matches!(
+        materials["red"].to_material(),
+        Material::Lambertian(_)
+    ));
+    assert!(matches!(
+        materials["gold"].to_material(),
+        Material::Metal(_)
+    ));
+    assert!(matches!(
+        materials["glass"].to_material(),
+        Material::Dielectric(_)
+    ));
+    assert!(matches!(
+        materials["lamp"].to_material(),
+        Material::DiffuseLight(_)
+    ));
+
+    let err = parse_mtl("Kd 1 1 1\n".as_bytes()).unwrap_err();
+    assert_eq!(err.to_string(), "line 1: `Kd` before any `newmtl`");
+    let err = parse_mtl("newmtl a\n\nKd 1 x 1\n".as_bytes()).unwrap_err();
+    assert_eq!(err.to_string(), "line 3: invalid number `x`");
+}
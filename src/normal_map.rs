@@ -0,0 +1,142 @@
+use crate::hittable::HitRecord;
+use crate::onb::Onb;
+use crate::texture::Texture;
+use crate::vector::Vec3;
+
+// Shading detail that perturbs the normal of a hit before the material scatters it.
+#[derive(Debug, Clone)]
+pub enum NormalMap {
+    Bump {
+        height: Texture, // Height along the normal, the average of the three channels
+        scale: f64,      // Height units per texture unit
+    },
+    Normal {
+        texture: Texture, // Tangent-space normals encoded as rgb = (n + 1) / 2
+        strength: f64,    // Blend between the surface normal (0) and the mapped one (1)
+    },
+}
+
+impl NormalMap {
+    pub fn bump(height: impl Into<Texture>, scale: f64) -> Self {
+        NormalMap::Bump {
+            height: height.into(),
+            scale,
+        }
+    }
+
+    pub fn normal(texture: impl Into<Texture>, strength: f64) -> Self {
+        NormalMap::Normal {
+            texture: texture.into(),
+            strength,
+        }
+    }
+
+    pub fn apply(&self, rec: &mut HitRecord) {
+        let n = rec.normal;
+        let (tangent, bitangent) = tangent_frame(rec);
+        let perturbed = match self {
+            NormalMap::Bump { height, scale } => {
+                // Finite differences of the height field displace the tangents along the normal.
+                const DELTA: f64 = 1e-4;
+                let h = |u: f64, v: f64, p: Vec3| {
+                    let c = height.value(u, v, p);
+                    scale * (c.x() + c.y() + c.z()) / 3.
+                };
+                let h0 = h(rec.u, rec.v, rec.p);
+                let hu = h(rec.u + DELTA, rec.v, rec.p + DELTA * tangent);
+                let hv = h(rec.u, rec.v + DELTA, rec.p + DELTA * bitangent);
+                let dpdu = tangent + (hu - h0) / DELTA * n;
+                let dpdv = bitangent + (hv - h0) / DELTA * n;
+                dpdu.cross(dpdv)
+            }
+            NormalMap::Normal { texture, strength } => {
+                let c = texture.value(rec.u, rec.v, rec.p);
+                let [x, y, z] = c.e.map(|c| 2. * c - 1.);
+                // Tangent-space z is the surface normal.
+                let mapped = x * tangent.unit_vector() + y * bitangent.unit_vector() + z * n;
+                (1. - strength) * n + *strength * mapped.unit_vector()
+            }
+        };
+        // Keep the perturbed normal on the side of the surface that was hit.
+        let perturbed = match perturbed.dot(n) < 0. {
+            true => -perturbed,
+            false => perturbed,
+        };
+        if perturbed.length_squared() > 1e-24 {
+            rec.normal = perturbed.unit_vector();
+        }
+    }
+}
+
+fn tangent_frame(rec: &HitRecord) -> (Vec3, Vec3) {
+    // Tangent and bitangent orthogonal to the (possibly interpolated) shading normal, keeping the
+    // lengths of the surface derivatives. Surfaces without derivatives get an arbitrary frame.
+    let n = rec.normal;
+    let tangent = rec.dpdu - rec.dpdu.dot(n) * n;
+    if tangent.length_squared() < 1e-24 {
+        let uvw = Onb::new(n);
+        return (uvw.u(), uvw.v());
+    }
+    let mut bitangent = n.cross(tangent).unit_vector();
+    // Follow the handedness of the uv mapping, mirrored textures flip the bitangent.
+    if bitangent.dot(rec.dpdv) < 0. {
+        bitangent = -bitangent;
+    }
+    let bitangent_length = match rec.dpdv.near_zero() {
+        true => tangent.length(),
+        false => rec.dpdv.length(),
+    };
+    (tangent, bitangent_length * bitangent)
+}
+
+#[test]
+fn test_normal_map() {
+    use std::sync::Arc;
+
+    use crate::image::Image;
+    use crate::texture::{ImageTexture, WrapMode};
+
+    let mut rec = HitRecord::new();
+    rec.normal = Vec3::from_xyz(0., 0., 1.);
+    rec.dpdu = Vec3::from_xyz(2., 0., 0.);
+    rec.dpdv = Vec3::from_xyz(0., 2., 0.);
+    rec.u = 0.5;
+    rec.v = 0.5;
+
+    // A flat normal map and a constant height leave the normal alone.
+    let flat = NormalMap::normal(Vec3::from_rgb(0.5, 0.5, 1.), 1.);
+    let mut flat_rec = rec.clone();
+    flat.apply(&mut flat_rec);
+    assert!((flat_rec.normal - rec.normal).near_zero());
+    let mut bump_rec = rec.clone();
+    NormalMap::bump(Vec3::ones(), 1.).apply(&mut bump_rec);
+    assert!((bump_rec.normal - rec.normal).near_zero());
+
+    // A normal tilted towards +u in tangent space.
+    let tilted = Vec3::from_rgb(1., 0.5, 1.);
+    let mut tilted_rec = rec.clone();
+    NormalMap::normal(tilted, 1.).apply(&mut tilted_rec);
+    let expected = Vec3::from_xyz(1., 0., 1.).unit_vector();
+    assert!((tilted_rec.normal - expected).near_zero());
+    // Hitting the back face flips the normal but keeps the tangent-space tilt.
+    let mut back = rec.clone();
+    back.normal = -rec.normal;
+    NormalMap::normal(tilted, 1.).apply(&mut back);
+    assert!((back.normal - Vec3::from_xyz(1., 0., -1.).unit_vector()).near_zero());
+
+    // A height ramp rising along u tilts the normal against it. Between the texel centers at
+    // u = 0.25 and u = 0.75 the height rises by 0.5.
+    let mut image = Image::new(2, 1);
+    image.set(1, 0, Vec3::ones());
+    let ramp = ImageTexture::new(Arc::new(image)).with_wrap(WrapMode::Clamp);
+    let mut ramp_rec = rec.clone();
+    NormalMap::bump(Texture::image(ramp), 0.5).apply(&mut ramp_rec);
+    let expected = Vec3::from_xyz(-1., 0., 2.).unit_vector();
+    assert!((ramp_rec.normal - expected).length() < 1e-6);
+
+    // Without surface derivatives an arbitrary tangent frame is used.
+    let mut bare = HitRecord::new();
+    bare.normal = Vec3::from_xyz(0., 1., 0.);
+    NormalMap::normal(Vec3::from_rgb(0.5, 0.5, 1.), 1.).apply(&mut bare);
+    assert!((bare.normal - Vec3::from_xyz(0., 1., 0.)).near_zero());
+}
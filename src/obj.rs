@@ -0,0 +1,336 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::hittable_list::HittableList;
+use crate::load_error::{parse_token, LoadError};
+use crate::material::Material;
+use crate::mesh::{triangulate, TriangleMesh};
+use crate::mtl::{load_mtl, MtlMaterial};
+use crate::vector::Vec3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ObjVertex {
+    pub position: usize,       // 0-based index into `ObjModel::positions`
+    pub uv: Option<usize>,     // 0-based index into `ObjModel::uvs`
+    pub normal: Option<usize>, // 0-based index into `ObjModel::normals`
+}
+
+#[derive(Debug, Clone)]
+pub struct ObjGroup {
+    pub name: String,
+    pub material: Option<String>,
+    pub faces: Vec<[ObjVertex; 3]>, // Faces triangulated on load
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ObjModel {
+    pub positions: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<(f64, f64)>,
+    pub groups: Vec<ObjGroup>,
+    pub materials: HashMap<String, MtlMaterial>,
+}
+
+impl ObjModel {
+    pub fn load(path: &Path) -> Result<Self, LoadError> {
+        let file = File::open(path).map_err(|err| LoadError::from(err).in_file(path))?;
+        // Material libraries are resolved relative to the OBJ file.
+        let dir = path.parent().unwrap_or(Path::new("."));
+        ObjModel::parse(BufReader::new(file), dir).map_err(|err| match err {
+            LoadError::InFile { .. } => err,
+            _ => err.in_file(path),
+        })
+    }
+
+    pub fn parse<R: BufRead>(reader: R, dir: &Path) -> Result<Self, LoadError> {
+        let mut model = ObjModel::default();
+        let mut group_name = String::from("default");
+        let mut material: Option<String> = None;
+
+        for (i, line) in reader.lines().enumerate() {
+            let line_no = i + 1;
+            let line = line?;
+            let mut tokens = line.split_whitespace();
+            let keyword = match tokens.next() {
+                Some(k) if !k.starts_with('#') => k,
+                _ => continue,
+            };
+            let args: Vec<&str> = tokens.collect();
+
+            match keyword {
+                "v" => {
+                    // Extra values (w, or vertex colors) are ignored.
+                    if !matches!(args.len(), 3 | 4 | 6 | 7) {
+                        return Err(LoadError::at_line(
+                            line_no,
+                            format!("`v` expects 3 coordinates, found {}", args.len()),
+                        ));
+                    }
+                    model.positions.push(parse_vec3(&args[..3], line_no)?);
+                }
+                "vn" => {
+                    if args.len() != 3 {
+                        return Err(LoadError::at_line(
+                            line_no,
+                            format!("`vn` expects 3 coordinates, found {}", args.len()),
+                        ));
+                    }
+                    model.normals.push(parse_vec3(&args, line_no)?);
+                }
+                "vt" => {
+                    let (u, v) = match args[..] {
+                        [u] => (parse_token(u, line_no)?, 0.),
+                        [u, v] | [u, v, _] => (parse_token(u, line_no)?, parse_token(v, line_no)?),
+                        _ => {
+                            return Err(LoadError::at_line(
+                                line_no,
+                                format!("`vt` expects 1 to 3 coordinates, found {}", args.len()),
+                            ))
+                        }
+                    };
+                    model.uvs.push((u, v));
+                }
+                "f" => {
+                    if args.len() < 3 {
+                        return Err(LoadError::at_line(
+                            line_no,
+                            format!("face needs at least 3 vertices, found {}", args.len()),
+                        ));
+                    }
+                    let vertices = args
+                        .iter()
+                        .map(|token| model.parse_vertex(token, line_no))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let points: Vec<Vec3> = vertices
+                        .iter()
+                        .map(|v| model.positions[v.position])
+                        .collect();
+                    let faces: Vec<[ObjVertex; 3]> = triangulate(&points)
+                        .into_iter()
+                        .map(|[a, b, c]| [vertices[a], vertices[b], vertices[c]])
+                        .collect();
+                    model
+                        .current_group(&group_name, &material)
+                        .faces
+                        .extend(faces);
+                }
+                "g" | "o" => {
+                    group_name = match args.is_empty() {
+                        true => String::from("default"),
+                        false => args.join(" "),
+                    };
+                }
+                "usemtl" => {
+                    let name = args.join(" ");
+                    if !model.materials.contains_key(&name) {
+                        return Err(LoadError::at_line(
+                            line_no,
+                            format!("undefined material `{}`", name),
+                        ));
+                    }
+                    material = Some(name);
+                }
+                "mtllib" => {
+                    if args.is_empty() {
+                        return Err(LoadError::at_line(line_no, "`mtllib` needs a file name"));
+                    }
+                    for lib in args {
+                        model.materials.extend(load_mtl(&dir.join(lib))?);
+                    }
+                }
+                // Smoothing groups, lines, points and free-form geometry are not supported.
+                _ => continue,
+            }
+        }
+        Ok(model)
+    }
+
+    fn current_group(&mut self, name: &str, material: &Option<String>) -> &mut ObjGroup {
+        // Faces sharing a group name and material end up in the same group.
+        let matches = |g: &ObjGroup| g.name == name && g.material == *material;
+        if !self.groups.last().is_some_and(matches) {
+            self.groups.push(ObjGroup {
+                name: name.to_string(),
+                material: material.clone(),
+                faces: Vec::new(),
+            });
+        }
+        self.groups.last_mut().unwrap()
+    }
+
+    fn parse_vertex(&self, token: &str, line_no: usize) -> Result<ObjVertex, LoadError> {
+        // Face vertices are `v`, `v/vt`, `v//vn` or `v/vt/vn`.
+        let mut parts = token.split('/');
+        let position = parts.next().unwrap_or("");
+        let uv = parts.next().filter(|s| !s.is_empty());
+        let normal = parts.next().filter(|s| !s.is_empty());
+        if parts.next().is_some() {
+            return Err(LoadError::at_line(
+                line_no,
+                format!("malformed face vertex `{}`", token),
+            ));
+        }
+
+        Ok(ObjVertex {
+            position: resolve_index(position, self.positions.len(), "v", line_no)?,
+            uv: match uv {
+                Some(s) => Some(resolve_index(s, self.uvs.len(), "vt", line_no)?),
+                None => None,
+            },
+            normal: match normal {
+                Some(s) => Some(resolve_index(s, self.normals.len(), "vn", line_no)?),
+                None => None,
+            },
+        })
+    }
+
+    pub fn to_hittable_list(&self, default_mat: Material) -> HittableList {
+        // Builds one triangle mesh per group. Groups without a material use `default_mat`.
+        let mut list = HittableList::new();
+        for group in self.groups.iter().filter(|g| !g.faces.is_empty()) {
+            let smooth = group.faces.iter().flatten().all(|v| v.normal.is_some());
+            let textured = group.faces.iter().flatten().all(|v| v.uv.is_some());
+
+            // Compact the referenced vertices into buffers local to this mesh.
+            let mut remap: HashMap<(usize, Option<usize>, Option<usize>), usize> = HashMap::new();
+            let mut positions = Vec::new();
+            let mut normals = Vec::new();
+            let mut uvs = Vec::new();
+            let mut indices = Vec::with_capacity(group.faces.len());
+            for face in group.faces.iter() {
+                let mut tri = [0; 3];
+                for (k, v) in face.iter().enumerate() {
+                    let key = (
+                        v.position,
+                        if smooth { v.normal } else { None },
+                        if textured { v.uv } else { None },
+                    );
+                    tri[k] = *remap.entry(key).or_insert_with(|| {
+                        positions.push(self.positions[v.position]);
+                        if let Some(n) = key.1 {
+                            normals.push(self.normals[n]);
+                        }
+                        if let Some(uv) = key.2 {
+                            uvs.push(self.uvs[uv]);
+                        }
+                        positions.len() - 1
+                    });
+                }
+                indices.push(tri);
+            }
+
+            let mat = match &group.material {
+                Some(name) => self.materials[name].to_material(),
+                None => default_mat.clone(),
+            };
+            list.add(TriangleMesh::from_buffers(
+                positions,
+                smooth.then_some(normals),
+                textured.then_some(uvs),
+                None,
+                indices,
+                mat,
+            ));
+        }
+        list
+    }
+}
+
+fn parse_vec3(args: &[&str], line_no: usize) -> Result<Vec3, LoadError> {
+    Ok(Vec3::from_xyz(
+        parse_token(args[0], line_no)?,
+        parse_token(args[1], line_no)?,
+        parse_token(args[2], line_no)?,
+    ))
+}
+
+fn resolve_index(token: &str, len: usize, kind: &str, line_no: usize) -> Result<usize, LoadError> {
+    // OBJ indices are 1-based, negative indices count back from the last element.
+    let index: i64 = token
+        .parse()
+        .map_err(|_| LoadError::at_line(line_no, format!("invalid {} index `{}`", kind, token)))?;
+    let resolved = match index {
+        i if i > 0 => i - 1,
+        i if i < 0 => len as i64 + i,
+        _ => -1,
+    };
+    if resolved < 0 || resolved >= len as i64 {
+        return Err(LoadError::at_line(
+            line_no,
+            format!("{} index {} out of range ({} defined)", kind, index, len),
+        ));
+    }
+    Ok(resolved as usize)
+}
+
+#[test]
+fn test_parse_obj() {
+    use crate::hittable::{HitRecord, Hittable};
+    use crate::interval::Interval;
+    use crate::ray::Ray;
+
+    let src = "\
+# unit quad and a concave L-shape
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+g quad
+f 1/1/1 2/2/1 3/3/1 4/4/1
+g l_shape
+v 2 0 0
+v 4 0 0
+v 4 1 0
+v 3 1 0
+v 3 2 0
+v 2 2 0
+f -6 -5 -4 -3 -2 -1
+";
+    let model = ObjModel::parse(src.as_bytes(), Path::new(".")).unwrap();
+    assert_eq!(model.positions.len(), 10);
+    assert_eq!(model.uvs.len(), 4);
+    assert_eq!(model.groups.len(), 2);
+    assert_eq!(model.groups[0].name, "quad");
+    assert_eq!(model.groups[0].faces.len(), 2);
+    let quad_vertices = || model.groups[0].faces.iter().flatten();
+    assert!(quad_vertices().all(|v| v.normal == Some(0)));
+    assert!(quad_vertices().any(|v| v.position == 1 && v.uv == Some(1)));
+    assert_eq!(model.groups[1].faces.len(), 4);
+
+    let world = model.to_hittable_list(Material::lambertian(Vec3::zeros()));
+    assert_eq!(world.len(), 2);
+    let r_t = Interval::new(1e-3, f64::INFINITY);
+    let mut rec = HitRecord::new();
+    let down = Vec3::from_xyz(0., 0., -1.);
+    // The notch of the L-shape must stay empty.
+    assert!(!world.hit(&Ray::new(Vec3::from_xyz(3.5, 1.5, 1.), down), r_t, &mut rec));
+    assert!(world.hit(&Ray::new(Vec3::from_xyz(2.5, 1.5, 1.), down), r_t, &mut rec));
+    assert!(world.hit(&Ray::new(Vec3::from_xyz(3.5, 0.5, 1.), down), r_t, &mut rec));
+    assert!(world.hit(
+        &Ray::new(Vec3::from_xyz(0.25, 0.75, 1.), down),
+        r_t,
+        &mut rec
+    ));
+    // Texture coordinates of the quad are interpolated.
+    assert!((rec.u - 0.25).abs() < 1e-12 && (rec.v - 0.75).abs() < 1e-12);
+
+    let err = ObjModel::parse("v 0 0 0\nf 1 2 3\n".as_bytes(), Path::new(".")).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "line 2: v index 2 out of range (1 defined)"
+    );
+    let err = ObjModel::parse("usemtl red\n".as_bytes(), Path::new(".")).unwrap_err();
+    assert_eq!(err.to_string(), "line 1: undefined material `red`");
+    let err = ObjModel::parse("vn 0 1\n".as_bytes(), Path::new(".")).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "line 1: `vn` expects 3 coordinates, found 2"
+    );
+}
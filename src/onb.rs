@@ -0,0 +1,56 @@
+use crate::vector::Vec3;
+
+// Orthonormal basis with `w` along a given direction.
+#[derive(Debug, Clone, Copy)]
+pub struct Onb {
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+}
+
+impl Onb {
+    pub fn new(n: Vec3) -> Self {
+        let w = n.unit_vector();
+        let a = match w.x().abs() > 0.9 {
+            true => Vec3::from_xyz(0., 1., 0.),
+            false => Vec3::from_xyz(1., 0., 0.),
+        };
+        let v = w.cross(a).unit_vector();
+        let u = v.cross(w);
+        Self { u, v, w }
+    }
+
+    pub fn u(&self) -> Vec3 {
+        self.u
+    }
+
+    pub fn v(&self) -> Vec3 {
+        self.v
+    }
+
+    pub fn w(&self) -> Vec3 {
+        self.w
+    }
+
+    pub fn transform(&self, a: Vec3) -> Vec3 {
+        // Transform from basis coordinates to world space.
+        a.x() * self.u + a.y() * self.v + a.z() * self.w
+    }
+
+    pub fn to_local(&self, a: Vec3) -> Vec3 {
+        // Transform from world space to basis coordinates.
+        Vec3::from_xyz(a.dot(self.u), a.dot(self.v), a.dot(self.w))
+    }
+}
+
+#[test]
+fn test_onb() {
+    let onb = Onb::new(Vec3::from_xyz(0., 0., 2.));
+    assert_eq!(onb.w(), Vec3::from_xyz(0., 0., 1.));
+    assert!(onb.u().dot(onb.v()).abs() < 1e-12);
+    assert!((onb.u().cross(onb.v()) - onb.w()).near_zero());
+    let a = Vec3::from_xyz(0.3, -0.2, 0.9);
+    assert!((onb.transform(a).length() - a.length()).abs() < 1e-12);
+    let tilted = Onb::new(Vec3::from_xyz(1., 2., -0.5));
+    assert!((tilted.to_local(tilted.transform(a)) - a).near_zero());
+}
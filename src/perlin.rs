@@ -0,0 +1,138 @@
+use rand::rngs::SmallRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use crate::vector::Vec3;
+
+const POINT_COUNT: usize = 256;
+
+// Gradient noise on the integer lattice, with random unit gradients hashed through three
+// permutation tables. The tables are drawn from their own generator so a seed always produces
+// the same noise, independently of the render seed and thread scheduling.
+#[derive(Debug, Clone)]
+pub struct Perlin {
+    gradients: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = SmallRng::seed_from_u64(seed);
+        let gradients = (0..POINT_COUNT)
+            .map(|_| loop {
+                // Rejection sampling keeps the directions uniform.
+                let v = Vec3::from_xyz(
+                    rng.gen_range(-1. ..1.),
+                    rng.gen_range(-1. ..1.),
+                    rng.gen_range(-1. ..1.),
+                );
+                let length_squared = v.length_squared();
+                if length_squared > 1e-12 && length_squared <= 1. {
+                    break v.unit_vector();
+                }
+            })
+            .collect();
+        let mut permutation = || {
+            let mut perm: Vec<usize> = (0..POINT_COUNT).collect();
+            perm.shuffle(&mut rng);
+            perm
+        };
+        let (perm_x, perm_y, perm_z) = (permutation(), permutation(), permutation());
+        Self {
+            gradients,
+            perm_x,
+            perm_y,
+            perm_z,
+        }
+    }
+
+    pub fn noise(&self, p: Vec3) -> f64 {
+        // Trilinear interpolation of the gradient ramps at the 8 surrounding lattice points,
+        // with Hermite smoothed weights to hide the lattice. The result lies in [-1, 1].
+        let floor = p.e.map(f64::floor);
+        let [u, v, w] = [0, 1, 2].map(|k| p.e[k] - floor[k]);
+        let [i, j, k] = floor.map(|f| f as i64);
+        let [uu, vv, ww] = [u, v, w].map(|t| t * t * (3. - 2. * t));
+
+        let mut accum = 0.;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let hash = self.perm_x[((i + di) & 255) as usize]
+                        ^ self.perm_y[((j + dj) & 255) as usize]
+                        ^ self.perm_z[((k + dk) & 255) as usize];
+                    let (fi, fj, fk) = (di as f64, dj as f64, dk as f64);
+                    let weight = Vec3::from_xyz(u - fi, v - fj, w - fk);
+                    accum += (fi * uu + (1. - fi) * (1. - uu))
+                        * (fj * vv + (1. - fj) * (1. - vv))
+                        * (fk * ww + (1. - fk) * (1. - ww))
+                        * self.gradients[hash].dot(weight);
+                }
+            }
+        }
+        // Unit gradients bound the raw value by sqrt(3) / 2.
+        (accum * 2. / 3f64.sqrt()).clamp(-1., 1.)
+    }
+
+    pub fn turbulence(&self, p: Vec3, octaves: usize) -> f64 {
+        // Sum of absolute noise values at doubling frequencies and halving amplitudes.
+        let mut accum = 0.;
+        let mut p = p;
+        let mut weight = 1.;
+        for _ in 0..octaves {
+            accum += weight * self.noise(p).abs();
+            weight *= 0.5;
+            p = p * 2.;
+        }
+        accum
+    }
+
+    pub fn fbm(&self, p: Vec3, octaves: usize) -> f64 {
+        // Fractal Brownian motion: signed octaves, normalized back to [-1, 1].
+        let mut accum = 0.;
+        let mut total = 0.;
+        let mut p = p;
+        let mut weight = 1.;
+        for _ in 0..octaves {
+            accum += weight * self.noise(p);
+            total += weight;
+            weight *= 0.5;
+            p = p * 2.;
+        }
+        match total > 0. {
+            true => accum / total,
+            false => 0.,
+        }
+    }
+}
+
+#[test]
+fn test_perlin() {
+    let perlin = Perlin::new(7);
+    let points: Vec<Vec3> = (0..64)
+        .map(|i| {
+            let t = i as f64 * 0.37;
+            Vec3::from_xyz(t, 1.3 * t - 2., 0.7 * t + 5.)
+        })
+        .collect();
+    let values: Vec<f64> = points.iter().map(|&p| perlin.noise(p)).collect();
+    assert!(values.iter().all(|n| (-1. ..=1.).contains(n)));
+    assert!(values.iter().any(|n| n.abs() > 0.05));
+
+    // The same seed gives the same noise, another seed a different one.
+    assert_eq!(Perlin::new(7).noise(points[5]), values[5]);
+    assert_ne!(Perlin::new(8).noise(points[5]), values[5]);
+
+    // Gradient noise vanishes on the lattice and is continuous in between.
+    assert_eq!(perlin.noise(Vec3::from_xyz(3., -4., 17.)), 0.);
+    let p = Vec3::from_xyz(0.5, 0.25, 0.75);
+    assert!((perlin.noise(p) - perlin.noise(p + Vec3::ones() * 1e-6)).abs() < 1e-4);
+
+    let turbulence = perlin.turbulence(p, 7);
+    assert!((0. ..2.).contains(&turbulence));
+    assert_eq!(perlin.turbulence(p, 1), perlin.noise(p).abs());
+    assert!((-1. ..=1.).contains(&perlin.fbm(p, 5)));
+    assert_eq!(perlin.fbm(p, 0), 0.);
+}
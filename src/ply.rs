@@ -0,0 +1,492 @@
+use std::fs;
+use std::path::Path;
+
+use crate::load_error::LoadError;
+use crate::material::Material;
+use crate::mesh::{triangulate, TriangleMesh};
+use crate::vector::Vec3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ScalarType {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+impl ScalarType {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "char" | "int8" => Some(ScalarType::Int8),
+            "uchar" | "uint8" => Some(ScalarType::UInt8),
+            "short" | "int16" => Some(ScalarType::Int16),
+            "ushort" | "uint16" => Some(ScalarType::UInt16),
+            "int" | "int32" => Some(ScalarType::Int32),
+            "uint" | "uint32" => Some(ScalarType::UInt32),
+            "float" | "float32" => Some(ScalarType::Float32),
+            "double" | "float64" => Some(ScalarType::Float64),
+            _ => None,
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            ScalarType::Int8 | ScalarType::UInt8 => 1,
+            ScalarType::Int16 | ScalarType::UInt16 => 2,
+            ScalarType::Int32 | ScalarType::UInt32 | ScalarType::Float32 => 4,
+            ScalarType::Float64 => 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Property {
+    name: String,
+    ty: ScalarType,
+    list_count: Option<ScalarType>, // Type of the element count for list properties
+}
+
+#[derive(Debug, Clone)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+// Reads scalars from the body of a PLY file, keeping track of the current position so that
+// errors can point at the offending line (ASCII) or byte (binary).
+struct BodyReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    format: Format,
+    line: usize,
+    tokens: Vec<&'a str>,
+}
+
+impl<'a> BodyReader<'a> {
+    fn error(&self, message: impl Into<String>) -> LoadError {
+        match self.format {
+            Format::Ascii => LoadError::at_line(self.line, message),
+            _ => LoadError::at_byte(self.pos, message),
+        }
+    }
+
+    fn begin_element(&mut self) -> Result<(), LoadError> {
+        // ASCII elements are stored one per line.
+        if self.format != Format::Ascii {
+            return Ok(());
+        }
+        if !self.tokens.is_empty() {
+            return Err(self.error("too many values for element"));
+        }
+        while self.pos < self.data.len() {
+            let end = self.data[self.pos..]
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(self.data.len(), |i| self.pos + i);
+            let line = std::str::from_utf8(&self.data[self.pos..end])
+                .map_err(|_| self.error("invalid UTF-8 in ASCII data"))?;
+            self.pos = end + 1;
+            self.line += 1;
+            self.tokens = line.split_whitespace().rev().collect();
+            if !self.tokens.is_empty() {
+                return Ok(());
+            }
+        }
+        Err(self.error("unexpected end of file"))
+    }
+
+    fn read(&mut self, ty: ScalarType) -> Result<f64, LoadError> {
+        if self.format == Format::Ascii {
+            let token = self
+                .tokens
+                .pop()
+                .ok_or_else(|| self.error("too few values for element"))?;
+            return token
+                .parse::<f64>()
+                .map_err(|_| self.error(format!("invalid number `{}`", token)));
+        }
+
+        let size = ty.size();
+        if self.pos + size > self.data.len() {
+            return Err(self.error("unexpected end of file"));
+        }
+        let mut bytes = [0u8; 8];
+        bytes[..size].copy_from_slice(&self.data[self.pos..self.pos + size]);
+        if self.format == Format::BinaryBigEndian {
+            bytes[..size].reverse();
+        }
+        self.pos += size;
+
+        let value = match ty {
+            ScalarType::Int8 => bytes[0] as i8 as f64,
+            ScalarType::UInt8 => bytes[0] as f64,
+            ScalarType::Int16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            ScalarType::UInt16 => u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            ScalarType::Int32 => i32::from_le_bytes(bytes[..4].try_into().unwrap()) as f64,
+            ScalarType::UInt32 => u32::from_le_bytes(bytes[..4].try_into().unwrap()) as f64,
+            ScalarType::Float32 => f32::from_le_bytes(bytes[..4].try_into().unwrap()) as f64,
+            ScalarType::Float64 => f64::from_le_bytes(bytes),
+        };
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PlyModel {
+    pub positions: Vec<Vec3>,
+    pub normals: Option<Vec<Vec3>>,
+    pub colors: Option<Vec<Vec3>>, // Linear RGB in [0, 1]
+    pub faces: Vec<[usize; 3]>,    // Faces triangulated on load
+}
+
+impl PlyModel {
+    pub fn load(path: &Path) -> Result<Self, LoadError> {
+        let data = fs::read(path).map_err(|err| LoadError::from(err).in_file(path))?;
+        PlyModel::parse(&data).map_err(|err| err.in_file(path))
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self, LoadError> {
+        let (format, elements, body_start, header_lines) = parse_header(data)?;
+        let mut reader = BodyReader {
+            data,
+            pos: body_start,
+            format,
+            line: header_lines,
+            tokens: Vec::new(),
+        };
+
+        let mut model = PlyModel::default();
+        for element in elements.iter() {
+            match element.name.as_str() {
+                "vertex" => model.read_vertices(element, &mut reader)?,
+                "face" => model.read_faces(element, &mut reader)?,
+                // Other elements (edges, materials, ...) are skipped.
+                _ => {
+                    for _ in 0..element.count {
+                        reader.begin_element()?;
+                        for prop in element.properties.iter() {
+                            read_property(prop, &mut reader)?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(model)
+    }
+
+    fn read_vertices(
+        &mut self,
+        element: &Element,
+        reader: &mut BodyReader,
+    ) -> Result<(), LoadError> {
+        let index_of = |name: &str| element.properties.iter().position(|p| p.name == name);
+        let lookup = |names: [&str; 3]| -> Option<[usize; 3]> {
+            Some([
+                index_of(names[0])?,
+                index_of(names[1])?,
+                index_of(names[2])?,
+            ])
+        };
+        let position = lookup(["x", "y", "z"])
+            .ok_or_else(|| reader.error("vertex element needs x, y and z properties"))?;
+        let normal = lookup(["nx", "ny", "nz"]);
+        let color = lookup(["red", "green", "blue"]).or(lookup(["r", "g", "b"]));
+
+        let mut normals = Vec::new();
+        let mut colors = Vec::new();
+        for _ in 0..element.count {
+            reader.begin_element()?;
+            let values = element
+                .properties
+                .iter()
+                .map(|prop| read_property(prop, reader))
+                .collect::<Result<Vec<_>, _>>()?;
+            // List properties on vertices are not used, only keep their first value.
+            let values: Vec<f64> = values
+                .iter()
+                .map(|v| v.first().copied().unwrap_or(0.))
+                .collect();
+            let vec3 =
+                |idx: [usize; 3]| Vec3::from_xyz(values[idx[0]], values[idx[1]], values[idx[2]]);
+
+            self.positions.push(vec3(position));
+            if let Some(idx) = normal {
+                normals.push(vec3(idx));
+            }
+            if let Some(idx) = color {
+                // Integer colors are stored in [0, 255].
+                let scale = match element.properties[idx[0]].ty {
+                    ScalarType::Float32 | ScalarType::Float64 => 1.,
+                    _ => 1. / 255.,
+                };
+                colors.push(scale * vec3(idx));
+            }
+        }
+        self.normals = normal.map(|_| normals);
+        self.colors = color.map(|_| colors);
+        Ok(())
+    }
+
+    fn read_faces(&mut self, element: &Element, reader: &mut BodyReader) -> Result<(), LoadError> {
+        let index = element
+            .properties
+            .iter()
+            .position(|p| {
+                p.list_count.is_some() && (p.name == "vertex_indices" || p.name == "vertex_index")
+            })
+            .ok_or_else(|| reader.error("face element needs a vertex_indices list property"))?;
+
+        for _ in 0..element.count {
+            reader.begin_element()?;
+            for (k, prop) in element.properties.iter().enumerate() {
+                let values = read_property(prop, reader)?;
+                if k != index {
+                    continue;
+                }
+                if values.len() < 3 {
+                    return Err(reader.error(format!(
+                        "face needs at least 3 vertices, found {}",
+                        values.len()
+                    )));
+                }
+                let mut face = Vec::with_capacity(values.len());
+                for &v in values.iter() {
+                    if v < 0. || v as usize >= self.positions.len() {
+                        return Err(reader.error(format!(
+                            "vertex index {} out of range ({} defined)",
+                            v,
+                            self.positions.len()
+                        )));
+                    }
+                    face.push(v as usize);
+                }
+                let points: Vec<Vec3> = face.iter().map(|&i| self.positions[i]).collect();
+                self.faces.extend(
+                    triangulate(&points)
+                        .into_iter()
+                        .map(|[a, b, c]| [face[a], face[b], face[c]]),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_mesh(&self, default_mat: Material) -> TriangleMesh {
+        // Vertex colors make a diffuse mesh, others use `default_mat`.
+        TriangleMesh::from_buffers(
+            self.positions.clone(),
+            self.normals.clone(),
+            None,
+            self.colors.clone(),
+            self.faces.clone(),
+            default_mat,
+        )
+    }
+}
+
+fn read_property(prop: &Property, reader: &mut BodyReader) -> Result<Vec<f64>, LoadError> {
+    match prop.list_count {
+        Some(count_ty) => {
+            let count = reader.read(count_ty)?;
+            (0..count as usize).map(|_| reader.read(prop.ty)).collect()
+        }
+        None => Ok(vec![reader.read(prop.ty)?]),
+    }
+}
+
+fn parse_header(data: &[u8]) -> Result<(Format, Vec<Element>, usize, usize), LoadError> {
+    // Returns the body format, the element layout, and the byte offset and line number at
+    // which the header ends.
+    let mut pos = 0;
+    let mut line_no = 0;
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+
+    loop {
+        let end = data[pos..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map(|i| pos + i)
+            .ok_or_else(|| LoadError::at_line(line_no + 1, "missing `end_header`"))?;
+        let line = std::str::from_utf8(&data[pos..end])
+            .map_err(|_| LoadError::at_line(line_no + 1, "invalid UTF-8 in header"))?;
+        pos = end + 1;
+        line_no += 1;
+
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if line_no == 1 {
+            if tokens != ["ply"] {
+                return Err(LoadError::at_line(line_no, "not a PLY file"));
+            }
+            continue;
+        }
+        match tokens[..] {
+            [] | ["comment", ..] | ["obj_info", ..] => continue,
+            ["format", name, _version] => {
+                format = Some(match name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => {
+                        return Err(LoadError::at_line(
+                            line_no,
+                            format!("unknown format `{}`", name),
+                        ))
+                    }
+                });
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| {
+                    LoadError::at_line(line_no, format!("invalid element count `{}`", count))
+                })?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count_ty, ty, name] => {
+                let prop = Property {
+                    name: name.to_string(),
+                    ty: parse_type(ty, line_no)?,
+                    list_count: Some(parse_type(count_ty, line_no)?),
+                };
+                add_property(&mut elements, prop, line_no)?;
+            }
+            ["property", ty, name] => {
+                let prop = Property {
+                    name: name.to_string(),
+                    ty: parse_type(ty, line_no)?,
+                    list_count: None,
+                };
+                add_property(&mut elements, prop, line_no)?;
+            }
+            ["end_header"] => break,
+            _ => {
+                return Err(LoadError::at_line(
+                    line_no,
+                    format!("malformed header line `{}`", line.trim()),
+                ))
+            }
+        }
+    }
+
+    let format = format.ok_or_else(|| LoadError::at_line(line_no, "missing `format` line"))?;
+    Ok((format, elements, pos, line_no))
+}
+
+fn parse_type(name: &str, line_no: usize) -> Result<ScalarType, LoadError> {
+    ScalarType::from_name(name)
+        .ok_or_else(|| LoadError::at_line(line_no, format!("unknown property type `{}`", name)))
+}
+
+fn add_property(elements: &mut [Element], prop: Property, line_no: usize) -> Result<(), LoadError> {
+    match elements.last_mut() {
+        Some(element) => {
+            element.properties.push(prop);
+            Ok(())
+        }
+        None => Err(LoadError::at_line(
+            line_no,
+            "`property` before any `element`",
+        )),
+    }
+}
+
+#[test]
+fn test_parse_ply() {
+    use std::f64::consts::PI;
+
+    use crate::hittable::{HitRecord, Hittable};
+    use crate::interval::Interval;
+    use crate::material::Scatterable;
+    use crate::ray::Ray;
+
+    let ascii = "\
+ply
+format ascii 1.0
+comment a unit quad
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 255 0 0
+1 0 0 0 255 0
+1 1 0 0 0 255
+0 1 0 255 255 255
+4 0 1 2 3
+";
+    let model = PlyModel::parse(ascii.as_bytes()).unwrap();
+    assert_eq!(model.positions.len(), 4);
+    assert_eq!(model.faces.len(), 2);
+    assert!(model.normals.is_none());
+    assert_eq!(
+        model.colors.as_ref().unwrap()[1],
+        Vec3::from_rgb(0., 1., 0.)
+    );
+    // Vertex colors are interpolated into the diffuse albedo of the mesh: 60% green, 20% red
+    // and 20% white at (0.6, 0.2).
+    let mesh = model.to_mesh(Material::lambertian(Vec3::zeros()));
+    let r = Ray::new(Vec3::from_xyz(0.6, 0.2, 1.), Vec3::from_xyz(0., 0., -1.));
+    let mut rec = HitRecord::new();
+    assert!(mesh.hit(&r, Interval::new(0., f64::INFINITY), &mut rec));
+    let albedo = rec.mat.eval(&r, &rec, rec.normal) * PI;
+    assert!((albedo - Vec3::from_rgb(0.4, 0.8, 0.2)).near_zero());
+
+    // The same mesh as big-endian binary with normals instead of colors.
+    let header = "\
+ply
+format binary_big_endian 1.0
+element vertex 4
+property double x
+property double y
+property double z
+property float nx
+property float ny
+property float nz
+element face 1
+property list uchar uint vertex_indices
+end_header
+";
+    let mut data = header.as_bytes().to_vec();
+    for p in model.positions.iter() {
+        for c in p.e {
+            data.extend_from_slice(&c.to_be_bytes());
+        }
+        for n in [0f32, 0., 1.] {
+            data.extend_from_slice(&n.to_be_bytes());
+        }
+    }
+    data.push(4);
+    for i in 0u32..4 {
+        data.extend_from_slice(&i.to_be_bytes());
+    }
+    let binary = PlyModel::parse(&data).unwrap();
+    assert_eq!(binary.positions, model.positions);
+    assert_eq!(binary.faces, model.faces);
+    assert_eq!(binary.normals.unwrap()[3], Vec3::from_xyz(0., 0., 1.));
+
+    let err = PlyModel::parse(&data[..data.len() - 2]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!("byte {}: unexpected end of file", data.len() - 4)
+    );
+    let err =
+        PlyModel::parse(ascii.replace("1 1 0 0 0 255", "1 x 0 0 0 255").as_bytes()).unwrap_err();
+    assert_eq!(err.to_string(), "line 16: invalid number `x`");
+}
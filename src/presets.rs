@@ -0,0 +1,501 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::camera::CameraBuilder;
+use crate::hittable_list::HittableList;
+use crate::material::{Conductor, Dielectric, Material, PrincipledBuilder};
+use crate::medium::{ConstantMedium, GridMedium};
+use crate::quad::{cuboid, Quad};
+use crate::random::random_f64;
+use crate::scene::Scene;
+use crate::spectrum::Dispersion;
+use crate::sphere::Sphere;
+use crate::texture::{NoisePattern, NoiseTexture, Texture};
+use crate::vector::Vec3;
+use crate::voxel_grid::VoxelGrid;
+
+pub struct Preset {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub build: fn() -> Scene,
+}
+
+pub const PRESETS: &[Preset] = &[
+    Preset {
+        name: "random-spheres",
+        description: "Many small random spheres around three large ones",
+        build: random_spheres,
+    },
+    Preset {
+        name: "checkered-spheres",
+        description: "Two large spheres with a 3D checker texture",
+        build: checkered_spheres,
+    },
+    Preset {
+        name: "perlin-spheres",
+        description: "Marble sphere on a ground of fractal noise",
+        build: perlin_spheres,
+    },
+    Preset {
+        name: "metal-spheres",
+        description: "Gold, copper, aluminium and frosted glass spheres of rising roughness",
+        build: metal_spheres,
+    },
+    Preset {
+        name: "principled-spheres",
+        description:
+            "Plastic, metal, car paint, velvet and glass made with the principled material",
+        build: principled_spheres,
+    },
+    Preset {
+        name: "dispersion",
+        description: "Diamond and flint glass spheres splitting white light, rendered spectrally",
+        build: dispersion,
+    },
+    Preset {
+        name: "cornell-box",
+        description: "Cornell box with two blocks, lit only by a ceiling light",
+        build: cornell_box,
+    },
+    Preset {
+        name: "cornell-smoke",
+        description: "Cornell box with blocks of dark smoke and white fog",
+        build: cornell_smoke,
+    },
+    Preset {
+        name: "cornell-cloud",
+        description: "Cornell box with a procedural cloud rendered from a voxel grid",
+        build: cornell_cloud,
+    },
+];
+
+pub fn find(name: &str) -> Option<&'static Preset> {
+    PRESETS.iter().find(|preset| preset.name == name)
+}
+
+pub fn random_spheres() -> Scene {
+    // World
+    let mut world = HittableList::new();
+
+    // Material
+    let material_ground = Material::lambertian(Vec3::from_xyz(0.5, 0.5, 0.5));
+    world.add(Sphere::new(
+        Vec3::from_xyz(0., -1000., 0.),
+        1000.,
+        material_ground,
+    ));
+
+    for a in -11..11 {
+        for b in -11..11 {
+            let choose_mat = random_f64();
+            let center = Vec3::from_xyz(
+                a as f64 + 0.9 * random_f64(),
+                0.2,
+                b as f64 + 0.9 * random_f64(),
+            );
+            if (center - Vec3::from_xyz(4.0, 0.2, 0.0)).length() > 0.9 {
+                let sphere_material: Material;
+                if choose_mat < 0.8 {
+                    // diffuse
+                    let albedo = Vec3::random() * Vec3::random();
+                    sphere_material = Material::lambertian(albedo);
+                    world.add(Sphere::new(center, 0.2, sphere_material));
+                } else if choose_mat < 0.95 {
+                    // metal
+                    let albedo = Vec3::random_range(0.5, 1.0);
+                    let fuzz = random_f64() * 0.5;
+                    sphere_material = Material::metal(albedo, fuzz);
+                    world.add(Sphere::new(center, 0.2, sphere_material));
+                } else {
+                    // glass
+                    sphere_material = Material::dielectric(1.5);
+                    world.add(Sphere::new(center, 0.2, sphere_material));
+                }
+            }
+        }
+    }
+
+    let material1 = Material::dielectric(1.5);
+    world.add(Sphere::new(Vec3::from_xyz(0., 1., 0.), 1.0, material1));
+
+    let material2 = Material::lambertian(Vec3::from_xyz(0.4, 0.2, 0.1));
+    world.add(Sphere::new(Vec3::from_xyz(-4., 1., 0.), 1.0, material2));
+
+    let material3 = Material::metal(Vec3::from_xyz(0.7, 0.6, 0.5), 0.0);
+    world.add(Sphere::new(Vec3::from_xyz(4., 1., 0.), 1.0, material3));
+
+    // Image
+    let image_width: usize = 640;
+    let image_height: usize = 360;
+    let samples_per_pixel: usize = 10;
+    let max_depth: usize = 50;
+
+    // Camera
+    let vfov: f64 = 20.0;
+    let look_from = Vec3::from_xyz(13.0, 2.0, 3.0);
+    let look_at = Vec3::from_xyz(0.0, 0.0, 0.0);
+    let vup = Vec3::from_xyz(0., 1., 0.);
+
+    let defocus_angle = 0.6;
+    let focus_dist = 10.0;
+
+    let mut camera = CameraBuilder::default();
+    camera
+        .image_size(image_width, image_height)
+        .samples_per_pixel(samples_per_pixel)
+        .max_depth(max_depth)
+        .vfov(vfov)
+        .look_from(look_from)
+        .look_at(look_at)
+        .vup(vup)
+        .defocus_angle(defocus_angle)
+        .focus_dist(focus_dist);
+    Scene { camera, world }
+}
+
+pub fn checkered_spheres() -> Scene {
+    let mut world = HittableList::new();
+
+    let checker = Texture::checker(
+        0.32,
+        Vec3::from_rgb(0.2, 0.3, 0.1),
+        Vec3::from_rgb(0.9, 0.9, 0.9),
+    );
+    let mat = Material::lambertian(checker);
+    world.add(Sphere::new(Vec3::from_xyz(0., -10., 0.), 10., mat.clone()));
+    world.add(Sphere::new(Vec3::from_xyz(0., 10., 0.), 10., mat));
+
+    let mut camera = CameraBuilder::default();
+    camera
+        .image_size(640, 360)
+        .samples_per_pixel(100)
+        .max_depth(50)
+        .vfov(20.)
+        .look_from(Vec3::from_xyz(13., 2., 3.))
+        .look_at(Vec3::zeros())
+        .vup(Vec3::from_xyz(0., 1., 0.))
+        .defocus_angle(0.)
+        .focus_dist(10.);
+    Scene { camera, world }
+}
+
+pub fn perlin_spheres() -> Scene {
+    let mut world = HittableList::new();
+
+    let ground = NoiseTexture::new(NoisePattern::Fbm, 1)
+        .with_scale(2.)
+        .with_colors(
+            Vec3::from_rgb(0.2, 0.15, 0.1),
+            Vec3::from_rgb(0.6, 0.55, 0.4),
+        );
+    let marble = NoiseTexture::new(NoisePattern::Marble, 2).with_scale(4.);
+    world.add(Sphere::new(
+        Vec3::from_xyz(0., -1000., 0.),
+        1000.,
+        Material::lambertian(Texture::noise(ground)),
+    ));
+    world.add(Sphere::new(
+        Vec3::from_xyz(0., 2., 0.),
+        2.,
+        Material::lambertian(Texture::noise(marble)),
+    ));
+
+    let mut camera = CameraBuilder::default();
+    camera
+        .image_size(640, 360)
+        .samples_per_pixel(100)
+        .max_depth(50)
+        .vfov(20.)
+        .look_from(Vec3::from_xyz(13., 2., 3.))
+        .look_at(Vec3::zeros())
+        .vup(Vec3::from_xyz(0., 1., 0.))
+        .defocus_angle(0.)
+        .focus_dist(10.);
+    Scene { camera, world }
+}
+
+pub fn metal_spheres() -> Scene {
+    let mut world = HittableList::new();
+
+    let checker = Texture::checker(
+        1.,
+        Vec3::from_rgb(0.2, 0.2, 0.2),
+        Vec3::from_rgb(0.8, 0.8, 0.8),
+    );
+    world.add(Sphere::new(
+        Vec3::from_xyz(0., -1000., 0.),
+        1000.,
+        Material::lambertian(checker),
+    ));
+    // One row per metal, roughness rising from left to right, and a row of frosted glass.
+    let rows = ["gold", "copper", "aluminium", "glass"];
+    let roughness = [0., 0.15, 0.3, 0.5];
+    for (row, name) in rows.iter().enumerate() {
+        for (column, &roughness) in roughness.iter().enumerate() {
+            let mat = match Conductor::preset(name, roughness) {
+                Some(conductor) => Material::Conductor(conductor),
+                None => Material::rough_dielectric(1.5, roughness),
+            };
+            let center = Vec3::from_xyz(2.5 * column as f64 - 3.75, 1., 2.5 * row as f64 - 3.75);
+            world.add(Sphere::new(center, 1., mat));
+        }
+    }
+
+    let mut camera = CameraBuilder::default();
+    camera
+        .image_size(640, 360)
+        .samples_per_pixel(100)
+        .max_depth(50)
+        .vfov(30.)
+        .look_from(Vec3::from_xyz(0., 12., 16.))
+        .look_at(Vec3::from_xyz(0., 0., 0.5))
+        .vup(Vec3::from_xyz(0., 1., 0.))
+        .defocus_angle(0.)
+        .focus_dist(10.);
+    Scene { camera, world }
+}
+
+pub fn principled_spheres() -> Scene {
+    let mut world = HittableList::new();
+
+    let checker = Texture::checker(
+        1.,
+        Vec3::from_rgb(0.2, 0.2, 0.2),
+        Vec3::from_rgb(0.8, 0.8, 0.8),
+    );
+    world.add(Sphere::new(
+        Vec3::from_xyz(0., -1000., 0.),
+        1000.,
+        Material::lambertian(checker),
+    ));
+    let mut plastic = PrincipledBuilder::default();
+    plastic
+        .base_color(Vec3::from_rgb(0.8, 0.1, 0.1))
+        .roughness(0.3);
+    let mut metal = PrincipledBuilder::default();
+    metal
+        .base_color(Vec3::from_rgb(0.95, 0.65, 0.35))
+        .metallic(1.)
+        .roughness(0.25);
+    let mut car_paint = PrincipledBuilder::default();
+    car_paint
+        .base_color(Vec3::from_rgb(0.05, 0.15, 0.6))
+        .metallic(0.6)
+        .roughness(0.5)
+        .clearcoat(1.)
+        .clearcoat_roughness(0.05);
+    let mut velvet = PrincipledBuilder::default();
+    velvet
+        .base_color(Vec3::from_rgb(0.3, 0.05, 0.3))
+        .roughness(1.)
+        .specular(0.)
+        .sheen(1.);
+    let mut glass = PrincipledBuilder::default();
+    glass
+        .base_color(Vec3::from_rgb(0.7, 1., 0.8))
+        .roughness(0.1)
+        .transmission(1.);
+    for (i, builder) in [plastic, metal, car_paint, velvet, glass]
+        .iter()
+        .enumerate()
+    {
+        // Every parameter has a default.
+        let mat = Material::principled(builder.build().unwrap());
+        let center = Vec3::from_xyz(2.2 * i as f64 - 4.4, 1., 0.);
+        world.add(Sphere::new(center, 1., mat));
+    }
+
+    let mut camera = CameraBuilder::default();
+    camera
+        .image_size(640, 360)
+        .samples_per_pixel(100)
+        .max_depth(50)
+        .vfov(30.)
+        .look_from(Vec3::from_xyz(0., 5., 14.))
+        .look_at(Vec3::from_xyz(0., 0.8, 0.))
+        .vup(Vec3::from_xyz(0., 1., 0.))
+        .defocus_angle(0.)
+        .focus_dist(10.);
+    Scene { camera, world }
+}
+
+pub fn dispersion() -> Scene {
+    let mut world = HittableList::new();
+
+    // Fine black and white stripes behind the spheres show the colored fringes of refraction.
+    let checker = Texture::checker(0.5, Vec3::zeros(), Vec3::ones());
+    world.add(Sphere::new(
+        Vec3::from_xyz(0., -1000., 0.),
+        1000.,
+        Material::lambertian(checker),
+    ));
+    let diamond = "diamond".parse::<Dispersion>().unwrap();
+    let flint = Dispersion::Cauchy { a: 1.67, b: 0.0074 };
+    world.add(Sphere::new(
+        Vec3::from_xyz(-1.2, 1., 0.),
+        1.,
+        Material::Dielectric(Dielectric::dispersive(diamond)),
+    ));
+    world.add(Sphere::new(
+        Vec3::from_xyz(1.2, 1., 0.),
+        1.,
+        Material::Dielectric(Dielectric::dispersive(flint)),
+    ));
+
+    let mut camera = CameraBuilder::default();
+    camera
+        .image_size(640, 360)
+        .samples_per_pixel(200)
+        .max_depth(50)
+        .vfov(25.)
+        .look_from(Vec3::from_xyz(0., 3., 7.))
+        .look_at(Vec3::from_xyz(0., 0.8, 0.))
+        .vup(Vec3::from_xyz(0., 1., 0.))
+        .defocus_angle(0.)
+        .focus_dist(10.)
+        .spectral(true);
+    Scene { camera, world }
+}
+
+fn cornell_room(world: &mut HittableList) -> CameraBuilder {
+    // Walls and ceiling light of the Cornell box, and the camera looking into it.
+    let red = Material::lambertian(Vec3::from_rgb(0.65, 0.05, 0.05));
+    let white = Material::lambertian(Vec3::from_rgb(0.73, 0.73, 0.73));
+    let green = Material::lambertian(Vec3::from_rgb(0.12, 0.45, 0.15));
+    let light = Material::diffuse_light(Vec3::from_rgb(15., 15., 15.));
+
+    // Walls of the 555 units wide box, open towards the camera.
+    world.add(Quad::new(
+        Vec3::from_xyz(555., 0., 0.),
+        Vec3::from_xyz(0., 555., 0.),
+        Vec3::from_xyz(0., 0., 555.),
+        green,
+    ));
+    world.add(Quad::new(
+        Vec3::zeros(),
+        Vec3::from_xyz(0., 555., 0.),
+        Vec3::from_xyz(0., 0., 555.),
+        red,
+    ));
+    world.add(Quad::new(
+        Vec3::zeros(),
+        Vec3::from_xyz(555., 0., 0.),
+        Vec3::from_xyz(0., 0., 555.),
+        white.clone(),
+    ));
+    world.add(Quad::new(
+        Vec3::from_xyz(555., 555., 555.),
+        Vec3::from_xyz(-555., 0., 0.),
+        Vec3::from_xyz(0., 0., -555.),
+        white.clone(),
+    ));
+    world.add(Quad::new(
+        Vec3::from_xyz(0., 0., 555.),
+        Vec3::from_xyz(555., 0., 0.),
+        Vec3::from_xyz(0., 555., 0.),
+        white.clone(),
+    ));
+
+    // Ceiling light, facing down.
+    world.add(Quad::new(
+        Vec3::from_xyz(343., 554., 332.),
+        Vec3::from_xyz(-130., 0., 0.),
+        Vec3::from_xyz(0., 0., -105.),
+        light,
+    ));
+
+    let mut camera = CameraBuilder::default();
+    camera
+        .image_size(600, 600)
+        .samples_per_pixel(200)
+        .max_depth(50)
+        .background(Vec3::zeros())
+        .vfov(40.)
+        .look_from(Vec3::from_xyz(278., 278., -800.))
+        .look_at(Vec3::from_xyz(278., 278., 0.))
+        .vup(Vec3::from_xyz(0., 1., 0.))
+        .defocus_angle(0.)
+        .focus_dist(10.);
+    camera
+}
+
+pub fn cornell_box() -> Scene {
+    let mut world = HittableList::new();
+    let camera = cornell_room(&mut world);
+
+    let white = Material::lambertian(Vec3::from_rgb(0.73, 0.73, 0.73));
+    world.add(cuboid(
+        Vec3::from_xyz(283.54, 0., 270.83),
+        Vec3::from_xyz(448.54, 330., 435.83),
+        15.,
+        white.clone(),
+    ));
+    world.add(cuboid(
+        Vec3::from_xyz(100.47, 0., 87.46),
+        Vec3::from_xyz(265.47, 165., 252.46),
+        -18.,
+        white,
+    ));
+    Scene { camera, world }
+}
+
+pub fn cornell_smoke() -> Scene {
+    let mut world = HittableList::new();
+    let camera = cornell_room(&mut world);
+
+    // The blocks of the Cornell box filled with dark smoke and forward-scattering white fog.
+    let boundary = Material::lambertian(Vec3::zeros());
+    let tall = cuboid(
+        Vec3::from_xyz(283.54, 0., 270.83),
+        Vec3::from_xyz(448.54, 330., 435.83),
+        15.,
+        boundary.clone(),
+    );
+    let short = cuboid(
+        Vec3::from_xyz(100.47, 0., 87.46),
+        Vec3::from_xyz(265.47, 165., 252.46),
+        -18.,
+        boundary,
+    );
+    world.add(ConstantMedium::new(
+        tall,
+        0.01,
+        Material::isotropic(Vec3::from_rgb(0.05, 0.05, 0.05)),
+    ));
+    world.add(ConstantMedium::new(
+        short,
+        0.01,
+        Material::henyey_greenstein(Vec3::ones(), 0.5),
+    ));
+    Scene { camera, world }
+}
+
+pub fn cornell_cloud() -> Scene {
+    let mut world = HittableList::new();
+    let camera = cornell_room(&mut world);
+
+    // A cloud of white, slightly forward-scattering droplets floating in the middle of the box.
+    let grid = Arc::new(VoxelGrid::from_noise(64, 3, 3., 5));
+    let bbox = Aabb::from_points(
+        Vec3::from_xyz(127.5, 100., 127.5),
+        Vec3::from_xyz(427.5, 400., 427.5),
+    );
+    world.add(GridMedium::new(
+        grid,
+        bbox,
+        0.2,
+        Material::henyey_greenstein(Vec3::from_rgb(0.95, 0.95, 0.95), 0.3),
+    ));
+    Scene { camera, world }
+}
+
+#[test]
+fn test_presets() {
+    for preset in PRESETS {
+        let scene = (preset.build)();
+        assert!(scene.camera.build().is_ok());
+        assert!(!scene.world.is_empty());
+    }
+    assert!(find("random-spheres").is_some());
+    assert!(find("missing").is_none());
+}
@@ -0,0 +1,201 @@
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable, LightSample};
+use crate::hittable_list::HittableList;
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::random::random_f64;
+use crate::ray::Ray;
+use crate::vector::Vec3;
+
+// Parallelogram spanned by the edges `u` and `v` from the corner `q`.
+#[derive(Debug, Clone)]
+pub struct Quad {
+    q: Vec3,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,      // n / (n . n), maps plane points to the (alpha, beta) coordinates
+    normal: Vec3, // Unit normal, facing along u x v
+    d: f64,       // Plane offset, normal . p = d
+    area: f64,
+    mat: Material,
+    bbox: Aabb,
+}
+
+impl Quad {
+    pub fn new(q: Vec3, u: Vec3, v: Vec3, mat: Material) -> Self {
+        let n = u.cross(v);
+        let normal = n.unit_vector();
+        let bbox = Aabb::surrounding(
+            Aabb::from_points(q, q + u + v),
+            Aabb::from_points(q + u, q + v),
+        )
+        .pad();
+        Self {
+            q,
+            u,
+            v,
+            w: n / n.dot(n),
+            normal,
+            d: normal.dot(q),
+            area: n.length(),
+            mat,
+            bbox,
+        }
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, r: &Ray, r_t: Interval, rec: &mut HitRecord) -> bool {
+        let denom = self.normal.dot(r.direction());
+        // The ray is parallel to the plane.
+        if denom.abs() < 1e-8 {
+            return false;
+        }
+        let t = (self.d - self.normal.dot(r.origin())) / denom;
+        if !r_t.contains(t) {
+            return false;
+        }
+
+        // Check that the plane hit lies inside the parallelogram.
+        let p = r.at(t);
+        let planar_hit = p - self.q;
+        let alpha = self.w.dot(planar_hit.cross(self.v));
+        let beta = self.w.dot(self.u.cross(planar_hit));
+        let unit = Interval::new(0., 1.);
+        if !unit.contains(alpha) || !unit.contains(beta) {
+            return false;
+        }
+
+        rec.t = t;
+        rec.p = p;
+        rec.set_face_normal(r, self.normal);
+        (rec.u, rec.v) = (alpha, beta);
+        (rec.dpdu, rec.dpdv) = (self.u, self.v);
+        rec.mat = self.mat.clone();
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn is_light(&self) -> bool {
+        self.mat.is_emissive()
+    }
+
+    fn sample(&self, origin: Vec3) -> Option<LightSample> {
+        // Uniform over the area, converted to solid angle.
+        let p = self.q + random_f64() * self.u + random_f64() * self.v;
+        let pdf = area_to_solid_angle(p - origin, self.normal, self.area)?;
+        Some(LightSample { p, pdf })
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3, r_t: Interval) -> f64 {
+        let mut rec = HitRecord::new();
+        match self.hit(&Ray::new(origin, direction), r_t, &mut rec) {
+            true => area_to_solid_angle(rec.p - origin, self.normal, self.area).unwrap_or(0.),
+            false => 0.,
+        }
+    }
+}
+
+pub(crate) fn area_to_solid_angle(to_light: Vec3, normal: Vec3, area: f64) -> Option<f64> {
+    // Converts the uniform area density 1 / area at a light point to solid angle.
+    let distance_squared = to_light.length_squared();
+    let cosine = (to_light.dot(normal) / distance_squared.sqrt()).abs();
+    match cosine > 1e-8 && area > 0. {
+        true => Some(distance_squared / (cosine * area)),
+        false => None,
+    }
+}
+
+pub fn cuboid(a: Vec3, b: Vec3, rotate_y: f64, mat: Material) -> HittableList {
+    // Six outward facing quads of the box with opposite corners `a` and `b`, rotated by
+    // `rotate_y` degrees around the vertical axis through its center.
+    let min = Vec3::from_xyz(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z()));
+    let max = Vec3::from_xyz(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z()));
+    let center = 0.5 * (min + max);
+
+    let (sin, cos) = rotate_y.to_radians().sin_cos();
+    let rotate =
+        |p: Vec3| Vec3::from_xyz(cos * p.x() + sin * p.z(), p.y(), -sin * p.x() + cos * p.z());
+    let corner = |p: Vec3| center + rotate(p - center);
+
+    let dx = rotate(Vec3::from_xyz(max.x() - min.x(), 0., 0.));
+    let dy = Vec3::from_xyz(0., max.y() - min.y(), 0.);
+    let dz = rotate(Vec3::from_xyz(0., 0., max.z() - min.z()));
+    let lo = corner(min);
+    let hi = corner(max);
+
+    let mut sides = HittableList::new();
+    sides.add(Quad::new(lo + dz, dx, dy, mat.clone())); // front
+    sides.add(Quad::new(lo + dx + dz, -dz, dy, mat.clone())); // right
+    sides.add(Quad::new(lo + dx, -dx, dy, mat.clone())); // back
+    sides.add(Quad::new(lo, dz, dy, mat.clone())); // left
+    sides.add(Quad::new(hi - dx, dx, -dz, mat.clone())); // top
+    sides.add(Quad::new(lo, dx, dz, mat)); // bottom
+    sides
+}
+
+#[test]
+fn test_quad() {
+    let mat = Material::lambertian(Vec3::zeros());
+    let quad = Quad::new(
+        Vec3::from_xyz(-1., -1., -2.),
+        Vec3::from_xyz(2., 0., 0.),
+        Vec3::from_xyz(0., 2., 0.),
+        mat.clone(),
+    );
+    let r_t = Interval::new(1e-3, f64::INFINITY);
+    let mut rec = HitRecord::new();
+
+    let r = Ray::new(Vec3::zeros(), Vec3::from_xyz(0.5, 0.5, -1.));
+    assert!(quad.hit(&r, r_t, &mut rec));
+    assert_eq!(rec.t, 2.);
+    assert_eq!(rec.p, Vec3::from_xyz(1., 1., -2.));
+    assert!(rec.front_face);
+    assert_eq!(rec.normal, Vec3::from_xyz(0., 0., 1.));
+    assert_eq!((rec.u, rec.v), (1., 1.));
+    assert!(!quad.hit(
+        &Ray::new(Vec3::zeros(), Vec3::from_xyz(0.6, 0., -1.)),
+        r_t,
+        &mut rec
+    ));
+    assert!(!quad.hit(
+        &Ray::new(Vec3::zeros(), Vec3::from_xyz(1., 0., 0.)),
+        r_t,
+        &mut rec
+    ));
+
+    // Rays from the center of a box hit the inside of every side.
+    let sides = cuboid(Vec3::zeros(), Vec3::ones(), 30., mat);
+    let center = Vec3::ones() * 0.5;
+    for dir in [
+        Vec3::from_xyz(1., 0., 0.),
+        Vec3::from_xyz(0., -1., 0.),
+        Vec3::from_xyz(0., 0.3, 1.),
+    ] {
+        assert!(sides.hit(&Ray::new(center, dir), r_t, &mut rec));
+        assert!(!rec.front_face);
+    }
+    let bbox = sides.bounding_box();
+    assert!((bbox.max().y() - 1.).abs() < 1e-3);
+    assert!(bbox.max().x() > 1.1);
+
+    // A 2x2 light seen head-on from distance 2.
+    let light = Quad::new(
+        Vec3::from_xyz(-1., -1., -2.),
+        Vec3::from_xyz(2., 0., 0.),
+        Vec3::from_xyz(0., 2., 0.),
+        Material::diffuse_light(Vec3::ones()),
+    );
+    assert!(light.is_light() && !quad.is_light());
+    let sample = light.sample(Vec3::zeros()).unwrap();
+    assert_eq!(sample.p.z(), -2.);
+    let pdf = light.pdf_value(Vec3::zeros(), sample.p, r_t);
+    assert!((pdf - sample.pdf).abs() < 1e-9);
+    assert_eq!(
+        light.pdf_value(Vec3::zeros(), Vec3::from_xyz(0., 0., -1.), r_t),
+        1.
+    );
+}
@@ -0,0 +1,44 @@
+use std::cell::RefCell;
+
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+
+// Every rayon worker owns a generator. Reseeding it per pixel makes renders reproducible
+// regardless of how pixels are scheduled across threads.
+thread_local! {
+    static RNG: RefCell<SmallRng> = RefCell::new(SmallRng::from_entropy());
+}
+
+pub fn seed(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = SmallRng::seed_from_u64(seed));
+}
+
+pub fn seed_for(seed: u64, stream: u64) {
+    // Seeds the generator for one of many independent streams (e.g. pixels) of a render.
+    self::seed(seed ^ stream.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+}
+
+pub fn random_f64() -> f64 {
+    // Returns a random number in [0, 1).
+    RNG.with(|rng| rng.borrow_mut().gen::<f64>())
+}
+
+pub fn random_range(min: f64, max: f64) -> f64 {
+    RNG.with(|rng| rng.borrow_mut().gen_range(min..max))
+}
+
+#[test]
+fn test_seed() {
+    seed(42);
+    let a: Vec<f64> = (0..4).map(|_| random_f64()).collect();
+    seed(42);
+    let b: Vec<f64> = (0..4).map(|_| random_f64()).collect();
+    assert_eq!(a, b);
+    assert!(a.iter().all(|x| (0. ..1.).contains(x)));
+
+    seed_for(42, 1);
+    let c = random_f64();
+    seed_for(42, 2);
+    assert_ne!(c, random_f64());
+    assert!((2. ..3.).contains(&random_range(2., 3.)));
+}
@@ -4,11 +4,21 @@ use crate::vector::Vec3;
 pub struct Ray {
     origin: Vec3,
     direction: Vec3,
+    wavelength: Option<f64>, // In nanometers, carried by the paths of spectral renders
 }
 
 impl Ray {
     pub fn new(origin: Vec3, direction: Vec3) -> Ray {
-        Ray { origin, direction }
+        Ray {
+            origin,
+            direction,
+            wavelength: None,
+        }
+    }
+
+    pub fn with_wavelength(mut self, wavelength: Option<f64>) -> Ray {
+        self.wavelength = wavelength;
+        self
     }
 
     pub fn origin(&self) -> Vec3 {
@@ -19,6 +29,10 @@ impl Ray {
         self.direction
     }
 
+    pub fn wavelength(&self) -> Option<f64> {
+        self.wavelength
+    }
+
     pub fn at(&self, t: f64) -> Vec3 {
         self.origin() + t * self.direction()
     }
@@ -33,4 +47,6 @@ fn test_ray() {
     assert_eq!(r.direction(), direction);
     assert_eq!(r.at(10.), Vec3::from_xyz(10., 10., 10.));
     assert_eq!(r.at(0.), Vec3::zeros());
+    assert_eq!(r.wavelength(), None);
+    assert_eq!(r.with_wavelength(Some(550.)).wavelength(), Some(550.));
 }
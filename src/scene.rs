@@ -0,0 +1,1252 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+
+use toml::de::{DeTable, DeValue};
+use toml::Spanned;
+
+use crate::aabb::Aabb;
+use crate::camera::CameraBuilder;
+use crate::environment::EnvironmentMap;
+use crate::hittable_list::HittableList;
+use crate::load_error::{LoadError, Location};
+use crate::material::{Dielectric, Material, PrincipledBuilder, CONDUCTORS};
+use crate::medium::{ConstantMedium, Fog, GridMedium};
+use crate::normal_map::NormalMap;
+use crate::obj::ObjModel;
+use crate::ply::PlyModel;
+use crate::quad::{cuboid, Quad};
+use crate::spectrum::Dispersion;
+use crate::sphere::Sphere;
+use crate::stl::StlModel;
+use crate::texture::{Filter, ImageTexture, NoisePattern, NoiseTexture, Texture, WrapMode};
+use crate::tone_map::ToneMap;
+use crate::triangle::Triangle;
+use crate::vector::Vec3;
+use crate::voxel_grid::VoxelGrid;
+
+pub struct Scene {
+    pub camera: CameraBuilder,
+    pub world: HittableList,
+}
+
+impl Scene {
+    pub fn load(path: &Path) -> Result<Self, LoadError> {
+        let src = fs::read_to_string(path).map_err(|err| LoadError::from(err).in_file(path))?;
+        // Mesh files are resolved relative to the scene file.
+        let dir = path.parent().unwrap_or(Path::new("."));
+        Scene::parse(&src, dir).map_err(|err| err.in_file(path))
+    }
+
+    pub fn parse(src: &str, dir: &Path) -> Result<Self, LoadError> {
+        let parser = SceneParser { src, dir };
+        let root = DeTable::parse(src).map_err(|err| {
+            let offset = err.span().map_or(0, |span| span.start);
+            LoadError::parse(Location::from_offset(src, offset), err.message().trim())
+        })?;
+        parser.check_keys(
+            root.get_ref(),
+            &[
+                "image",
+                "camera",
+                "fog",
+                "environment",
+                "textures",
+                "materials",
+                "objects",
+            ],
+            "scene",
+        )?;
+
+        let mut camera = CameraBuilder::default();
+        camera
+            .image_size(400, 225)
+            .samples_per_pixel(10)
+            .max_depth(50)
+            .vfov(90.)
+            .look_from(Vec3::zeros())
+            .look_at(Vec3::from_xyz(0., 0., -1.))
+            .vup(Vec3::from_xyz(0., 1., 0.))
+            .defocus_angle(0.)
+            .focus_dist(10.);
+
+        if let Some(image) = root.get_ref().get("image") {
+            let image = parser.table(image, "image")?;
+            parser.check_keys(
+                image,
+                &[
+                    "width",
+                    "height",
+                    "samples_per_pixel",
+                    "max_depth",
+                    "min_depth",
+                    "exposure",
+                    "tone_map",
+                    "white_point",
+                    "spectral",
+                ],
+                "image",
+            )?;
+            let width = parser.usize(image, "width")?;
+            if let Some(width) = parser.positive(image, "width", width)? {
+                camera.image_width(width);
+            }
+            let height = parser.usize(image, "height")?;
+            if let Some(height) = parser.positive(image, "height", height)? {
+                camera.image_height(height);
+            }
+            let spp = parser.usize(image, "samples_per_pixel")?;
+            if let Some(spp) = parser.positive(image, "samples_per_pixel", spp)? {
+                camera.samples_per_pixel(spp);
+            }
+            if let Some(max_depth) = parser.usize(image, "max_depth")? {
+                camera.max_depth(max_depth);
+            }
+            if let Some(min_depth) = parser.usize(image, "min_depth")? {
+                camera.min_depth(min_depth);
+            }
+            if let Some(exposure) = parser.f64(image, "exposure")? {
+                camera.exposure(exposure);
+            }
+            if let Some(tone_map) = parser.string(image, "tone_map")? {
+                let name = tone_map.get_ref().as_str().unwrap();
+                camera.tone_map(
+                    name.parse::<ToneMap>()
+                        .map_err(|message| parser.error(tone_map.span(), message))?,
+                );
+            }
+            if let Some(white_point) = parser.f64(image, "white_point")? {
+                camera.white_point(white_point);
+            }
+            if let Some(spectral) = parser.bool(image, "spectral")? {
+                camera.spectral(spectral);
+            }
+        }
+
+        if let Some(cam) = root.get_ref().get("camera") {
+            let cam = parser.table(cam, "camera")?;
+            parser.check_keys(
+                cam,
+                &[
+                    "vfov",
+                    "look_from",
+                    "look_at",
+                    "vup",
+                    "defocus_angle",
+                    "focus_dist",
+                    "background",
+                ],
+                "camera",
+            )?;
+            if let Some(vfov) = parser.f64(cam, "vfov")? {
+                camera.vfov(vfov);
+            }
+            if let Some(look_from) = parser.vec3(cam, "look_from")? {
+                camera.look_from(look_from);
+            }
+            if let Some(look_at) = parser.vec3(cam, "look_at")? {
+                camera.look_at(look_at);
+            }
+            if let Some(vup) = parser.vec3(cam, "vup")? {
+                camera.vup(vup);
+            }
+            if let Some(defocus_angle) = parser.f64(cam, "defocus_angle")? {
+                camera.defocus_angle(defocus_angle);
+            }
+            if let Some(focus_dist) = parser.f64(cam, "focus_dist")? {
+                camera.focus_dist(focus_dist);
+            }
+            if let Some(background) = parser.vec3(cam, "background")? {
+                camera.background(background);
+            }
+        }
+
+        if let Some(value) = root.get_ref().get("fog") {
+            let fog = parser.table(value, "fog")?;
+            parser.check_keys(fog, &["density", "albedo", "g"], "fog")?;
+            let density = parser.f64(fog, "density")?;
+            let density = parser.finite(fog, "density", density)?;
+            let density = parser.positive(fog, "density", density)?;
+            let density = parser.required(density, value.span(), "density", "fog")?;
+            let albedo = parser.vec3(fog, "albedo")?.unwrap_or(Vec3::ones());
+            let g = parser.f64(fog, "g")?.unwrap_or(0.);
+            camera.fog(Fog::new(density, albedo, g));
+        }
+
+        if let Some(value) = root.get_ref().get("environment") {
+            let environment = parser.table(value, "environment")?;
+            parser.check_keys(
+                environment,
+                &["file", "rotation", "intensity"],
+                "environment",
+            )?;
+            let background = root
+                .get_ref()
+                .get("camera")
+                .and_then(|cam| match cam.get_ref() {
+                    DeValue::Table(cam) => cam.get("background"),
+                    _ => None,
+                });
+            if let Some(background) = background {
+                return Err(parser.error(
+                    background.span(),
+                    "cannot have both a camera `background` and an `environment`",
+                ));
+            }
+            let file = parser.string(environment, "file")?;
+            let file = parser.required(file, value.span(), "file", "environment")?;
+            let rotation = parser.f64(environment, "rotation")?.unwrap_or(0.);
+            let intensity = parser.f64(environment, "intensity")?.unwrap_or(1.);
+            let path = dir.join(file.get_ref().as_str().unwrap());
+            let map = EnvironmentMap::load(&path, rotation, intensity).map_err(|err| {
+                parser.error(file.span(), format!("cannot load environment map: {}", err))
+            })?;
+            camera.environment(Arc::new(map));
+        }
+
+        let mut textures = HashMap::new();
+        if let Some(table) = root.get_ref().get("textures") {
+            let table = parser.table(table, "textures")?;
+            for name in table.keys() {
+                parser.texture(name.get_ref(), table, &mut textures, &mut Vec::new())?;
+            }
+        }
+
+        let mut materials = HashMap::new();
+        if let Some(table) = root.get_ref().get("materials") {
+            for (name, value) in parser.table(table, "materials")?.iter() {
+                let mat = parser.material(value, name.get_ref(), &textures)?;
+                materials.insert(name.get_ref().to_string(), mat);
+            }
+        }
+
+        let mut world = HittableList::new();
+        if let Some(objects) = root.get_ref().get("objects") {
+            let objects = match objects.get_ref() {
+                DeValue::Array(array) => array,
+                other => {
+                    return Err(parser.type_error(objects.span(), "objects", "an array", other))
+                }
+            };
+            for object in objects.iter() {
+                parser.object(object, &materials, None, &mut world)?;
+            }
+        }
+
+        Ok(Scene { camera, world })
+    }
+}
+
+struct SceneParser<'s> {
+    src: &'s str,
+    dir: &'s Path,
+}
+
+type Value<'i> = Spanned<DeValue<'i>>;
+
+const MAP_KEYS: &[&str] = &["bump", "bump_scale", "normal_map", "normal_strength"];
+
+impl SceneParser<'_> {
+    fn error(&self, span: Range<usize>, message: impl Into<String>) -> LoadError {
+        LoadError::parse(Location::from_offset(self.src, span.start), message)
+    }
+
+    fn type_error(
+        &self,
+        span: Range<usize>,
+        key: &str,
+        expected: &str,
+        found: &DeValue,
+    ) -> LoadError {
+        self.error(
+            span,
+            format!("`{}` must be {}, found {}", key, expected, found.type_str()),
+        )
+    }
+
+    fn check_keys(
+        &self,
+        table: &DeTable,
+        allowed: &[&str],
+        context: &str,
+    ) -> Result<(), LoadError> {
+        match table
+            .keys()
+            .find(|key| !allowed.contains(&key.get_ref().as_ref()))
+        {
+            Some(key) => Err(self.error(
+                key.span(),
+                format!(
+                    "unknown key `{}` in {}, expected one of: {}",
+                    key.get_ref(),
+                    context,
+                    allowed.join(", ")
+                ),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn table<'a, 'i>(&self, value: &'a Value<'i>, key: &str) -> Result<&'a DeTable<'i>, LoadError> {
+        match value.get_ref() {
+            DeValue::Table(table) => Ok(table),
+            other => Err(self.type_error(value.span(), key, "a table", other)),
+        }
+    }
+
+    fn number(&self, value: &Value, key: &str) -> Result<f64, LoadError> {
+        let parsed = match value.get_ref() {
+            DeValue::Integer(i) => i64::from_str_radix(i.as_str(), i.radix())
+                .ok()
+                .map(|i| i as f64),
+            DeValue::Float(f) => f.as_str().parse::<f64>().ok(),
+            other => return Err(self.type_error(value.span(), key, "a number", other)),
+        };
+        parsed.ok_or_else(|| self.error(value.span(), format!("`{}` is out of range", key)))
+    }
+
+    fn f64(&self, table: &DeTable, key: &str) -> Result<Option<f64>, LoadError> {
+        table
+            .get(key)
+            .map(|value| self.number(value, key))
+            .transpose()
+    }
+
+    fn usize(&self, table: &DeTable, key: &str) -> Result<Option<usize>, LoadError> {
+        let value = match table.get(key) {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        match value.get_ref() {
+            DeValue::Integer(i) => match usize::from_str_radix(i.as_str(), i.radix()) {
+                Ok(n) => Ok(Some(n)),
+                Err(_) => Err(self.error(
+                    value.span(),
+                    format!("`{}` must be a non-negative integer", key),
+                )),
+            },
+            other => Err(self.type_error(value.span(), key, "an integer", other)),
+        }
+    }
+
+    fn positive<T: PartialOrd + Default>(
+        &self,
+        table: &DeTable,
+        key: &str,
+        value: Option<T>,
+    ) -> Result<Option<T>, LoadError> {
+        // Rejects values parsed from `key` that are not greater than zero, NaN included.
+        match (&value, table.get(key)) {
+            (Some(v), Some(entry)) if v.partial_cmp(&T::default()) != Some(Ordering::Greater) => {
+                Err(self.error(entry.span(), format!("`{}` must be positive", key)))
+            }
+            _ => Ok(value),
+        }
+    }
+
+    fn finite(
+        &self,
+        table: &DeTable,
+        key: &str,
+        value: Option<f64>,
+    ) -> Result<Option<f64>, LoadError> {
+        // TOML accepts `inf` and `nan`, which break sampling.
+        match (value, table.get(key)) {
+            (Some(v), Some(entry)) if !v.is_finite() => {
+                Err(self.error(entry.span(), format!("`{}` must be finite", key)))
+            }
+            _ => Ok(value),
+        }
+    }
+
+    fn vec3_value(&self, value: &Value, key: &str) -> Result<Vec3, LoadError> {
+        match value.get_ref() {
+            DeValue::Array(array) if array.len() == 3 => Ok(Vec3::from_xyz(
+                self.number(&array[0], key)?,
+                self.number(&array[1], key)?,
+                self.number(&array[2], key)?,
+            )),
+            DeValue::Array(array) => Err(self.error(
+                value.span(),
+                format!("`{}` must have 3 components, found {}", key, array.len()),
+            )),
+            other => Err(self.type_error(value.span(), key, "an array of 3 numbers", other)),
+        }
+    }
+
+    fn vec3(&self, table: &DeTable, key: &str) -> Result<Option<Vec3>, LoadError> {
+        table
+            .get(key)
+            .map(|value| self.vec3_value(value, key))
+            .transpose()
+    }
+
+    fn string<'a>(
+        &self,
+        table: &'a DeTable,
+        key: &str,
+    ) -> Result<Option<&'a Value<'a>>, LoadError> {
+        match table.get(key) {
+            Some(value) => match value.get_ref() {
+                DeValue::String(_) => Ok(Some(value)),
+                other => Err(self.type_error(value.span(), key, "a string", other)),
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn bool(&self, table: &DeTable, key: &str) -> Result<Option<bool>, LoadError> {
+        match table.get(key) {
+            Some(value) => match value.get_ref() {
+                DeValue::Boolean(b) => Ok(Some(*b)),
+                other => Err(self.type_error(value.span(), key, "a boolean", other)),
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn required<T>(
+        &self,
+        value: Option<T>,
+        span: Range<usize>,
+        key: &str,
+        context: &str,
+    ) -> Result<T, LoadError> {
+        value.ok_or_else(|| self.error(span, format!("missing `{}` in {}", key, context)))
+    }
+
+    fn texture(
+        &self,
+        name: &str,
+        defs: &DeTable,
+        textures: &mut HashMap<String, Texture>,
+        pending: &mut Vec<String>,
+    ) -> Result<Texture, LoadError> {
+        // Parses the texture `name` of the `[textures]` table, after the textures it refers to.
+        if let Some(texture) = textures.get(name) {
+            return Ok(texture.clone());
+        }
+        let value = &defs[name];
+        if pending.iter().any(|p| p == name) {
+            return Err(self.error(value.span(), format!("texture `{}` refers to itself", name)));
+        }
+        pending.push(name.to_string());
+
+        let context = format!("texture `{}`", name);
+        let table = self.table(value, name)?;
+        let ty = self.string(table, "type")?;
+        let ty = self.required(ty, value.span(), "type", &context)?;
+        let texture = match ty.get_ref().as_str().unwrap() {
+            "solid" => {
+                self.check_keys(table, &["type", "color"], &context)?;
+                let color = self.vec3(table, "color")?;
+                Texture::solid(self.required(color, value.span(), "color", &context)?)
+            }
+            "checker" => {
+                self.check_keys(table, &["type", "scale", "even", "odd"], &context)?;
+                let mut pattern =
+                    |key: &str| -> Result<Texture, LoadError> {
+                        let value = self.required(table.get(key), value.span(), key, &context)?;
+                        match value.get_ref() {
+                            DeValue::String(other) => match defs.get(other.as_ref()) {
+                                Some(_) => self.texture(other, defs, textures, pending),
+                                None => Err(self
+                                    .error(value.span(), format!("undefined texture `{}`", other))),
+                            },
+                            _ => Ok(Texture::solid(self.vec3_value(value, key)?)),
+                        }
+                    };
+                let (even, odd) = (pattern("even")?, pattern("odd")?);
+                Texture::checker(self.f64(table, "scale")?.unwrap_or(1.), even, odd)
+            }
+            "image" => {
+                self.check_keys(
+                    table,
+                    &["type", "file", "color_space", "wrap", "filter"],
+                    &context,
+                )?;
+                let file = self.string(table, "file")?;
+                let file = self.required(file, value.span(), "file", &context)?;
+                let path = self.dir.join(file.get_ref().as_str().unwrap());
+                // Normal maps and other data textures are stored linearly.
+                let load = match self.string(table, "color_space")? {
+                    Some(space) => match space.get_ref().as_str().unwrap() {
+                        "srgb" => ImageTexture::load,
+                        "linear" => ImageTexture::load_linear,
+                        other => {
+                            return Err(self.error(
+                                space.span(),
+                                format!(
+                                    "unknown color space `{}`, expected one of: srgb, linear",
+                                    other
+                                ),
+                            ))
+                        }
+                    },
+                    None => ImageTexture::load,
+                };
+                let mut image = load(&path).map_err(|err| {
+                    self.error(file.span(), format!("cannot load texture: {}", err))
+                })?;
+                if let Some(wrap) = self.string(table, "wrap")? {
+                    let name = wrap.get_ref().as_str().unwrap();
+                    image = image.with_wrap(
+                        name.parse::<WrapMode>()
+                            .map_err(|message| self.error(wrap.span(), message))?,
+                    );
+                }
+                if let Some(filter) = self.string(table, "filter")? {
+                    let name = filter.get_ref().as_str().unwrap();
+                    image = image.with_filter(
+                        name.parse::<Filter>()
+                            .map_err(|message| self.error(filter.span(), message))?,
+                    );
+                }
+                Texture::image(image)
+            }
+            "noise" => {
+                self.check_keys(
+                    table,
+                    &["type", "pattern", "seed", "scale", "octaves", "low", "high"],
+                    &context,
+                )?;
+                let pattern = match self.string(table, "pattern")? {
+                    Some(pattern) => pattern
+                        .get_ref()
+                        .as_str()
+                        .unwrap()
+                        .parse::<NoisePattern>()
+                        .map_err(|message| self.error(pattern.span(), message))?,
+                    None => NoisePattern::default(),
+                };
+                let seed = self.usize(table, "seed")?.unwrap_or(0) as u64;
+                let mut noise = NoiseTexture::new(pattern, seed);
+                if let Some(scale) = self.f64(table, "scale")? {
+                    noise = noise.with_scale(scale);
+                }
+                if let Some(octaves) = self.usize(table, "octaves")? {
+                    noise = noise.with_octaves(octaves);
+                }
+                let low = self.vec3(table, "low")?.unwrap_or(Vec3::zeros());
+                let high = self.vec3(table, "high")?.unwrap_or(Vec3::ones());
+                Texture::noise(noise.with_colors(low, high))
+            }
+            other => {
+                return Err(self.error(
+                    ty.span(),
+                    format!(
+                        "unknown texture type `{}`, expected one of: solid, checker, image, noise",
+                        other
+                    ),
+                ))
+            }
+        };
+
+        pending.pop();
+        textures.insert(name.to_string(), texture.clone());
+        Ok(texture)
+    }
+
+    fn texture_ref(
+        &self,
+        table: &DeTable,
+        key: &str,
+        textures: &HashMap<String, Texture>,
+    ) -> Result<Option<Texture>, LoadError> {
+        // Either a constant color or the name of a texture.
+        let value = match table.get(key) {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        match value.get_ref() {
+            DeValue::String(name) => match textures.get(name.as_ref()) {
+                Some(texture) => Ok(Some(texture.clone())),
+                None => Err(self.error(value.span(), format!("undefined texture `{}`", name))),
+            },
+            _ => Ok(Some(Texture::solid(self.vec3_value(value, key)?))),
+        }
+    }
+
+    fn material(
+        &self,
+        value: &Value,
+        name: &str,
+        textures: &HashMap<String, Texture>,
+    ) -> Result<Material, LoadError> {
+        let context = format!("material `{}`", name);
+        let table = self.table(value, name)?;
+        let ty = self.string(table, "type")?;
+        let ty = self.required(ty, value.span(), "type", &context)?;
+
+        // Every material type accepts a bump or normal map.
+        let keys = |keys: &[&'static str]| [keys, MAP_KEYS].concat();
+        let mat = match ty.get_ref().as_str().unwrap() {
+            "lambertian" => {
+                self.check_keys(table, &keys(&["type", "albedo"]), &context)?;
+                let albedo = self.texture_ref(table, "albedo", textures)?;
+                Material::lambertian(self.required(albedo, value.span(), "albedo", &context)?)
+            }
+            "metal" => {
+                self.check_keys(table, &keys(&["type", "albedo", "fuzz"]), &context)?;
+                let albedo = self.texture_ref(table, "albedo", textures)?;
+                let albedo = self.required(albedo, value.span(), "albedo", &context)?;
+                Material::metal(albedo, self.f64(table, "fuzz")?.unwrap_or(0.))
+            }
+            "conductor" => {
+                self.check_keys(
+                    table,
+                    &keys(&["type", "preset", "eta", "k", "roughness"]),
+                    &context,
+                )?;
+                // A named metal supplies eta and k, explicit values override it.
+                let (mut eta, mut k) = (None, None);
+                if let Some(preset) = self.string(table, "preset")? {
+                    let name = preset.get_ref().as_str().unwrap();
+                    match CONDUCTORS.iter().find(|(n, _, _)| *n == name) {
+                        Some((_, preset_eta, preset_k)) => {
+                            eta = Some(Vec3::new(*preset_eta));
+                            k = Some(Vec3::new(*preset_k));
+                        }
+                        None => {
+                            let names: Vec<&str> = CONDUCTORS.iter().map(|(n, _, _)| *n).collect();
+                            return Err(self.error(
+                                preset.span(),
+                                format!(
+                                    "unknown conductor `{}`, expected one of: {}",
+                                    name,
+                                    names.join(", ")
+                                ),
+                            ));
+                        }
+                    }
+                }
+                let eta = self.vec3(table, "eta")?.or(eta);
+                let k = self.vec3(table, "k")?.or(k);
+                Material::conductor(
+                    self.required(eta, value.span(), "eta", &context)?,
+                    self.required(k, value.span(), "k", &context)?,
+                    self.f64(table, "roughness")?.unwrap_or(0.),
+                )
+            }
+            "dielectric" => {
+                self.check_keys(
+                    table,
+                    &keys(&[
+                        "type",
+                        "ref_idx",
+                        "roughness",
+                        "absorption",
+                        "transmittance",
+                        "transmittance_distance",
+                        "glass",
+                        "cauchy_a",
+                        "cauchy_b",
+                        "sellmeier_b",
+                        "sellmeier_c",
+                    ]),
+                    &context,
+                )?;
+                // The index of refraction is a constant, or depends on the wavelength through a
+                // named glass or Cauchy or Sellmeier coefficients.
+                let ref_idx = self.f64(table, "ref_idx")?;
+                let mut dispersions = Vec::new();
+                if let Some(glass) = self.string(table, "glass")? {
+                    let name = glass.get_ref().as_str().unwrap();
+                    dispersions.push(
+                        name.parse::<Dispersion>()
+                            .map_err(|message| self.error(glass.span(), message))?,
+                    );
+                }
+                if let Some(a) = self.f64(table, "cauchy_a")? {
+                    let b = self.f64(table, "cauchy_b")?.unwrap_or(0.);
+                    dispersions.push(Dispersion::Cauchy { a, b });
+                }
+                if let Some(b) = self.vec3(table, "sellmeier_b")? {
+                    let c = self.vec3(table, "sellmeier_c")?;
+                    let c = self.required(c, value.span(), "sellmeier_c", &context)?;
+                    dispersions.push(Dispersion::Sellmeier { b: b.e, c: c.e });
+                }
+                let dielectric = match (ref_idx, dispersions.as_slice()) {
+                    (Some(ref_idx), []) => Dielectric::new(ref_idx),
+                    (None, [dispersion]) => Dielectric::dispersive(*dispersion),
+                    (None, []) => {
+                        return Err(
+                            self.error(value.span(), format!("missing `ref_idx` in {}", context))
+                        )
+                    }
+                    _ => {
+                        return Err(self.error(
+                            value.span(),
+                            format!(
+                                "{} cannot have more than one of `ref_idx`, `glass`, `cauchy_a` \
+                                 and `sellmeier_b`",
+                                context
+                            ),
+                        ))
+                    }
+                };
+                let roughness = self.f64(table, "roughness")?.unwrap_or(0.);
+                let mut dielectric = dielectric.with_roughness(roughness);
+                // Colored interiors are given either directly or as the color left after a distance.
+                let absorption = self.vec3(table, "absorption")?;
+                let transmittance = self.vec3(table, "transmittance")?;
+                match (absorption, transmittance) {
+                    (Some(_), Some(_)) => {
+                        return Err(self.error(
+                            value.span(),
+                            format!(
+                                "{} cannot have both `absorption` and `transmittance`",
+                                context
+                            ),
+                        ))
+                    }
+                    (Some(absorption), None) => dielectric = dielectric.with_absorption(absorption),
+                    (None, Some(color)) => {
+                        let distance = self.f64(table, "transmittance_distance")?;
+                        let distance = self
+                            .positive(table, "transmittance_distance", distance)?
+                            .unwrap_or(1.);
+                        dielectric = dielectric.with_transmittance(color, distance);
+                    }
+                    (None, None) => {}
+                }
+                Material::Dielectric(dielectric)
+            }
+            "isotropic" => {
+                self.check_keys(table, &keys(&["type", "albedo"]), &context)?;
+                let albedo = self.texture_ref(table, "albedo", textures)?;
+                Material::isotropic(self.required(albedo, value.span(), "albedo", &context)?)
+            }
+            "henyey_greenstein" => {
+                self.check_keys(table, &keys(&["type", "albedo", "g"]), &context)?;
+                let albedo = self.texture_ref(table, "albedo", textures)?;
+                let albedo = self.required(albedo, value.span(), "albedo", &context)?;
+                let g = self.f64(table, "g")?;
+                Material::henyey_greenstein(albedo, self.required(g, value.span(), "g", &context)?)
+            }
+            "principled" => {
+                const FACTORS: [&str; 9] = [
+                    "metallic",
+                    "roughness",
+                    "specular",
+                    "ior",
+                    "transmission",
+                    "clearcoat",
+                    "clearcoat_roughness",
+                    "sheen",
+                    "sheen_tint",
+                ];
+                let setters: [fn(&mut PrincipledBuilder, f64) -> &mut PrincipledBuilder; 9] = [
+                    PrincipledBuilder::metallic,
+                    PrincipledBuilder::roughness,
+                    PrincipledBuilder::specular,
+                    PrincipledBuilder::ior,
+                    PrincipledBuilder::transmission,
+                    PrincipledBuilder::clearcoat,
+                    PrincipledBuilder::clearcoat_roughness,
+                    PrincipledBuilder::sheen,
+                    PrincipledBuilder::sheen_tint,
+                ];
+                let textured: &[&str] = &["type", "base_color", "metallic_roughness"];
+                self.check_keys(table, &keys(&[textured, &FACTORS].concat()), &context)?;
+                let mut principled = PrincipledBuilder::default();
+                if let Some(base_color) = self.texture_ref(table, "base_color", textures)? {
+                    principled.base_color(base_color);
+                }
+                if let Some(texture) = self.texture_ref(table, "metallic_roughness", textures)? {
+                    principled.metallic_roughness(texture);
+                }
+                for (key, set) in FACTORS.into_iter().zip(setters) {
+                    if let Some(value) = self.f64(table, key)? {
+                        set(&mut principled, value);
+                    }
+                }
+                // Every parameter has a default.
+                Material::principled(principled.build().unwrap())
+            }
+            "diffuse_light" => {
+                self.check_keys(table, &keys(&["type", "emit"]), &context)?;
+                let emit = self.vec3(table, "emit")?;
+                Material::diffuse_light(self.required(emit, value.span(), "emit", &context)?)
+            }
+            other => {
+                return Err(self.error(
+                    ty.span(),
+                    format!(
+                        "unknown material type `{}`, expected one of: lambertian, metal, \
+                         conductor, dielectric, principled, isotropic, henyey_greenstein, \
+                         diffuse_light",
+                        other
+                    ),
+                ))
+            }
+        };
+
+        let bump = self.texture_ref(table, "bump", textures)?;
+        let normal_map = self.texture_ref(table, "normal_map", textures)?;
+        match (bump, normal_map) {
+            (Some(_), Some(_)) => Err(self.error(
+                value.span(),
+                format!("{} cannot have both `bump` and `normal_map`", context),
+            )),
+            (Some(height), None) => {
+                let scale = self.f64(table, "bump_scale")?.unwrap_or(1.);
+                Ok(mat.with_normal_map(NormalMap::bump(height, scale)))
+            }
+            (None, Some(texture)) => {
+                let strength = self.f64(table, "normal_strength")?.unwrap_or(1.);
+                Ok(mat.with_normal_map(NormalMap::normal(texture, strength)))
+            }
+            (None, None) => Ok(mat),
+        }
+    }
+
+    fn material_ref(
+        &self,
+        table: &DeTable,
+        materials: &HashMap<String, Material>,
+    ) -> Result<Option<Material>, LoadError> {
+        match self.string(table, "material")? {
+            Some(value) => {
+                let name = value.get_ref().as_str().unwrap();
+                match materials.get(name) {
+                    Some(mat) => Ok(Some(mat.clone())),
+                    None => Err(self.error(value.span(), format!("undefined material `{}`", name))),
+                }
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn object(
+        &self,
+        value: &Value,
+        materials: &HashMap<String, Material>,
+        default_mat: Option<&Material>,
+        world: &mut HittableList,
+    ) -> Result<(), LoadError> {
+        // Objects without a `material` get `default_mat`, if any.
+        let table = self.table(value, "objects")?;
+        let ty = self.string(table, "type")?;
+        let ty = self.required(ty, value.span(), "type", "object")?;
+        let missing = |key: &str| self.error(value.span(), format!("missing `{}` in object", key));
+        let material = || match self.material_ref(table, materials)? {
+            Some(mat) => Ok(mat),
+            None => default_mat.cloned().ok_or_else(|| missing("material")),
+        };
+
+        match ty.get_ref().as_str().unwrap() {
+            "sphere" => {
+                self.check_keys(table, &["type", "center", "radius", "material"], "sphere")?;
+                let center = self
+                    .vec3(table, "center")?
+                    .ok_or_else(|| missing("center"))?;
+                let radius = self
+                    .f64(table, "radius")?
+                    .ok_or_else(|| missing("radius"))?;
+                let mat = material()?;
+                world.add(Sphere::new(center, radius, mat));
+            }
+            "triangle" => {
+                self.check_keys(
+                    table,
+                    &["type", "vertices", "normals", "material"],
+                    "triangle",
+                )?;
+                let vertices = self
+                    .vec3_triple(table, "vertices")?
+                    .ok_or_else(|| missing("vertices"))?;
+                let mat = material()?;
+                let mut triangle = Triangle::new(vertices[0], vertices[1], vertices[2], mat);
+                if let Some(n) = self.vec3_triple(table, "normals")? {
+                    triangle = triangle.with_normals(n[0], n[1], n[2]);
+                }
+                world.add(triangle);
+            }
+            "quad" => {
+                self.check_keys(table, &["type", "q", "u", "v", "material"], "quad")?;
+                let q = self.vec3(table, "q")?.ok_or_else(|| missing("q"))?;
+                let u = self.vec3(table, "u")?.ok_or_else(|| missing("u"))?;
+                let v = self.vec3(table, "v")?.ok_or_else(|| missing("v"))?;
+                let mat = material()?;
+                world.add(Quad::new(q, u, v, mat));
+            }
+            "box" => {
+                self.check_keys(
+                    table,
+                    &["type", "min", "max", "rotate_y", "material"],
+                    "box",
+                )?;
+                let min = self.vec3(table, "min")?.ok_or_else(|| missing("min"))?;
+                let max = self.vec3(table, "max")?.ok_or_else(|| missing("max"))?;
+                let rotate_y = self.f64(table, "rotate_y")?.unwrap_or(0.);
+                let mat = material()?;
+                world.add(cuboid(min, max, rotate_y, mat));
+            }
+            "mesh" => {
+                self.check_keys(table, &["type", "file", "material"], "mesh")?;
+                let file = self.string(table, "file")?.ok_or_else(|| missing("file"))?;
+                // Meshes without a material (or OBJ groups without `usemtl`) are light gray.
+                let mat = self
+                    .material_ref(table, materials)?
+                    .unwrap_or_else(|| Material::lambertian(Vec3::from_rgb(0.5, 0.5, 0.5)));
+                world.add(self.mesh(file, mat)?);
+            }
+            "medium" => {
+                self.check_keys(
+                    table,
+                    &["type", "boundary", "density", "material"],
+                    "medium",
+                )?;
+                // The boundary is an object itself, its material is not used.
+                let boundary = table.get("boundary").ok_or_else(|| missing("boundary"))?;
+                let mut shape = HittableList::new();
+                let unused = Material::lambertian(Vec3::zeros());
+                self.object(boundary, materials, Some(&unused), &mut shape)?;
+                let density = self.f64(table, "density")?;
+                let density = self.finite(table, "density", density)?;
+                let density = self
+                    .positive(table, "density", density)?
+                    .ok_or_else(|| missing("density"))?;
+                let mat = material()?;
+                world.add(ConstantMedium::new(shape, density, mat));
+            }
+            "volume" => {
+                self.check_keys(
+                    table,
+                    &[
+                        "type",
+                        "min",
+                        "max",
+                        "file",
+                        "resolution",
+                        "seed",
+                        "frequency",
+                        "octaves",
+                        "density",
+                        "material",
+                    ],
+                    "volume",
+                )?;
+                let min = self.vec3(table, "min")?.ok_or_else(|| missing("min"))?;
+                let max = self.vec3(table, "max")?.ok_or_else(|| missing("max"))?;
+                let grid = match self.string(table, "file")? {
+                    Some(file) => {
+                        let noise = ["resolution", "seed", "frequency", "octaves"];
+                        if let Some(key) = noise.iter().find(|key| table.contains_key(**key)) {
+                            return Err(self.error(
+                                value.span(),
+                                format!("volume cannot have both `file` and `{}`", key),
+                            ));
+                        }
+                        self.voxel_grid(file)?
+                    }
+                    // Without a file the grid is a procedural cloud.
+                    None => VoxelGrid::from_noise(
+                        self.resolution(table)?.unwrap_or(64),
+                        self.usize(table, "seed")?.unwrap_or(0) as u64,
+                        self.f64(table, "frequency")?.unwrap_or(4.),
+                        self.usize(table, "octaves")?.unwrap_or(4),
+                    ),
+                };
+                let density = self.f64(table, "density")?;
+                let density = self.finite(table, "density", density)?;
+                let density = self.positive(table, "density", density)?.unwrap_or(1.);
+                let mat = material()?;
+                let bbox = Aabb::from_points(min, max);
+                world.add(GridMedium::new(Arc::new(grid), bbox, density, mat));
+            }
+            other => {
+                return Err(self.error(
+                    ty.span(),
+                    format!(
+                        "unknown object type `{}`, expected one of: sphere, triangle, quad, box, \
+                         mesh, medium, volume",
+                        other
+                    ),
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    fn vec3_triple(&self, table: &DeTable, key: &str) -> Result<Option<[Vec3; 3]>, LoadError> {
+        let value = match table.get(key) {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        match value.get_ref() {
+            DeValue::Array(array) if array.len() == 3 => Ok(Some([
+                self.vec3_value(&array[0], key)?,
+                self.vec3_value(&array[1], key)?,
+                self.vec3_value(&array[2], key)?,
+            ])),
+            DeValue::Array(array) => Err(self.error(
+                value.span(),
+                format!("`{}` must have 3 entries, found {}", key, array.len()),
+            )),
+            other => Err(self.type_error(value.span(), key, "an array of 3 points", other)),
+        }
+    }
+
+    fn voxel_grid(&self, file: &Value) -> Result<VoxelGrid, LoadError> {
+        let path = self.dir.join(file.get_ref().as_str().unwrap());
+        VoxelGrid::load(&path)
+            .map_err(|err| self.error(file.span(), format!("cannot load voxel grid: {}", err)))
+    }
+
+    fn resolution(&self, table: &DeTable) -> Result<Option<usize>, LoadError> {
+        // Procedural grids are dense, their memory grows with the cube of the resolution.
+        const MAX_RESOLUTION: usize = 1024;
+        let resolution = self.usize(table, "resolution")?;
+        match (
+            self.positive(table, "resolution", resolution)?,
+            table.get("resolution"),
+        ) {
+            (Some(n), Some(value)) if n > MAX_RESOLUTION => Err(self.error(
+                value.span(),
+                format!("`resolution` must be at most {}", MAX_RESOLUTION),
+            )),
+            (resolution, _) => Ok(resolution),
+        }
+    }
+
+    fn mesh(&self, file: &Value, mat: Material) -> Result<HittableList, LoadError> {
+        let name = file.get_ref().as_str().unwrap();
+        let path = self.dir.join(name);
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase());
+
+        let mut list = HittableList::new();
+        let loaded = match extension.as_deref() {
+            Some("obj") => ObjModel::load(&path).map(|model| list = model.to_hittable_list(mat)),
+            Some("ply") => PlyModel::load(&path).map(|model| list.add(model.to_mesh(mat))),
+            Some("stl") => StlModel::load(&path).map(|model| list.add(model.to_mesh(mat))),
+            _ => {
+                return Err(self.error(
+                    file.span(),
+                    format!(
+                        "unsupported mesh format `{}`, expected .obj, .ply or .stl",
+                        name
+                    ),
+                ))
+            }
+        };
+        loaded.map_err(|err| self.error(file.span(), format!("cannot load mesh: {}", err)))?;
+        Ok(list)
+    }
+}
+
+#[test]
+fn test_parse_scene() {
+    use crate::hittable::Hittable;
+
+    let scene = Scene::parse(include_str!("../scenes/example.toml"), Path::new("scenes")).unwrap();
+    assert_eq!(scene.world.len(), 5);
+    let camera = scene.camera.build();
+    assert!(camera.is_ok());
+    assert_eq!(scene.world.bounding_box().min().y(), -2000.);
+
+    let parse_err = |src: &str| Scene::parse(src, Path::new(".")).err().unwrap().to_string();
+    assert_eq!(
+        parse_err("[camera]\nvfov = 20\nfov = 30\n"),
+        "line 3, column 1: unknown key `fov` in camera, expected one of: \
+         vfov, look_from, look_at, vup, defocus_angle, focus_dist, background"
+    );
+    assert_eq!(
+        parse_err("[image]\nwidth = \"wide\"\n"),
+        "line 2, column 9: `width` must be an integer, found string"
+    );
+    assert_eq!(
+        parse_err("[camera]\nlook_at = [0, 1]\n"),
+        "line 2, column 11: `look_at` must have 3 components, found 2"
+    );
+    assert_eq!(
+        parse_err(
+            "[[objects]]\ntype = \"sphere\"\ncenter = [0, 0, 0]\nradius = 1\nmaterial = \"gold\"\n"
+        ),
+        "line 5, column 12: undefined material `gold`"
+    );
+    assert!(parse_err("[image]\ntone_map = \"filmic\"\n")
+        .starts_with("line 2, column 12: unknown tone mapper `filmic`"));
+    assert!(parse_err("[camera\n").starts_with("line 1, column 8: "));
+    assert_eq!(
+        parse_err("[fog]\nalbedo = [1, 1, 1]\n"),
+        "line 1, column 1: missing `density` in fog"
+    );
+    assert_eq!(
+        parse_err("[fog]\ndensity = 0\n"),
+        "line 2, column 11: `density` must be positive"
+    );
+    assert_eq!(
+        parse_err("[fog]\ndensity = inf\n"),
+        "line 2, column 11: `density` must be finite"
+    );
+    assert!(
+        parse_err("[environment]\nfile = \"missing.hdr\"\nrotation = 90\n")
+            .starts_with("line 2, column 8: cannot load environment map: ")
+    );
+    assert_eq!(
+        parse_err("[camera]\nbackground = [0, 0, 0]\n[environment]\nfile = \"sky.exr\"\n"),
+        "line 2, column 14: cannot have both a camera `background` and an `environment`"
+    );
+
+    let textured = "\
+[image]
+spectral = true
+[fog]
+density = 0.01
+albedo = [0.9, 0.9, 0.9]
+g = 0.3
+[textures.white]
+type = \"solid\"
+color = [1, 1, 1]
+[textures.floor]
+type = \"checker\"
+scale = 0.5
+even = \"white\"
+odd = [0, 0, 0]
+[textures.marble]
+type = \"noise\"
+pattern = \"marble\"
+seed = 3
+scale = 4
+[materials.ground]
+type = \"lambertian\"
+albedo = \"floor\"
+[materials.stone]
+type = \"lambertian\"
+albedo = \"marble\"
+bump = \"marble\"
+bump_scale = 0.05
+[materials.gold]
+type = \"conductor\"
+preset = \"gold\"
+roughness = 0.3
+[materials.car_paint]
+type = \"principled\"
+base_color = \"marble\"
+metallic = 0.5
+roughness = 0.4
+clearcoat = 1
+[materials.prism]
+type = \"dielectric\"
+glass = \"bk7\"
+[materials.flint]
+type = \"dielectric\"
+cauchy_a = 1.67
+cauchy_b = 0.0074
+[materials.smoke]
+type = \"henyey_greenstein\"
+albedo = [0.8, 0.8, 0.8]
+g = 0.6
+[materials.frosted]
+type = \"dielectric\"
+ref_idx = 1.5
+roughness = 0.2
+transmittance = [0.2, 0.8, 0.4]
+transmittance_distance = 2
+[[objects]]
+type = \"medium\"
+density = 0.5
+material = \"smoke\"
+boundary = { type = \"sphere\", center = [0, 1, 0], radius = 1 }
+[[objects]]
+type = \"volume\"
+min = [-1, 0, -1]
+max = [1, 2, 1]
+resolution = 8
+density = 4
+material = \"smoke\"
+";
+    assert_eq!(
+        Scene::parse(textured, Path::new(".")).unwrap().world.len(),
+        2
+    );
+    assert_eq!(
+        parse_err("[materials.m]\ntype = \"dielectric\"\nref_idx = 1.5\nglass = \"bk7\"\n"),
+        "line 1, column 1: material `m` cannot have more than one of `ref_idx`, `glass`, \
+         `cauchy_a` and `sellmeier_b`"
+    );
+    assert_eq!(
+        parse_err("[materials.m]\ntype = \"conductor\"\npreset = \"tin\"\n"),
+        "line 3, column 10: unknown conductor `tin`, expected one of: gold, copper, aluminium"
+    );
+    assert_eq!(
+        parse_err("[materials.m]\ntype = \"metal\"\nalbedo = \"rust\"\n"),
+        "line 3, column 10: undefined texture `rust`"
+    );
+    assert_eq!(
+        parse_err("[textures.a]\ntype = \"checker\"\neven = \"a\"\nodd = [0, 0, 0]\n"),
+        "line 1, column 1: texture `a` refers to itself"
+    );
+    assert!(
+        parse_err("[textures.a]\ntype = \"noise\"\npattern = \"cloud\"\n")
+            .starts_with("line 3, column 11: unknown noise pattern `cloud`")
+    );
+    assert_eq!(
+        parse_err(
+            "[materials.m]\ntype = \"isotropic\"\nalbedo = [1, 1, 1]\n[[objects]]\ntype = \"volume\"\nmin = [0, 0, 0]\n\
+             max = [1, 1, 1]\nfile = \"smoke.vol\"\nseed = 3\nmaterial = \"m\"\n"
+        ),
+        "line 4, column 1: volume cannot have both `file` and `seed`"
+    );
+    let volume = |key: &str| {
+        parse_err(&format!(
+            "[materials.m]\ntype = \"isotropic\"\nalbedo = [1, 1, 1]\n[[objects]]\n\
+             type = \"volume\"\nmin = [0, 0, 0]\nmax = [1, 1, 1]\nmaterial = \"m\"\n{}\n",
+            key
+        ))
+    };
+    assert_eq!(
+        volume("density = inf"),
+        "line 9, column 11: `density` must be finite"
+    );
+    assert_eq!(
+        volume("density = nan"),
+        "line 9, column 11: `density` must be finite"
+    );
+    assert_eq!(
+        volume("density = -1"),
+        "line 9, column 11: `density` must be positive"
+    );
+    assert_eq!(
+        volume("resolution = 0"),
+        "line 9, column 14: `resolution` must be positive"
+    );
+    assert_eq!(
+        volume("resolution = 10000000"),
+        "line 9, column 14: `resolution` must be at most 1024"
+    );
+    assert_eq!(
+        parse_err(
+            "[[objects]]\ntype = \"medium\"\ndensity = 0\n\
+             boundary = { type = \"sphere\", center = [0, 0, 0], radius = 1 }\n"
+        ),
+        "line 3, column 11: `density` must be positive"
+    );
+    assert_eq!(
+        parse_err("[image]\nsamples_per_pixel = 0\n"),
+        "line 2, column 21: `samples_per_pixel` must be positive"
+    );
+    assert_eq!(
+        parse_err("[image]\nheight = 0\n"),
+        "line 2, column 10: `height` must be positive"
+    );
+    assert_eq!(
+        parse_err(
+            "[materials.m]\ntype = \"dielectric\"\nref_idx = 1.5\ntransmittance = [1, 0.5, 1]\n\
+             transmittance_distance = 0\n"
+        ),
+        "line 5, column 26: `transmittance_distance` must be positive"
+    );
+    assert!(
+        parse_err("[textures.a]\ntype = \"image\"\nfile = \"missing.png\"\n")
+            .starts_with("line 3, column 8: cannot load texture: ")
+    );
+}
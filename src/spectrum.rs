@@ -0,0 +1,248 @@
+use std::f64::consts::PI;
+use std::str::FromStr;
+
+use crate::vector::Vec3;
+
+// Wavelengths in nanometers sampled by spectral rendering.
+pub const LAMBDA_MIN: f64 = 360.;
+pub const LAMBDA_MAX: f64 = 830.;
+
+pub fn sample_wavelength(u: f64) -> f64 {
+    // Samples wavelengths roughly proportional to the eye's sensitivity, which needs fewer
+    // samples than a uniform distribution for the same color noise (pbrt's visible wavelengths).
+    (538. - 138.888889 * (0.85691062 - 1.82750197 * u).atanh()).clamp(LAMBDA_MIN, LAMBDA_MAX)
+}
+
+pub fn wavelength_pdf(lambda: f64) -> f64 {
+    if !(LAMBDA_MIN..=LAMBDA_MAX).contains(&lambda) {
+        return 0.;
+    }
+    0.0039398042 / (0.0072 * (lambda - 538.)).cosh().powi(2)
+}
+
+// Multi-lobe Gaussian fit of the CIE 1931 color matching functions (Wyman, Sloan and Shirley
+// 2013), as (weight, mean, sigma below the mean, sigma above the mean).
+const CIE_X: [(f64, f64, f64, f64); 3] = [
+    (1.056, 599.8, 37.9, 31.0),
+    (0.362, 442.0, 16.0, 26.7),
+    (-0.065, 501.1, 20.4, 26.2),
+];
+const CIE_Y: [(f64, f64, f64, f64); 2] = [(0.821, 568.8, 46.9, 40.5), (0.286, 530.9, 16.3, 31.1)];
+const CIE_Z: [(f64, f64, f64, f64); 2] = [(1.217, 437.0, 11.8, 36.0), (0.681, 459.0, 26.0, 13.8)];
+
+fn lobes(lambda: f64, lobes: &[(f64, f64, f64, f64)]) -> f64 {
+    lobes
+        .iter()
+        .map(|&(weight, mean, below, above)| {
+            let sigma = if lambda < mean { below } else { above };
+            weight * (-0.5 * ((lambda - mean) / sigma).powi(2)).exp()
+        })
+        .sum()
+}
+
+fn lobes_integral(lobes: &[(f64, f64, f64, f64)]) -> f64 {
+    // Each lobe is two half Gaussians.
+    lobes
+        .iter()
+        .map(|&(weight, _, below, above)| weight * (PI / 2.).sqrt() * (below + above))
+        .sum()
+}
+
+pub fn cie_xyz(lambda: f64) -> Vec3 {
+    Vec3::from_xyz(
+        lobes(lambda, &CIE_X),
+        lobes(lambda, &CIE_Y),
+        lobes(lambda, &CIE_Z),
+    )
+}
+
+pub fn xyz_to_linear_srgb(xyz: Vec3) -> Vec3 {
+    let [x, y, z] = xyz.e;
+    Vec3::from_rgb(
+        3.2404542 * x - 1.5371385 * y - 0.4985314 * z,
+        -0.9692660 * x + 1.8760108 * y + 0.0415560 * z,
+        0.0556434 * x - 0.2040259 * y + 1.0572252 * z,
+    )
+}
+
+pub fn spectrum_to_rgb(value: f64, lambda: f64) -> Vec3 {
+    // Single-wavelength estimate of the linear sRGB color of a spectrum with `value` at
+    // `lambda`. Channels are balanced so a constant spectrum of 1 is white (1, 1, 1).
+    let pdf = wavelength_pdf(lambda);
+    if pdf <= 0. {
+        return Vec3::zeros();
+    }
+    let white = xyz_to_linear_srgb(Vec3::from_xyz(
+        lobes_integral(&CIE_X),
+        lobes_integral(&CIE_Y),
+        lobes_integral(&CIE_Z),
+    ));
+    let rgb = xyz_to_linear_srgb(cie_xyz(lambda)) * (value / pdf);
+    Vec3::new([0, 1, 2].map(|i| rgb.e[i] / white.e[i]))
+}
+
+// Spectra of Smits' RGB to spectrum conversion (1999), in 10 bins from 380 to 720 nm.
+const SMITS_WHITE: [f64; 10] = [
+    1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000,
+];
+const SMITS_CYAN: [f64; 10] = [
+    0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000,
+];
+const SMITS_MAGENTA: [f64; 10] = [
+    1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959,
+];
+const SMITS_YELLOW: [f64; 10] = [
+    0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840,
+];
+const SMITS_RED: [f64; 10] = [
+    0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149,
+];
+const SMITS_GREEN: [f64; 10] = [
+    0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025,
+];
+const SMITS_BLUE: [f64; 10] = [
+    1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496,
+];
+
+fn smits(spectrum: &[f64; 10], lambda: f64) -> f64 {
+    // Linear interpolation between the bin centers, constant beyond the outer ones.
+    let x = ((lambda - 380.) / 34. - 0.5).clamp(0., 9.);
+    let i = (x as usize).min(8);
+    let t = x - i as f64;
+    (1. - t) * spectrum[i] + t * spectrum[i + 1]
+}
+
+pub fn rgb_to_spectrum(rgb: Vec3, lambda: f64) -> f64 {
+    // Value at `lambda` of a smooth spectrum with the color `rgb`: the smallest component as
+    // white, the next as the secondary color and the rest as the primary color.
+    let [r, g, b] = rgb.e;
+    let s = |spectrum: &[f64; 10]| smits(spectrum, lambda);
+    if r <= g && r <= b {
+        r * s(&SMITS_WHITE)
+            + match g <= b {
+                true => (g - r) * s(&SMITS_CYAN) + (b - g) * s(&SMITS_BLUE),
+                false => (b - r) * s(&SMITS_CYAN) + (g - b) * s(&SMITS_GREEN),
+            }
+    } else if g <= r && g <= b {
+        g * s(&SMITS_WHITE)
+            + match r <= b {
+                true => (r - g) * s(&SMITS_MAGENTA) + (b - r) * s(&SMITS_BLUE),
+                false => (b - g) * s(&SMITS_MAGENTA) + (r - b) * s(&SMITS_RED),
+            }
+    } else {
+        b * s(&SMITS_WHITE)
+            + match r <= g {
+                true => (r - b) * s(&SMITS_YELLOW) + (g - r) * s(&SMITS_GREEN),
+                false => (g - b) * s(&SMITS_YELLOW) + (r - g) * s(&SMITS_RED),
+            }
+    }
+}
+
+// Wavelength-dependent index of refraction, with wavelengths in micrometers in the formulas.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dispersion {
+    Cauchy { a: f64, b: f64 },              // n = a + b / lambda^2
+    Sellmeier { b: [f64; 3], c: [f64; 3] }, // n^2 = 1 + sum b lambda^2 / (lambda^2 - c)
+}
+
+// Sellmeier coefficients of common optical materials.
+pub const GLASSES: &[(&str, Dispersion)] = &[
+    (
+        "bk7",
+        Dispersion::Sellmeier {
+            b: [1.03961212, 0.231792344, 1.01046945],
+            c: [0.00600069867, 0.0200179144, 103.560653],
+        },
+    ),
+    (
+        "fused_silica",
+        Dispersion::Sellmeier {
+            b: [0.6961663, 0.4079426, 0.8974794],
+            c: [0.00467914826, 0.0135120631, 97.9340025],
+        },
+    ),
+    (
+        "diamond",
+        Dispersion::Sellmeier {
+            b: [0.3306, 4.3356, 0.],
+            c: [0.030625, 0.011236, 0.],
+        },
+    ),
+];
+
+impl Dispersion {
+    pub fn ior(&self, lambda: f64) -> f64 {
+        let l2 = (lambda / 1000.).powi(2);
+        match self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => {
+                let n2 = 1. + (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum::<f64>();
+                n2.max(1.).sqrt()
+            }
+        }
+    }
+
+    pub fn d_line_ior(&self) -> f64 {
+        // Index at the helium d line, used when rendering without wavelengths.
+        self.ior(587.56)
+    }
+}
+
+impl FromStr for Dispersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match GLASSES.iter().find(|(name, _)| *name == s) {
+            Some((_, dispersion)) => Ok(*dispersion),
+            None => {
+                let names: Vec<&str> = GLASSES.iter().map(|(name, _)| *name).collect();
+                Err(format!(
+                    "unknown glass `{}`, expected one of: {}",
+                    s,
+                    names.join(", ")
+                ))
+            }
+        }
+    }
+}
+
+#[test]
+fn test_spectrum() {
+    // The wavelength density integrates to 1 and matches the samples.
+    let total: f64 = (360..830).map(|l| wavelength_pdf(l as f64 + 0.5)).sum();
+    assert!((total - 1.).abs() < 1e-3);
+    assert!((sample_wavelength(0.5) - 550.).abs() < 15.);
+    assert_eq!(wavelength_pdf(900.), 0.);
+
+    // Upsampled colors integrate back to themselves, white exactly and saturated primaries
+    // approximately.
+    let round_trip = |rgb: Vec3| {
+        let mut sum = Vec3::zeros();
+        for l in 360..830 {
+            let lambda = l as f64 + 0.5;
+            sum += spectrum_to_rgb(rgb_to_spectrum(rgb, lambda), lambda) * wavelength_pdf(lambda);
+        }
+        sum
+    };
+    assert!((round_trip(Vec3::ones()) - Vec3::ones()).length() < 0.01);
+    let gray = Vec3::from_rgb(0.5, 0.5, 0.5);
+    assert!((round_trip(gray) - gray).length() < 0.01);
+    for primary in [
+        Vec3::from_rgb(1., 0., 0.),
+        Vec3::from_rgb(0., 1., 0.),
+        Vec3::from_rgb(0., 0., 1.),
+    ] {
+        assert!((round_trip(primary) - primary).length() < 0.2);
+    }
+    assert_eq!(rgb_to_spectrum(Vec3::zeros(), 500.), 0.);
+
+    // Normal dispersion: blue light refracts more than red.
+    let bk7: Dispersion = "bk7".parse().unwrap();
+    assert!((bk7.d_line_ior() - 1.5168).abs() < 1e-3);
+    assert!(bk7.ior(450.) > bk7.ior(650.));
+    let diamond: Dispersion = "diamond".parse().unwrap();
+    assert!((diamond.ior(589.3) - 2.417).abs() < 5e-3);
+    let cauchy = Dispersion::Cauchy { a: 1.5, b: 0.004 };
+    assert!((cauchy.ior(500.) - 1.516).abs() < 1e-12);
+    assert!("glass".parse::<Dispersion>().is_err());
+}
@@ -1,7 +1,12 @@
+use std::f64::consts::PI;
+
+use crate::aabb::Aabb;
 use crate::hittable::HitRecord;
-use crate::hittable::Hittable;
+use crate::hittable::{Hittable, LightSample};
 use crate::interval::Interval;
 use crate::material::Material;
+use crate::onb::Onb;
+use crate::random::random_f64;
 use crate::ray::Ray;
 use crate::vector::Vec3;
 
@@ -10,17 +15,52 @@ pub struct Sphere {
     center: Vec3,
     radius: f64,
     mat: Material,
+    bbox: Aabb,
 }
 
 impl Sphere {
     pub fn new(center: Vec3, radius: f64, mat: Material) -> Self {
         let radius = radius.max(0.0);
+        let rvec = Vec3::from_xyz(radius, radius, radius);
+        let bbox = Aabb::from_points(center - rvec, center + rvec);
         Self {
             center,
             radius,
             mat,
+            bbox,
+        }
+    }
+
+    fn cos_theta_max(&self, origin: Vec3) -> Option<f64> {
+        // Half angle of the cone subtended by the sphere as seen from `origin`.
+        let distance_squared = (self.center - origin).length_squared();
+        let ratio = self.radius * self.radius / distance_squared;
+        match ratio < 1. {
+            true => Some((1. - ratio).sqrt()),
+            false => None,
         }
     }
+
+    fn uv(p: Vec3) -> (f64, f64) {
+        // Spherical coordinates of a point `p` on the unit sphere. u is the angle around the
+        // y axis starting from -x, v the angle from -y to +y, both mapped to [0, 1].
+        let theta = (-p.y()).clamp(-1., 1.).acos();
+        let phi = (-p.z()).atan2(p.x()) + PI;
+        (phi / (2. * PI), theta / PI)
+    }
+
+    fn tangents(&self, p: Vec3) -> (Vec3, Vec3) {
+        // Derivatives of the hit point `p` along the spherical coordinates of `uv`. The
+        // bitangent is undefined at the poles and left zero there.
+        let d = p - self.center;
+        let dpdu = 2. * PI * Vec3::from_xyz(d.z(), 0., -d.x());
+        let rho = d.x().hypot(d.z());
+        let dpdv = match rho > 1e-12 {
+            true => PI * Vec3::from_xyz(-d.y() * d.x() / rho, rho, -d.y() * d.z() / rho),
+            false => Vec3::zeros(),
+        };
+        (dpdu, dpdv)
+    }
 }
 
 impl Hittable for Sphere {
@@ -45,7 +85,96 @@ impl Hittable for Sphere {
         rec.p = r.at(t);
         let outward_normal = (rec.p - self.center) / self.radius;
         rec.set_face_normal(r, outward_normal);
+        (rec.u, rec.v) = Sphere::uv(outward_normal);
+        (rec.dpdu, rec.dpdv) = self.tangents(rec.p);
         rec.mat = self.mat.clone();
         true
     }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn is_light(&self) -> bool {
+        self.mat.is_emissive()
+    }
+
+    fn sample(&self, origin: Vec3) -> Option<LightSample> {
+        // Uniformly sample the cone of directions subtended by the sphere, which is only defined
+        // from outside of it.
+        let cos_theta_max = self.cos_theta_max(origin)?;
+        let z = 1. + random_f64() * (cos_theta_max - 1.);
+        let phi = 2. * PI * random_f64();
+        let sin_theta = (1. - z * z).max(0.).sqrt();
+        let uvw = Onb::new(self.center - origin);
+        let direction = uvw.transform(Vec3::from_xyz(
+            phi.cos() * sin_theta,
+            phi.sin() * sin_theta,
+            z,
+        ));
+
+        let mut rec = HitRecord::new();
+        match self.hit(
+            &Ray::new(origin, direction),
+            Interval::new(0., f64::INFINITY),
+            &mut rec,
+        ) {
+            true => Some(LightSample {
+                p: rec.p,
+                pdf: 1. / (2. * PI * (1. - cos_theta_max)),
+            }),
+            false => None,
+        }
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3, r_t: Interval) -> f64 {
+        let mut rec = HitRecord::new();
+        match self.cos_theta_max(origin) {
+            Some(cos_theta_max) if self.hit(&Ray::new(origin, direction), r_t, &mut rec) => {
+                1. / (2. * PI * (1. - cos_theta_max))
+            }
+            _ => 0.,
+        }
+    }
+}
+
+#[test]
+fn test_sphere_sampling() {
+    let light = Sphere::new(
+        Vec3::from_xyz(0., 0., -4.),
+        2.,
+        Material::diffuse_light(Vec3::ones()),
+    );
+    assert!(light.is_light());
+    let origin = Vec3::zeros();
+    // The cone has a half angle of 30 degrees.
+    let expected_pdf = 1. / (2. * PI * (1. - 0.75f64.sqrt()));
+    let r_t = Interval::new(1e-3, f64::INFINITY);
+    for _ in 0..16 {
+        let sample = light.sample(origin).unwrap();
+        assert!(((sample.p - light.center).length() - 2.).abs() < 1e-9);
+        assert!((sample.pdf - expected_pdf).abs() < 1e-9);
+        assert!((light.pdf_value(origin, sample.p - origin, r_t) - sample.pdf).abs() < 1e-9);
+    }
+    assert_eq!(light.pdf_value(origin, Vec3::from_xyz(0., 1., 0.), r_t), 0.);
+    assert!(light.sample(light.center).is_none());
+
+    let uv = |x, y, z| Sphere::uv(Vec3::from_xyz(x, y, z));
+    assert_eq!(uv(-1., 0., 0.), (0., 0.5));
+    assert_eq!(uv(0., 1., 0.).1, 1.);
+    assert_eq!(uv(0., -1., 0.).1, 0.);
+    assert_eq!(uv(1., 0., 0.), (0.5, 0.5));
+    assert_eq!(uv(0., 0., 1.), (0.25, 0.5));
+
+    // The tangents agree with finite differences of the uv mapping, moving along the unit
+    // direction `d` moves the hit point twice as far.
+    let sphere = Sphere::new(Vec3::from_xyz(1., 2., 3.), 2., Material::dielectric(1.5));
+    let d = Vec3::from_xyz(0.3, -0.5, 0.8).unit_vector();
+    let (dpdu, dpdv) = sphere.tangents(sphere.center + 2. * d);
+    let (u0, v0) = Sphere::uv(d);
+    let (u1, _) = Sphere::uv((d + 1e-6 * dpdu).unit_vector());
+    let (_, v1) = Sphere::uv((d + 1e-6 * dpdv).unit_vector());
+    assert!(((u1 - u0) / 1e-6 / 2. - 1.).abs() < 1e-4);
+    assert!(((v1 - v0) / 1e-6 / 2. - 1.).abs() < 1e-4);
+    assert!(dpdu.dot(d).abs() < 1e-12 && dpdv.dot(d).abs() < 1e-12);
 }
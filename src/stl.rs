@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::load_error::{parse_token, LoadError};
+use crate::material::Material;
+use crate::mesh::TriangleMesh;
+use crate::vector::Vec3;
+
+// Binary STL: 80-byte header, u32 triangle count, then 50 bytes per triangle.
+const HEADER_SIZE: usize = 84;
+const FACET_SIZE: usize = 50;
+
+#[derive(Debug, Clone, Default)]
+pub struct StlModel {
+    pub name: String,
+    pub positions: Vec<Vec3>, // Vertices shared between facets are merged
+    pub faces: Vec<[usize; 3]>,
+}
+
+impl StlModel {
+    pub fn load(path: &Path) -> Result<Self, LoadError> {
+        let data = fs::read(path).map_err(|err| LoadError::from(err).in_file(path))?;
+        StlModel::parse(&data).map_err(|err| err.in_file(path))
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self, LoadError> {
+        // Binary files may also start with "solid", so the size is checked first.
+        let is_binary = data.len() >= HEADER_SIZE && {
+            let count = u32::from_le_bytes(data[80..84].try_into().unwrap()) as usize;
+            HEADER_SIZE + count * FACET_SIZE == data.len()
+        };
+        match is_binary || !data.trim_ascii_start().starts_with(b"solid") {
+            true => StlModel::parse_binary(data),
+            false => StlModel::parse_ascii(data),
+        }
+    }
+
+    fn parse_binary(data: &[u8]) -> Result<Self, LoadError> {
+        if data.len() < HEADER_SIZE {
+            return Err(LoadError::at_byte(data.len(), "truncated header"));
+        }
+        let count = u32::from_le_bytes(data[80..84].try_into().unwrap()) as usize;
+        let mut model = StlModel {
+            name: String::from_utf8_lossy(&data[..80])
+                .trim_end_matches('\0')
+                .trim()
+                .to_string(),
+            ..Default::default()
+        };
+        let mut vertices = HashMap::new();
+
+        for i in 0..count {
+            let offset = HEADER_SIZE + i * FACET_SIZE;
+            if offset + FACET_SIZE > data.len() {
+                return Err(LoadError::at_byte(
+                    offset,
+                    format!("truncated facet {} of {}", i + 1, count),
+                ));
+            }
+            // Skip the facet normal, the winding order defines the geometric normal.
+            let read = |k: usize| {
+                let start = offset + 12 + 4 * k;
+                f32::from_le_bytes(data[start..start + 4].try_into().unwrap()) as f64
+            };
+            let mut face = [Vec3::zeros(); 3];
+            for (v, vertex) in face.iter_mut().enumerate() {
+                *vertex = Vec3::from_xyz(read(3 * v), read(3 * v + 1), read(3 * v + 2));
+            }
+            model.add_facet(face, &mut vertices);
+        }
+        Ok(model)
+    }
+
+    fn parse_ascii(data: &[u8]) -> Result<Self, LoadError> {
+        let text = std::str::from_utf8(data)
+            .map_err(|err| LoadError::at_byte(err.valid_up_to(), "invalid UTF-8 in ASCII STL"))?;
+        let mut model = StlModel::default();
+        let mut vertices = HashMap::new();
+        let mut face: Vec<Vec3> = Vec::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let line_no = i + 1;
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            match tokens[..] {
+                [] => continue,
+                ["solid", ref name @ ..] => model.name = name.join(" "),
+                ["facet", "normal", _, _, _] | ["outer", "loop"] => continue,
+                ["vertex", x, y, z] => {
+                    if face.len() == 3 {
+                        return Err(LoadError::at_line(
+                            line_no,
+                            "facet has more than 3 vertices",
+                        ));
+                    }
+                    face.push(Vec3::from_xyz(
+                        parse_token(x, line_no)?,
+                        parse_token(y, line_no)?,
+                        parse_token(z, line_no)?,
+                    ));
+                }
+                ["endloop"] => {
+                    if face.len() != 3 {
+                        return Err(LoadError::at_line(
+                            line_no,
+                            format!("facet needs 3 vertices, found {}", face.len()),
+                        ));
+                    }
+                    model.add_facet([face[0], face[1], face[2]], &mut vertices);
+                    face.clear();
+                }
+                ["endfacet"] => continue,
+                ["endsolid", ..] => break,
+                _ => {
+                    return Err(LoadError::at_line(
+                        line_no,
+                        format!("malformed line `{}`", line.trim()),
+                    ))
+                }
+            }
+        }
+        Ok(model)
+    }
+
+    fn add_facet(&mut self, face: [Vec3; 3], vertices: &mut HashMap<[u64; 3], usize>) {
+        let mut tri = [0; 3];
+        for (k, p) in face.iter().enumerate() {
+            let key = p.e.map(f64::to_bits);
+            tri[k] = *vertices.entry(key).or_insert_with(|| {
+                self.positions.push(*p);
+                self.positions.len() - 1
+            });
+        }
+        self.faces.push(tri);
+    }
+
+    pub fn to_mesh(&self, mat: Material) -> TriangleMesh {
+        TriangleMesh::new(self.positions.clone(), self.faces.clone(), mat)
+    }
+}
+
+#[test]
+fn test_parse_stl() {
+    let ascii = "\
+solid quad
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+      vertex 1 1 0
+    endloop
+  endfacet
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 1 1 0
+      vertex 0 1 0
+    endloop
+  endfacet
+endsolid quad
+";
+    let model = StlModel::parse(ascii.as_bytes()).unwrap();
+    assert_eq!(model.name, "quad");
+    assert_eq!(model.positions.len(), 4);
+    assert_eq!(model.faces, vec![[0, 1, 2], [0, 2, 3]]);
+
+    // The same quad in binary form, with a header that also starts with "solid".
+    let mut data = b"solid binary".to_vec();
+    data.resize(80, 0);
+    data.extend_from_slice(&2u32.to_le_bytes());
+    for face in model.faces.iter() {
+        data.extend_from_slice(&[0; 12]);
+        for &i in face {
+            for c in model.positions[i].e {
+                data.extend_from_slice(&(c as f32).to_le_bytes());
+            }
+        }
+        data.extend_from_slice(&[0; 2]);
+    }
+    let binary = StlModel::parse(&data).unwrap();
+    assert_eq!(binary.name, "solid binary");
+    assert_eq!(binary.positions, model.positions);
+    assert_eq!(binary.faces, model.faces);
+
+    let err = StlModel::parse_binary(&data[..120]).unwrap_err();
+    assert_eq!(err.to_string(), "byte 84: truncated facet 1 of 2");
+    let err = StlModel::parse(ascii.replace("vertex 1 1 0", "vertex 1 1").as_bytes()).unwrap_err();
+    assert_eq!(err.to_string(), "line 6: malformed line `vertex 1 1`");
+}
@@ -0,0 +1,371 @@
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::image::Image;
+use crate::load_error::LoadError;
+use crate::perlin::Perlin;
+use crate::vector::Vec3;
+
+// How texel coordinates outside of the image are folded back into it.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum WrapMode {
+    #[default]
+    Repeat, // Tile the image
+    Clamp,  // Stretch the border texels
+    Mirror, // Tile the image, flipping every other copy
+}
+
+impl WrapMode {
+    fn wrap(&self, i: i64, n: usize) -> usize {
+        let n = n as i64;
+        let i = match self {
+            WrapMode::Repeat => i.rem_euclid(n),
+            WrapMode::Clamp => i.clamp(0, n - 1),
+            WrapMode::Mirror => {
+                let m = i.rem_euclid(2 * n);
+                match m < n {
+                    true => m,
+                    false => 2 * n - 1 - m,
+                }
+            }
+        };
+        i as usize
+    }
+}
+
+impl FromStr for WrapMode {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "repeat" => Ok(WrapMode::Repeat),
+            "clamp" => Ok(WrapMode::Clamp),
+            "mirror" => Ok(WrapMode::Mirror),
+            _ => Err(format!(
+                "unknown wrap mode `{}`, expected one of: repeat, clamp, mirror",
+                name
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Filter {
+    Nearest,
+    #[default]
+    Bilinear,
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "nearest" => Ok(Filter::Nearest),
+            "bilinear" => Ok(Filter::Bilinear),
+            _ => Err(format!(
+                "unknown filter `{}`, expected one of: nearest, bilinear",
+                name
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ImageTexture {
+    image: Arc<Image>,
+    wrap: WrapMode,
+    filter: Filter,
+}
+
+impl ImageTexture {
+    pub fn new(image: Arc<Image>) -> Self {
+        Self {
+            image,
+            wrap: WrapMode::default(),
+            filter: Filter::default(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, LoadError> {
+        // Color textures are stored sRGB encoded.
+        Ok(ImageTexture::new(Arc::new(Image::load(path, true)?)))
+    }
+
+    pub fn load_linear(path: &Path) -> Result<Self, LoadError> {
+        // Data textures such as normal maps are stored without the sRGB encoding.
+        Ok(ImageTexture::new(Arc::new(Image::load(path, false)?)))
+    }
+
+    pub fn with_wrap(mut self, wrap: WrapMode) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Vec3 {
+        let (width, height) = (self.image.width(), self.image.height());
+        self.image
+            .get(self.wrap.wrap(x, width), self.wrap.wrap(y, height))
+    }
+
+    pub fn value(&self, u: f64, v: f64) -> Vec3 {
+        if self.image.width() == 0 || self.image.height() == 0 {
+            return Vec3::zeros();
+        }
+        // The image is stored from the top row, v = 0 is the bottom.
+        let x = u * self.image.width() as f64;
+        let y = (1. - v) * self.image.height() as f64;
+        match self.filter {
+            Filter::Nearest => self.texel(x.floor() as i64, y.floor() as i64),
+            Filter::Bilinear => {
+                // Texel centers sit at half-integer coordinates.
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                let top = (1. - fx) * self.texel(x0, y0) + fx * self.texel(x0 + 1, y0);
+                let bottom = (1. - fx) * self.texel(x0, y0 + 1) + fx * self.texel(x0 + 1, y0 + 1);
+                (1. - fy) * top + fy * bottom
+            }
+        }
+    }
+}
+
+// Alternates between two textures on a 3D grid of cubes with edge length `scale`.
+#[derive(Debug, Clone)]
+pub struct Checker {
+    inv_scale: f64,
+    even: Texture,
+    odd: Texture,
+}
+
+impl Checker {
+    pub fn new(scale: f64, even: Texture, odd: Texture) -> Self {
+        Self {
+            inv_scale: 1. / scale,
+            even,
+            odd,
+        }
+    }
+
+    pub fn value(&self, u: f64, v: f64, p: Vec3) -> Vec3 {
+        let cell: i64 =
+            p.e.iter()
+                .map(|c| (self.inv_scale * c).floor() as i64)
+                .sum();
+        match cell.rem_euclid(2) == 0 {
+            true => self.even.value(u, v, p),
+            false => self.odd.value(u, v, p),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum NoisePattern {
+    #[default]
+    Noise, // Plain Perlin noise
+    Turbulence, // Sum of absolute noise octaves
+    Fbm,        // Fractal Brownian motion
+    Marble,     // Sine stripes along z distorted by turbulence
+    Wood,       // Rings around the y axis distorted by turbulence
+}
+
+pub const NOISE_PATTERNS: &[(&str, NoisePattern)] = &[
+    ("noise", NoisePattern::Noise),
+    ("turbulence", NoisePattern::Turbulence),
+    ("fbm", NoisePattern::Fbm),
+    ("marble", NoisePattern::Marble),
+    ("wood", NoisePattern::Wood),
+];
+
+impl FromStr for NoisePattern {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        NOISE_PATTERNS
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, pattern)| *pattern)
+            .ok_or_else(|| {
+                let names: Vec<&str> = NOISE_PATTERNS.iter().map(|(n, _)| *n).collect();
+                format!(
+                    "unknown noise pattern `{}`, expected one of: {}",
+                    name,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+// Procedural pattern blending between two colors, deterministic for a given seed.
+#[derive(Debug, Clone)]
+pub struct NoiseTexture {
+    perlin: Perlin,
+    pattern: NoisePattern,
+    scale: f64,     // Frequency of the pattern in world space
+    octaves: usize, // Octaves of turbulence and fBm
+    low: Vec3,      // Color where the pattern is 0
+    high: Vec3,     // Color where the pattern is 1
+}
+
+impl NoiseTexture {
+    pub fn new(pattern: NoisePattern, seed: u64) -> Self {
+        Self {
+            perlin: Perlin::new(seed),
+            pattern,
+            scale: 1.,
+            octaves: 7,
+            low: Vec3::zeros(),
+            high: Vec3::ones(),
+        }
+    }
+
+    pub fn with_scale(mut self, scale: f64) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_octaves(mut self, octaves: usize) -> Self {
+        self.octaves = octaves;
+        self
+    }
+
+    pub fn with_colors(mut self, low: Vec3, high: Vec3) -> Self {
+        self.low = low;
+        self.high = high;
+        self
+    }
+
+    pub fn pattern(&self, p: Vec3) -> f64 {
+        // Pattern value in [0, 1] at the point `p`.
+        let perlin = &self.perlin;
+        let t = match self.pattern {
+            NoisePattern::Noise => 0.5 * (1. + perlin.noise(self.scale * p)),
+            NoisePattern::Turbulence => perlin.turbulence(self.scale * p, self.octaves),
+            NoisePattern::Fbm => 0.5 * (1. + perlin.fbm(self.scale * p, self.octaves)),
+            NoisePattern::Marble => {
+                let phase = self.scale * p.z() + 10. * perlin.turbulence(p, self.octaves);
+                0.5 * (1. + phase.sin())
+            }
+            NoisePattern::Wood => {
+                let radius = self.scale * p.x().hypot(p.z());
+                (radius + perlin.turbulence(p, self.octaves)).fract()
+            }
+        };
+        t.clamp(0., 1.)
+    }
+
+    pub fn value(&self, p: Vec3) -> Vec3 {
+        let t = self.pattern(p);
+        (1. - t) * self.low + t * self.high
+    }
+}
+
+// Spatially varying color, evaluated at the surface coordinates (u, v) and hit point p.
+#[derive(Debug, Clone)]
+pub enum Texture {
+    Solid(Vec3),
+    Checker(Arc<Checker>),
+    Image(ImageTexture),
+    Noise(Arc<NoiseTexture>),
+}
+
+impl Texture {
+    pub fn solid(color: Vec3) -> Self {
+        Texture::Solid(color)
+    }
+
+    pub fn checker(scale: f64, even: impl Into<Texture>, odd: impl Into<Texture>) -> Self {
+        Texture::Checker(Arc::new(Checker::new(scale, even.into(), odd.into())))
+    }
+
+    pub fn image(texture: ImageTexture) -> Self {
+        Texture::Image(texture)
+    }
+
+    pub fn noise(texture: NoiseTexture) -> Self {
+        Texture::Noise(Arc::new(texture))
+    }
+
+    pub fn value(&self, u: f64, v: f64, p: Vec3) -> Vec3 {
+        match self {
+            Texture::Solid(color) => *color,
+            Texture::Checker(checker) => checker.value(u, v, p),
+            Texture::Image(image) => image.value(u, v),
+            Texture::Noise(noise) => noise.value(p),
+        }
+    }
+}
+
+impl From<Vec3> for Texture {
+    fn from(color: Vec3) -> Self {
+        Texture::Solid(color)
+    }
+}
+
+#[test]
+fn test_texture() {
+    let solid = Texture::from(Vec3::from_rgb(0.1, 0.2, 0.3));
+    assert_eq!(
+        solid.value(0.5, 0.5, Vec3::zeros()),
+        Vec3::from_rgb(0.1, 0.2, 0.3)
+    );
+
+    let checker = Texture::checker(0.5, Vec3::zeros(), Vec3::ones());
+    assert_eq!(
+        checker.value(0., 0., Vec3::from_xyz(0.1, 0.1, 0.1)),
+        Vec3::zeros()
+    );
+    assert_eq!(
+        checker.value(0., 0., Vec3::from_xyz(0.6, 0.1, 0.1)),
+        Vec3::ones()
+    );
+    assert_eq!(
+        checker.value(0., 0., Vec3::from_xyz(-0.1, 0.1, 0.1)),
+        Vec3::ones()
+    );
+
+    // A 2x2 image: black and white on the top row, red and green on the bottom one.
+    let mut image = Image::new(2, 2);
+    image.set(1, 0, Vec3::ones());
+    image.set(0, 1, Vec3::from_rgb(1., 0., 0.));
+    image.set(1, 1, Vec3::from_rgb(0., 1., 0.));
+    let texture = ImageTexture::new(Arc::new(image));
+    let nearest = texture.clone().with_filter(Filter::Nearest);
+    assert_eq!(nearest.value(0.25, 0.25), Vec3::from_rgb(1., 0., 0.));
+    assert_eq!(nearest.value(0.75, 0.75), Vec3::ones());
+    assert_eq!(nearest.value(1.25, -0.75), Vec3::from_rgb(1., 0., 0.));
+
+    // Bilinear filtering blends the four texels at the center.
+    let center = texture.value(0.5, 0.5);
+    assert!((center - Vec3::from_rgb(0.5, 0.5, 0.25)).near_zero());
+    // At the left border, repeating blends with the right column while clamping does not.
+    let repeat = texture.value(0., 0.25);
+    assert!((repeat - Vec3::from_rgb(0.5, 0.5, 0.)).near_zero());
+    let clamp = texture.clone().with_wrap(WrapMode::Clamp).value(0., 0.25);
+    assert_eq!(clamp, Vec3::from_rgb(1., 0., 0.));
+
+    assert_eq!(WrapMode::Mirror.wrap(2, 2), 1);
+    assert_eq!(WrapMode::Mirror.wrap(-1, 2), 0);
+    assert_eq!("clamp".parse(), Ok(WrapMode::Clamp));
+    assert!("border".parse::<WrapMode>().is_err());
+
+    let p = Vec3::from_xyz(0.3, 1.7, -2.2);
+    for (name, pattern) in NOISE_PATTERNS {
+        assert_eq!(name.parse(), Ok(*pattern));
+        let noise = NoiseTexture::new(*pattern, 3)
+            .with_scale(4.)
+            .with_colors(Vec3::zeros(), Vec3::ones() * 2.);
+        assert!((0. ..=1.).contains(&noise.pattern(p)));
+        assert_eq!(noise.value(p), Vec3::ones() * (2. * noise.pattern(p)));
+        // Textures built from the same seed agree.
+        let same = Texture::noise(NoiseTexture::new(*pattern, 3).with_scale(4.));
+        assert_eq!(same.value(0., 0., p), Vec3::ones() * noise.pattern(p));
+    }
+}
@@ -0,0 +1,150 @@
+use std::str::FromStr;
+
+use crate::vector::Vec3;
+
+// Operators mapping linear scene radiance to linear display values in [0, 1].
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ToneMap {
+    #[default]
+    Clamp, // Values above 1 are clipped
+    Reinhard,         // L / (1 + L) on luminance
+    ReinhardExtended, // Reinhard that maps the white point to 1
+    Aces,             // Narkowicz fit of the ACES filmic curve
+    Hable,            // Uncharted 2 filmic curve
+    Agx,              // AgX-style log encoding and sigmoid
+}
+
+pub const TONE_MAPS: &[(&str, ToneMap)] = &[
+    ("clamp", ToneMap::Clamp),
+    ("reinhard", ToneMap::Reinhard),
+    ("reinhard-extended", ToneMap::ReinhardExtended),
+    ("aces", ToneMap::Aces),
+    ("hable", ToneMap::Hable),
+    ("agx", ToneMap::Agx),
+];
+
+impl FromStr for ToneMap {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        TONE_MAPS
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, tone_map)| *tone_map)
+            .ok_or_else(|| {
+                let names: Vec<&str> = TONE_MAPS.iter().map(|(n, _)| *n).collect();
+                format!(
+                    "unknown tone mapper `{}`, expected one of: {}",
+                    name,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+impl ToneMap {
+    pub fn apply(&self, color: Vec3, white_point: f64) -> Vec3 {
+        let color = Vec3::new(color.e.map(|c| c.max(0.)));
+        match self {
+            ToneMap::Clamp => color,
+            ToneMap::Reinhard => scale_luminance(color, |l| l / (1. + l)),
+            ToneMap::ReinhardExtended => scale_luminance(color, |l| {
+                l * (1. + l / (white_point * white_point)) / (1. + l)
+            }),
+            ToneMap::Aces => Vec3::new(color.e.map(aces)),
+            ToneMap::Hable => {
+                // Exposure bias of the original curve, normalized by the linear white point 11.2.
+                let white_scale = 1. / hable(11.2);
+                Vec3::new(color.e.map(|c| (hable(2. * c) * white_scale).min(1.)))
+            }
+            ToneMap::Agx => agx(color),
+        }
+    }
+}
+
+pub fn luminance(color: Vec3) -> f64 {
+    // Rec. 709 luminance.
+    0.2126 * color.x() + 0.7152 * color.y() + 0.0722 * color.z()
+}
+
+fn scale_luminance(color: Vec3, f: impl Fn(f64) -> f64) -> Vec3 {
+    let l = luminance(color);
+    match l > 0. {
+        true => color * (f(l) / l),
+        false => Vec3::zeros(),
+    }
+}
+
+fn aces(x: f64) -> f64 {
+    let (a, b, c, d, e) = (2.51, 0.03, 2.43, 0.59, 0.14);
+    ((x * (a * x + b)) / (x * (c * x + d) + e)).clamp(0., 1.)
+}
+
+fn hable(x: f64) -> f64 {
+    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+    ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+}
+
+fn mat_mul(m: [[f64; 3]; 3], v: Vec3) -> Vec3 {
+    Vec3::new(m.map(|row| Vec3::new(row).dot(v)))
+}
+
+fn agx(color: Vec3) -> Vec3 {
+    // Inset into the AgX working space.
+    const INSET: [[f64; 3]; 3] = [
+        [0.842479062253094, 0.0784335999999992, 0.0792237451477643],
+        [0.0423282422610123, 0.878468636469772, 0.0791661274605434],
+        [0.0423756549057051, 0.0784336, 0.879142973793104],
+    ];
+    const OUTSET: [[f64; 3]; 3] = [
+        [1.19687900512017, -0.0980208811401368, -0.0990297440797205],
+        [-0.0528968517574562, 1.15190312990417, -0.0989611768448433],
+        [-0.0529716355144438, -0.0980434501171241, 1.15107367264116],
+    ];
+    let (min_ev, max_ev) = (-12.47393, 4.026069);
+
+    let color = mat_mul(INSET, color);
+    let color = Vec3::new(color.e.map(|c| {
+        // Log2 encoding over a fixed range of stops, then a polynomial sigmoid.
+        let x = (c.max(1e-10).log2().clamp(min_ev, max_ev) - min_ev) / (max_ev - min_ev);
+        let x2 = x * x;
+        let x4 = x2 * x2;
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x
+            - 0.00232
+    }));
+    // The sigmoid output is display encoded, linearize it for the sRGB transfer function.
+    let color = mat_mul(OUTSET, color);
+    Vec3::new(color.e.map(|c| c.clamp(0., 1.).powf(2.2)))
+}
+
+#[test]
+fn test_tone_map() {
+    let white_point = 4.;
+    for (_, tone_map) in TONE_MAPS {
+        // Every operator maps black to black, bright values into the display range unless
+        // they are clipped or above the white point.
+        let black = tone_map.apply(Vec3::zeros(), white_point);
+        assert!(black.near_zero());
+        let bright = tone_map.apply(Vec3::from_rgb(100., 50., 0.), white_point);
+        if ![ToneMap::Clamp, ToneMap::ReinhardExtended].contains(tone_map) {
+            assert!((0. ..=1.001).contains(&luminance(bright)));
+        }
+        // Brighter input never gets darker.
+        let dim = tone_map.apply(Vec3::from_rgb(0.2, 0.2, 0.2), white_point);
+        let mid = tone_map.apply(Vec3::from_rgb(0.8, 0.8, 0.8), white_point);
+        assert!(mid.x() > dim.x());
+    }
+
+    let reinhard = ToneMap::Reinhard.apply(Vec3::ones(), white_point);
+    assert!((reinhard - Vec3::ones() * 0.5).near_zero());
+    let extended = ToneMap::ReinhardExtended.apply(Vec3::ones() * white_point, white_point);
+    assert!((extended - Vec3::ones()).near_zero());
+    let hable = ToneMap::Hable.apply(Vec3::ones() * 5.6, white_point);
+    assert!((hable - Vec3::ones()).near_zero());
+
+    assert_eq!("aces".parse(), Ok(ToneMap::Aces));
+    assert_eq!(
+        "filmic".parse::<ToneMap>(),
+        Err("unknown tone mapper `filmic`, expected one of: clamp, reinhard, reinhard-extended, aces, hable, agx".to_string())
+    );
+}
@@ -0,0 +1,211 @@
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable, LightSample};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::quad::area_to_solid_angle;
+use crate::random::random_f64;
+use crate::ray::Ray;
+use crate::vector::Vec3;
+
+pub(crate) fn intersect(r: &Ray, r_t: Interval, p: [Vec3; 3]) -> Option<(f64, f64, f64)> {
+    // Möller–Trumbore ray/triangle intersection.
+    // Returns the ray parameter t and the barycentric coordinates (b1, b2) of p[1] and p[2].
+    const EPSILON: f64 = 1e-12;
+
+    let edge1 = p[1] - p[0];
+    let edge2 = p[2] - p[0];
+    let pvec = r.direction().cross(edge2);
+    let det = edge1.dot(pvec);
+    // The ray is parallel to the triangle plane (or the triangle is degenerate).
+    if det.abs() < EPSILON {
+        return None;
+    }
+    let inv_det = 1. / det;
+
+    let tvec = r.origin() - p[0];
+    let b1 = tvec.dot(pvec) * inv_det;
+    if !(0. ..=1.).contains(&b1) {
+        return None;
+    }
+
+    let qvec = tvec.cross(edge1);
+    let b2 = r.direction().dot(qvec) * inv_det;
+    if b2 < 0. || b1 + b2 > 1. {
+        return None;
+    }
+
+    let t = edge2.dot(qvec) * inv_det;
+    match r_t.contains(t) {
+        true => Some((t, b1, b2)),
+        false => None,
+    }
+}
+
+pub(crate) fn set_hit_record(
+    r: &Ray,
+    hit: (f64, f64, f64),
+    p: [Vec3; 3],
+    normals: Option<[Vec3; 3]>,
+    uvs: Option<[(f64, f64); 3]>,
+    rec: &mut HitRecord,
+) {
+    let (t, b1, b2) = hit;
+    let b0 = 1. - b1 - b2;
+    rec.t = t;
+    rec.p = r.at(t);
+    rec.barycentric = Vec3::from_xyz(b0, b1, b2);
+    // Without vertex coordinates the triangle maps onto the lower left half of the unit square.
+    let uv = uvs.unwrap_or([(0., 0.), (1., 0.), (0., 1.)]);
+    rec.u = b0 * uv[0].0 + b1 * uv[1].0 + b2 * uv[2].0;
+    rec.v = b0 * uv[0].1 + b1 * uv[1].1 + b2 * uv[2].1;
+
+    // Solve the edges for the tangents, dp = dpdu * du + dpdv * dv.
+    let (dp1, dp2) = (p[1] - p[0], p[2] - p[0]);
+    let (du1, dv1) = (uv[1].0 - uv[0].0, uv[1].1 - uv[0].1);
+    let (du2, dv2) = (uv[2].0 - uv[0].0, uv[2].1 - uv[0].1);
+    let det = du1 * dv2 - dv1 * du2;
+    (rec.dpdu, rec.dpdv) = match det.abs() > 1e-12 {
+        true => ((dv2 * dp1 - dv1 * dp2) / det, (du1 * dp2 - du2 * dp1) / det),
+        false => (Vec3::zeros(), Vec3::zeros()),
+    };
+
+    // The geometric normal decides which side was hit, the interpolated vertex normal (if any)
+    // is only used for shading.
+    let geometric_normal = (p[1] - p[0]).cross(p[2] - p[0]).unit_vector();
+    rec.set_face_normal(r, geometric_normal);
+    if let Some(n) = normals {
+        let shading_normal = (b0 * n[0] + b1 * n[1] + b2 * n[2]).unit_vector();
+        rec.normal = match rec.front_face {
+            true => shading_normal,
+            false => -shading_normal,
+        };
+    }
+}
+
+fn normal_and_area(p: [Vec3; 3]) -> (Vec3, f64) {
+    let n = (p[1] - p[0]).cross(p[2] - p[0]);
+    (n.unit_vector(), 0.5 * n.length())
+}
+
+pub(crate) fn sample(p: [Vec3; 3], origin: Vec3) -> Option<LightSample> {
+    // Uniform over the area, folding the unit square onto the triangle.
+    let (mut b1, mut b2) = (random_f64(), random_f64());
+    if b1 + b2 > 1. {
+        (b1, b2) = (1. - b1, 1. - b2);
+    }
+    let point = p[0] + b1 * (p[1] - p[0]) + b2 * (p[2] - p[0]);
+    let (normal, area) = normal_and_area(p);
+    let pdf = area_to_solid_angle(point - origin, normal, area)?;
+    Some(LightSample { p: point, pdf })
+}
+
+pub(crate) fn pdf_value(p: [Vec3; 3], origin: Vec3, direction: Vec3, r_t: Interval) -> f64 {
+    match intersect(&Ray::new(origin, direction), r_t, p) {
+        Some((t, _, _)) => {
+            let (normal, area) = normal_and_area(p);
+            area_to_solid_angle(t * direction, normal, area).unwrap_or(0.)
+        }
+        None => 0.,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Triangle {
+    vertices: [Vec3; 3],
+    normals: Option<[Vec3; 3]>,
+    uvs: Option<[(f64, f64); 3]>,
+    mat: Material,
+    bbox: Aabb,
+}
+
+impl Triangle {
+    pub fn new(v0: Vec3, v1: Vec3, v2: Vec3, mat: Material) -> Self {
+        let bbox = Aabb::surrounding(Aabb::from_points(v0, v1), Aabb::from_points(v2, v2)).pad();
+        Self {
+            vertices: [v0, v1, v2],
+            normals: None,
+            uvs: None,
+            mat,
+            bbox,
+        }
+    }
+
+    pub fn with_normals(mut self, n0: Vec3, n1: Vec3, n2: Vec3) -> Self {
+        // Use per-vertex normals for smooth shading.
+        self.normals = Some([n0.unit_vector(), n1.unit_vector(), n2.unit_vector()]);
+        self
+    }
+
+    pub fn with_uvs(mut self, uv0: (f64, f64), uv1: (f64, f64), uv2: (f64, f64)) -> Self {
+        // Per-vertex texture coordinates.
+        self.uvs = Some([uv0, uv1, uv2]);
+        self
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, r: &Ray, r_t: Interval, rec: &mut HitRecord) -> bool {
+        match intersect(r, r_t, self.vertices) {
+            Some(hit) => {
+                set_hit_record(r, hit, self.vertices, self.normals, self.uvs, rec);
+                rec.mat = self.mat.clone();
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn is_light(&self) -> bool {
+        self.mat.is_emissive()
+    }
+
+    fn sample(&self, origin: Vec3) -> Option<LightSample> {
+        sample(self.vertices, origin)
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3, r_t: Interval) -> f64 {
+        pdf_value(self.vertices, origin, direction, r_t)
+    }
+}
+
+#[test]
+fn test_triangle() {
+    let mat = Material::lambertian(Vec3::zeros());
+    let tri = Triangle::new(
+        Vec3::from_xyz(0., 0., -1.),
+        Vec3::from_xyz(1., 0., -1.),
+        Vec3::from_xyz(0., 1., -1.),
+        mat,
+    );
+    let r_t = Interval::new(1e-3, f64::INFINITY);
+    let mut rec = HitRecord::new();
+
+    let r = Ray::new(Vec3::from_xyz(0.25, 0.25, 0.), Vec3::from_xyz(0., 0., -1.));
+    assert!(tri.hit(&r, r_t, &mut rec));
+    assert_eq!(rec.t, 1.);
+    assert_eq!(rec.barycentric, Vec3::from_xyz(0.5, 0.25, 0.25));
+    assert_eq!((rec.u, rec.v), (0.25, 0.25));
+    assert!(rec.front_face);
+    assert_eq!(rec.normal, Vec3::from_xyz(0., 0., 1.));
+
+    let miss = Ray::new(Vec3::from_xyz(0.75, 0.75, 0.), Vec3::from_xyz(0., 0., -1.));
+    assert!(!tri.hit(&miss, r_t, &mut rec));
+    let parallel = Ray::new(Vec3::from_xyz(0.25, 0.25, 0.), Vec3::from_xyz(1., 0., 0.));
+    assert!(!tri.hit(&parallel, r_t, &mut rec));
+
+    let n = Vec3::from_xyz(0., 1., 1.);
+    let smooth = tri.clone().with_normals(n, n, n);
+    assert!(smooth.hit(&r, r_t, &mut rec));
+    assert!((rec.normal - n.unit_vector()).near_zero());
+    assert_eq!(rec.geometric_normal, Vec3::from_xyz(0., 0., 1.));
+
+    let textured = tri.clone().with_uvs((1., 1.), (0., 1.), (1., 0.));
+    assert!(textured.hit(&r, r_t, &mut rec));
+    assert_eq!((rec.u, rec.v), (0.75, 0.75));
+    assert_eq!(rec.dpdu, Vec3::from_xyz(-1., 0., 0.));
+    assert_eq!(rec.dpdv, Vec3::from_xyz(0., -1., 0.));
+}
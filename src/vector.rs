@@ -1,4 +1,4 @@
-use rand::prelude::*;
+use crate::random;
 use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub};
 
 #[derive(Debug, Clone, Copy)]
@@ -140,51 +140,63 @@ impl Vec3 {
     }
 
     pub fn random() -> Vec3 {
-        let mut rng = rand::thread_rng();
-        let mut vec = Vec3::zeros();
-        for i in 0..3 {
-            vec.e[i] = rng.gen::<f64>();
-        }
-        vec
+        Vec3::from_xyz(
+            random::random_f64(),
+            random::random_f64(),
+            random::random_f64(),
+        )
     }
 
     pub fn random_range(min: f64, max: f64) -> Vec3 {
-        let mut rng = rand::thread_rng();
-        let mut vec = Vec3::zeros();
-        for i in 0..3 {
-            vec.e[i] = rng.gen_range(min..max);
-        }
-        vec
+        Vec3::from_xyz(
+            random::random_range(min, max),
+            random::random_range(min, max),
+            random::random_range(min, max),
+        )
     }
 
     pub fn random_in_unit_sphere() -> Vec3 {
         // Here we use a simpler way to prevent recursive
         let p = Vec3::random_range(-1., 1.);
-        let p = match p.length_squared() < 1. {
+        match p.length_squared() < 1. {
             true => p,
             false => p.unit_vector(),
             // false => Vec3::random_in_unit_sphere(),
-        };
-        p
+        }
+    }
+
+    pub fn random_unit_vector() -> Vec3 {
+        // Uniformly distributed direction on the unit sphere.
+        let z = random::random_range(-1., 1.);
+        let phi = 2. * std::f64::consts::PI * random::random_f64();
+        let r = (1. - z * z).sqrt();
+        Vec3::from_xyz(r * phi.cos(), r * phi.sin(), z)
+    }
+
+    pub fn random_cosine_direction() -> Vec3 {
+        // Cosine-weighted direction on the hemisphere around +z.
+        let r1 = random::random_f64();
+        let r2 = random::random_f64();
+        let phi = 2. * std::f64::consts::PI * r1;
+        let r = r2.sqrt();
+        Vec3::from_xyz(r * phi.cos(), r * phi.sin(), (1. - r2).sqrt())
     }
 
     pub fn random_on_hemisphere(self) -> Vec3 {
         let on_unit_sphere = Vec3::random_in_unit_sphere().unit_vector();
-        let on_unit_sphere = match on_unit_sphere.dot(self) > 0. {
+        match on_unit_sphere.dot(self) > 0. {
             true => on_unit_sphere,
             false => -on_unit_sphere,
-        };
-        on_unit_sphere
+        }
     }
 
     pub fn random_in_unit_disk() -> Vec3 {
         let mut p = Vec3::random_range(-1., 1.);
         p.e[2] = 0.;
-        let p = match p.length_squared() < 1. {
+        match p.length_squared() < 1. {
             true => p,
             false => p.unit_vector(),
-        };
-        p
+        }
     }
 }
 
@@ -0,0 +1,174 @@
+use std::fs;
+use std::path::Path;
+
+use crate::load_error::LoadError;
+use crate::perlin::Perlin;
+use crate::vector::Vec3;
+
+// Raw grids: three u32 dimensions (x, y, z), then one f32 per voxel with x varying fastest,
+// all little-endian.
+const HEADER_SIZE: usize = 12;
+
+// Dense grid of non-negative densities filling the unit cube, with samples at the voxel
+// centers.
+#[derive(Debug, Clone)]
+pub struct VoxelGrid {
+    dims: [usize; 3],
+    values: Vec<f64>,
+    max: f64, // Largest value, the majorant of the trilinear interpolation
+}
+
+impl VoxelGrid {
+    pub fn new(dims: [usize; 3], values: Vec<f64>) -> Self {
+        assert!(dims.iter().all(|&n| n > 0), "empty voxel grid");
+        assert_eq!(values.len(), dims[0] * dims[1] * dims[2]);
+        assert!(values.iter().all(|v| v.is_finite()), "non-finite voxel");
+        let values: Vec<f64> = values.into_iter().map(|v| v.max(0.)).collect();
+        let max = values.iter().copied().fold(0., f64::max);
+        Self { dims, values, max }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, LoadError> {
+        let data = fs::read(path).map_err(|err| LoadError::from(err).in_file(path))?;
+        VoxelGrid::parse(&data).map_err(|err| err.in_file(path))
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self, LoadError> {
+        if data.len() < HEADER_SIZE {
+            return Err(LoadError::at_byte(data.len(), "truncated header"));
+        }
+        let read_u32 = |k: usize| u32::from_le_bytes(data[4 * k..4 * k + 4].try_into().unwrap());
+        let dims = [0, 1, 2].map(|k| read_u32(k) as usize);
+        if dims.contains(&0) {
+            return Err(LoadError::at_byte(0, "grid dimensions must be positive"));
+        }
+        // Overflowing sizes can't match the data either.
+        let size = dims
+            .iter()
+            .try_fold(4usize, |acc, &n| acc.checked_mul(n))
+            .and_then(|n| n.checked_add(HEADER_SIZE));
+        match size {
+            Some(size) if size == data.len() => {}
+            Some(size) if size < data.len() => {
+                return Err(LoadError::at_byte(size, "unexpected data after the voxels"))
+            }
+            _ => {
+                return Err(LoadError::at_byte(
+                    data.len(),
+                    format!(
+                        "truncated voxels, expected {}x{}x{} f32 values",
+                        dims[0], dims[1], dims[2]
+                    ),
+                ))
+            }
+        }
+        // An infinite majorant would stall the free-flight sampling of the volume.
+        let values = data[HEADER_SIZE..]
+            .chunks_exact(4)
+            .enumerate()
+            .map(|(i, bytes)| {
+                let value = f32::from_le_bytes(bytes.try_into().unwrap());
+                match value.is_finite() {
+                    true => Ok(value as f64),
+                    false => Err(LoadError::at_byte(
+                        HEADER_SIZE + 4 * i,
+                        format!("voxel {i} is not finite"),
+                    )),
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(VoxelGrid::new(dims, values))
+    }
+
+    pub fn from_noise(resolution: usize, seed: u64, frequency: f64, octaves: usize) -> Self {
+        // Cloud-like puffs: fBm noise thresholded by the distance to the center, so the density
+        // fades out before reaching the sides of the cube.
+        let perlin = Perlin::new(seed);
+        let n = resolution.max(1);
+        let count = n.checked_mul(n).and_then(|m| m.checked_mul(n));
+        let mut values = Vec::with_capacity(count.expect("voxel grid too large"));
+        for k in 0..n {
+            for j in 0..n {
+                for i in 0..n {
+                    let p = Vec3::new([i, j, k].map(|c| (c as f64 + 0.5) / n as f64));
+                    let radius = (p - Vec3::from_xyz(0.5, 0.5, 0.5)).length() * 2.;
+                    let noise = perlin.fbm(p * frequency, octaves);
+                    values.push((2. * (0.8 - radius + noise)).clamp(0., 1.));
+                }
+            }
+        }
+        VoxelGrid::new([n; 3], values)
+    }
+
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+
+    pub fn density(&self, p: Vec3) -> f64 {
+        // Trilinear interpolation at `p` in the unit cube, zero outside of it.
+        if p.e.iter().any(|c| !(0. ..=1.).contains(c)) {
+            return 0.;
+        }
+        let mut index = [[0; 2]; 3];
+        let mut weight = [0.; 3];
+        for k in 0..3 {
+            let n = self.dims[k];
+            let x = (p.e[k] * n as f64 - 0.5).clamp(0., (n - 1) as f64);
+            let i = (x as usize).min(n.saturating_sub(2));
+            index[k] = [i, (i + 1).min(n - 1)];
+            weight[k] = x - i as f64;
+        }
+
+        let mut accum = 0.;
+        for dz in 0..2 {
+            for dy in 0..2 {
+                for dx in 0..2 {
+                    let [i, j, k] = [index[0][dx], index[1][dy], index[2][dz]];
+                    let value = self.values[i + self.dims[0] * (j + self.dims[1] * k)];
+                    let w = [dx, dy, dz]
+                        .iter()
+                        .zip(weight)
+                        .map(|(&d, t)| if d == 1 { t } else { 1. - t })
+                        .product::<f64>();
+                    accum += w * value;
+                }
+            }
+        }
+        accum
+    }
+}
+
+#[test]
+fn test_voxel_grid() {
+    // A 2x1x1 grid ramps from the first voxel center to the second and is clamped outside.
+    let mut data = Vec::new();
+    for n in [2u32, 1, 1] {
+        data.extend_from_slice(&n.to_le_bytes());
+    }
+    for value in [1f32, 3.] {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    let grid = VoxelGrid::parse(&data).unwrap();
+    assert_eq!(grid.max(), 3.);
+    assert_eq!(grid.density(Vec3::from_xyz(0.1, 0.5, 0.5)), 1.);
+    assert_eq!(grid.density(Vec3::from_xyz(0.5, 0.2, 0.9)), 2.);
+    assert_eq!(grid.density(Vec3::from_xyz(1., 0.5, 0.5)), 3.);
+    assert_eq!(grid.density(Vec3::from_xyz(1.1, 0.5, 0.5)), 0.);
+
+    let err = VoxelGrid::parse(&data[..16]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "byte 16: truncated voxels, expected 2x1x1 f32 values"
+    );
+    assert!(VoxelGrid::parse(&data[..8]).is_err());
+    for value in [f32::INFINITY, f32::NAN] {
+        data[16..].copy_from_slice(&value.to_le_bytes());
+        let err = VoxelGrid::parse(&data).unwrap_err();
+        assert_eq!(err.to_string(), "byte 16: voxel 1 is not finite");
+    }
+
+    // Procedural clouds stay within [0, 1] and vanish at the corners.
+    let cloud = VoxelGrid::from_noise(16, 7, 4., 4);
+    assert!(cloud.max() > 0. && cloud.max() <= 1.);
+    assert_eq!(cloud.density(Vec3::zeros()), 0.);
+}